@@ -0,0 +1,708 @@
+// asm.rs - Intel 8080 line assembler
+//
+// One line at a time, single pass. Labels defined on earlier lines are
+// remembered for the session, so multi-line entry works without forward
+// references.
+//
+// Line syntax:  [LABEL:] [MNEMONIC [operand[,operand]]] [; comment]
+//               NAME EQU value
+//
+// Directives: ORG, DB, DW, EQU
+//
+// Numbers: 42H / 0F000H (hex), 1010B (binary), 17Q / 17O (octal),
+//          42 / 42D (decimal), 'A' (char), $ (location counter).
+//          In hex-radix mode a bare number is hex (the monitor convention),
+//          so binary and decimal suffixes are not available.
+// Expressions: terms joined with + and -.
+
+use crate::registers::{Condition, PushPopPair, Register, RegisterPair};
+use std::collections::HashMap;
+
+/// Most bytes one line can produce (4-bit count in the device status)
+pub const MAX_LINE_BYTES: usize = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AsmErrorCode {
+    UnknownMnemonic = 1,
+    BadOperand = 2,     // Wrong register or register pair
+    BadNumber = 3,      // Malformed number or expression
+    Syntax = 4,         // Wrong operand count, stray characters
+    Undefined = 5,      // Label not defined yet
+    Duplicate = 6,      // Label already defined
+    Range = 7,          // Value does not fit
+    TooLong = 8,        // Line would emit more than MAX_LINE_BYTES
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AsmError {
+    pub code: AsmErrorCode,
+    pub column: usize,  // 0-based position in the line
+}
+
+impl AsmError {
+    fn new(code: AsmErrorCode, column: usize) -> Self {
+        AsmError { code, column }
+    }
+}
+
+/// Operand text with its column in the original line
+#[derive(Debug, Clone, Copy)]
+struct Operand<'a> {
+    text: &'a str,
+    column: usize,
+}
+
+pub struct Assembler {
+    symbols: HashMap<String, u16>,
+    location: u16,
+    hex_radix: bool,
+}
+
+impl Assembler {
+    pub fn new() -> Self {
+        Assembler {
+            symbols: HashMap::new(),
+            location: 0,
+            hex_radix: false,
+        }
+    }
+
+    pub fn location(&self) -> u16 {
+        self.location
+    }
+
+    pub fn set_location(&mut self, addr: u16) {
+        self.location = addr;
+    }
+
+    /// Bare numbers are hex when true, decimal when false
+    pub fn set_hex_radix(&mut self, hex: bool) {
+        self.hex_radix = hex;
+    }
+
+    pub fn symbol(&self, name: &str) -> Option<u16> {
+        self.symbols.get(&name.to_ascii_uppercase()).copied()
+    }
+
+    /// Forget labels and reset the location counter
+    pub fn clear(&mut self) {
+        self.symbols.clear();
+        self.location = 0;
+    }
+
+    /// Assemble one line at the location counter, then advance it
+    pub fn assemble_line(&mut self, line: &str) -> Result<Vec<u8>, AsmError> {
+        let code = strip_comment(line);
+        let mut rest = Operand { text: code, column: 0 };
+        let mut label = None;
+
+        // LABEL:
+        let (first, after_first) = next_word(rest);
+        if let Some(word) = first {
+            if let Some(name) = word.text.strip_suffix(':') {
+                if !is_symbol(name) {
+                    return Err(AsmError::new(AsmErrorCode::Syntax, word.column));
+                }
+                label = Some(Operand { text: name, column: word.column });
+                rest = after_first;
+            }
+        }
+
+        // NAME EQU value
+        let (first, after_first) = next_word(rest);
+        let (second, after_second) = next_word(after_first);
+        if let (Some(name), Some(word)) = (first, second) {
+            if word.text.eq_ignore_ascii_case("EQU") && label.is_none() && is_symbol(name.text) {
+                let value = self.expr(trim(after_second))?;
+                self.define(name)?;
+                self.symbols.insert(name.text.to_ascii_uppercase(), value);
+                return Ok(Vec::new());
+            }
+        }
+
+        let mnemonic = match first {
+            Some(m) => m,
+            None => {
+                if let Some(name) = label {
+                    self.define(name)?;
+                    self.symbols.insert(name.text.to_ascii_uppercase(), self.location);
+                }
+                return Ok(Vec::new());
+            }
+        };
+        let operands = split_operands(trim(after_first))?;
+        let upper = mnemonic.text.to_ascii_uppercase();
+
+        if upper == "EQU" {
+            let name = label.ok_or(AsmError::new(AsmErrorCode::Syntax, mnemonic.column))?;
+            let value = self.one_operand(&operands, mnemonic).and_then(|op| self.expr(op))?;
+            self.define(name)?;
+            self.symbols.insert(name.text.to_ascii_uppercase(), value);
+            return Ok(Vec::new());
+        }
+
+        if let Some(name) = label {
+            self.define(name)?;
+        }
+
+        let bytes = match upper.as_str() {
+            "ORG" => {
+                let addr = self.one_operand(&operands, mnemonic).and_then(|op| self.expr(op))?;
+                if let Some(name) = label {
+                    self.symbols.insert(name.text.to_ascii_uppercase(), addr);
+                }
+                self.location = addr;
+                return Ok(Vec::new());
+            }
+            _ => self.instruction(&upper, mnemonic, &operands)?,
+        };
+
+        if let Some(name) = label {
+            self.symbols.insert(name.text.to_ascii_uppercase(), self.location);
+        }
+        self.location = self.location.wrapping_add(bytes.len() as u16);
+        Ok(bytes)
+    }
+
+    fn define(&self, name: Operand) -> Result<(), AsmError> {
+        if self.symbols.contains_key(&name.text.to_ascii_uppercase()) {
+            Err(AsmError::new(AsmErrorCode::Duplicate, name.column))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn instruction(&self, upper: &str, mnemonic: Operand, ops: &[Operand]) -> Result<Vec<u8>, AsmError> {
+        let none = |opcode: u8| -> Result<Vec<u8>, AsmError> {
+            match ops.first() {
+                None => Ok(vec![opcode]),
+                Some(op) => Err(AsmError::new(AsmErrorCode::Syntax, op.column)),
+            }
+        };
+
+        match upper {
+            "NOP" => none(0x00),
+            "HLT" => none(0x76),
+            "RLC" => none(0x07),
+            "RRC" => none(0x0F),
+            "RAL" => none(0x17),
+            "RAR" => none(0x1F),
+            "DAA" => none(0x27),
+            "CMA" => none(0x2F),
+            "STC" => none(0x37),
+            "CMC" => none(0x3F),
+            "RET" => none(0xC9),
+            "XTHL" => none(0xE3),
+            "PCHL" => none(0xE9),
+            "XCHG" => none(0xEB),
+            "SPHL" => none(0xF9),
+            "DI" => none(0xF3),
+            "EI" => none(0xFB),
+
+            "MOV" => {
+                let (dst, src) = two_operands(ops, mnemonic)?;
+                let d = register(dst)?;
+                let s = register(src)?;
+                if d == Register::M && s == Register::M {
+                    return Err(AsmError::new(AsmErrorCode::BadOperand, src.column));
+                }
+                Ok(vec![0x40 | (d.to_code() << 3) | s.to_code()])
+            }
+            "MVI" => {
+                let (dst, src) = two_operands(ops, mnemonic)?;
+                let d = register(dst)?;
+                Ok(vec![0x06 | (d.to_code() << 3), self.byte(src)?])
+            }
+            "INR" | "DCR" => {
+                let r = register(self.one_operand(ops, mnemonic)?)?;
+                let base = if upper == "INR" { 0x04 } else { 0x05 };
+                Ok(vec![base | (r.to_code() << 3)])
+            }
+            "ADD" | "ADC" | "SUB" | "SBB" | "ANA" | "XRA" | "ORA" | "CMP" => {
+                let r = register(self.one_operand(ops, mnemonic)?)?;
+                let alu = ["ADD", "ADC", "SUB", "SBB", "ANA", "XRA", "ORA", "CMP"]
+                    .iter()
+                    .position(|&m| m == upper)
+                    .unwrap() as u8;
+                Ok(vec![0x80 | (alu << 3) | r.to_code()])
+            }
+            "ADI" | "ACI" | "SUI" | "SBI" | "ANI" | "XRI" | "ORI" | "CPI" => {
+                let alu = ["ADI", "ACI", "SUI", "SBI", "ANI", "XRI", "ORI", "CPI"]
+                    .iter()
+                    .position(|&m| m == upper)
+                    .unwrap() as u8;
+                let value = self.byte(self.one_operand(ops, mnemonic)?)?;
+                Ok(vec![0xC6 | (alu << 3), value])
+            }
+            "IN" | "OUT" => {
+                let port = self.byte(self.one_operand(ops, mnemonic)?)?;
+                Ok(vec![if upper == "IN" { 0xDB } else { 0xD3 }, port])
+            }
+            "LXI" => {
+                let (dst, src) = two_operands(ops, mnemonic)?;
+                let rp = register_pair(dst)?;
+                let value = self.expr(src)?;
+                Ok(vec![0x01 | (rp.to_code() << 4), value as u8, (value >> 8) as u8])
+            }
+            "DAD" | "INX" | "DCX" => {
+                let rp = register_pair(self.one_operand(ops, mnemonic)?)?;
+                let base = match upper {
+                    "DAD" => 0x09,
+                    "INX" => 0x03,
+                    _ => 0x0B,
+                };
+                Ok(vec![base | (rp.to_code() << 4)])
+            }
+            "PUSH" | "POP" => {
+                let pp = push_pop_pair(self.one_operand(ops, mnemonic)?)?;
+                let base = if upper == "PUSH" { 0xC5 } else { 0xC1 };
+                Ok(vec![base | (pp.to_code() << 4)])
+            }
+            "STAX" | "LDAX" => {
+                let op = self.one_operand(ops, mnemonic)?;
+                let rp = register_pair(op)?;
+                if !rp.supports_indirect() {
+                    return Err(AsmError::new(AsmErrorCode::BadOperand, op.column));
+                }
+                let base = if upper == "STAX" { 0x02 } else { 0x0A };
+                Ok(vec![base | (rp.to_code() << 4)])
+            }
+            "JMP" | "CALL" | "SHLD" | "LHLD" | "STA" | "LDA" => {
+                let opcode = match upper {
+                    "JMP" => 0xC3,
+                    "CALL" => 0xCD,
+                    "SHLD" => 0x22,
+                    "LHLD" => 0x2A,
+                    "STA" => 0x32,
+                    _ => 0x3A,
+                };
+                let addr = self.expr(self.one_operand(ops, mnemonic)?)?;
+                Ok(vec![opcode, addr as u8, (addr >> 8) as u8])
+            }
+            "RST" => {
+                let op = self.one_operand(ops, mnemonic)?;
+                let n = self.expr(op)?;
+                if n > 7 {
+                    return Err(AsmError::new(AsmErrorCode::Range, op.column));
+                }
+                Ok(vec![0xC7 | ((n as u8) << 3)])
+            }
+            "DB" => {
+                if ops.is_empty() {
+                    return Err(AsmError::new(AsmErrorCode::Syntax, mnemonic.column));
+                }
+                let mut bytes = Vec::new();
+                for op in ops {
+                    match string_literal(op.text) {
+                        Some(s) if s.len() != 1 => bytes.extend_from_slice(s.as_bytes()),
+                        _ => bytes.push(self.byte(*op)?),
+                    }
+                    if bytes.len() > MAX_LINE_BYTES {
+                        return Err(AsmError::new(AsmErrorCode::TooLong, op.column));
+                    }
+                }
+                Ok(bytes)
+            }
+            "DW" => {
+                if ops.is_empty() {
+                    return Err(AsmError::new(AsmErrorCode::Syntax, mnemonic.column));
+                }
+                let mut bytes = Vec::new();
+                for op in ops {
+                    let value = self.expr(*op)?;
+                    bytes.push(value as u8);
+                    bytes.push((value >> 8) as u8);
+                    if bytes.len() > MAX_LINE_BYTES {
+                        return Err(AsmError::new(AsmErrorCode::TooLong, op.column));
+                    }
+                }
+                Ok(bytes)
+            }
+            _ => {
+                // Jcc / Ccc / Rcc
+                let cond = upper.get(1..).and_then(condition);
+                match (upper.get(..1), cond) {
+                    (Some("J"), Some(cond)) | (Some("C"), Some(cond)) => {
+                        let base = if upper.starts_with('J') { 0xC2 } else { 0xC4 };
+                        let addr = self.expr(self.one_operand(ops, mnemonic)?)?;
+                        Ok(vec![base | (cond.to_code() << 3), addr as u8, (addr >> 8) as u8])
+                    }
+                    (Some("R"), Some(cond)) => none(0xC0 | (cond.to_code() << 3)),
+                    _ => Err(AsmError::new(AsmErrorCode::UnknownMnemonic, mnemonic.column)),
+                }
+            }
+        }
+    }
+
+    fn one_operand<'a>(&self, ops: &[Operand<'a>], mnemonic: Operand) -> Result<Operand<'a>, AsmError> {
+        match ops {
+            [op] => Ok(*op),
+            [] => Err(AsmError::new(AsmErrorCode::Syntax, mnemonic.column)),
+            [_, extra, ..] => Err(AsmError::new(AsmErrorCode::Syntax, extra.column)),
+        }
+    }
+
+    /// 8-bit value; accepts -128..=255
+    fn byte(&self, op: Operand) -> Result<u8, AsmError> {
+        let value = self.expr_i32(op)?;
+        if !(-128..=255).contains(&value) {
+            return Err(AsmError::new(AsmErrorCode::Range, op.column));
+        }
+        Ok(value as u8)
+    }
+
+    /// 16-bit value; accepts -32768..=65535
+    fn expr(&self, op: Operand) -> Result<u16, AsmError> {
+        let value = self.expr_i32(op)?;
+        if !(-32768..=65535).contains(&value) {
+            return Err(AsmError::new(AsmErrorCode::Range, op.column));
+        }
+        Ok(value as u16)
+    }
+
+    fn expr_i32(&self, op: Operand) -> Result<i32, AsmError> {
+        let text = op.text;
+        if text.is_empty() {
+            return Err(AsmError::new(AsmErrorCode::BadNumber, op.column));
+        }
+        let bytes = text.as_bytes();
+        let mut total: i32 = 0;
+        let mut sign = 1;
+        let mut i = 0;
+        loop {
+            // Optional unary sign
+            while i < bytes.len() && (bytes[i] == b'+' || bytes[i] == b'-' || bytes[i] == b' ') {
+                if bytes[i] == b'-' {
+                    sign = -sign;
+                }
+                i += 1;
+            }
+            let start = i;
+            if i < bytes.len() && bytes[i] == b'\'' {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'\'' {
+                    i += 1;
+                }
+                i += 1;
+            } else {
+                while i < bytes.len() && bytes[i] != b'+' && bytes[i] != b'-' && bytes[i] != b' ' {
+                    i += 1;
+                }
+            }
+            let term = Operand { text: &text[start..i.min(text.len())], column: op.column + start };
+            total = total.wrapping_add(sign * self.term(term)?);
+
+            while i < bytes.len() && bytes[i] == b' ' {
+                i += 1;
+            }
+            if i >= bytes.len() {
+                return Ok(total);
+            }
+            sign = 1;
+        }
+    }
+
+    fn term(&self, op: Operand) -> Result<i32, AsmError> {
+        let text = op.text;
+        let err = |code| AsmError::new(code, op.column);
+
+        if text.is_empty() {
+            return Err(err(AsmErrorCode::BadNumber));
+        }
+        if text == "$" {
+            return Ok(self.location as i32);
+        }
+        if let Some(s) = string_literal(text) {
+            let mut chars = s.bytes();
+            return match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(c as i32),
+                _ => Err(err(AsmErrorCode::BadNumber)),
+            };
+        }
+        if text.as_bytes()[0].is_ascii_digit() {
+            return parse_number(text, self.hex_radix).ok_or(err(AsmErrorCode::BadNumber));
+        }
+        if is_symbol(text) {
+            return self.symbol(text)
+                .map(|v| v as i32)
+                .ok_or(err(AsmErrorCode::Undefined));
+        }
+        Err(err(AsmErrorCode::BadNumber))
+    }
+}
+
+impl Default for Assembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn parse_number(text: &str, hex_radix: bool) -> Option<i32> {
+    let upper = text.to_ascii_uppercase();
+    let (digits, radix) = if let Some(d) = upper.strip_suffix('H') {
+        (d, 16)
+    } else if hex_radix {
+        (upper.as_str(), 16)
+    } else if let Some(d) = upper.strip_suffix('B') {
+        (d, 2)
+    } else if let Some(d) = upper.strip_suffix('Q').or_else(|| upper.strip_suffix('O')) {
+        (d, 8)
+    } else if let Some(d) = upper.strip_suffix('D') {
+        (d, 10)
+    } else {
+        (upper.as_str(), 10)
+    };
+    if digits.is_empty() {
+        return None;
+    }
+    i32::from_str_radix(digits, radix).ok().filter(|&v| v <= 0xFFFF)
+}
+
+fn is_symbol(text: &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '?' || c == '@' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '?' || c == '@')
+}
+
+fn string_literal(text: &str) -> Option<&str> {
+    if text.len() >= 2 && text.starts_with('\'') && text.ends_with('\'') {
+        Some(&text[1..text.len() - 1])
+    } else {
+        None
+    }
+}
+
+fn register(op: Operand) -> Result<Register, AsmError> {
+    let code = match op.text.to_ascii_uppercase().as_str() {
+        "B" => 0,
+        "C" => 1,
+        "D" => 2,
+        "E" => 3,
+        "H" => 4,
+        "L" => 5,
+        "M" => 6,
+        "A" => 7,
+        _ => return Err(AsmError::new(AsmErrorCode::BadOperand, op.column)),
+    };
+    Ok(Register::from_code(code))
+}
+
+fn register_pair(op: Operand) -> Result<RegisterPair, AsmError> {
+    let code = match op.text.to_ascii_uppercase().as_str() {
+        "B" => 0,
+        "D" => 1,
+        "H" => 2,
+        "SP" => 3,
+        _ => return Err(AsmError::new(AsmErrorCode::BadOperand, op.column)),
+    };
+    Ok(RegisterPair::from_code(code))
+}
+
+fn push_pop_pair(op: Operand) -> Result<PushPopPair, AsmError> {
+    let code = match op.text.to_ascii_uppercase().as_str() {
+        "B" => 0,
+        "D" => 1,
+        "H" => 2,
+        "PSW" => 3,
+        _ => return Err(AsmError::new(AsmErrorCode::BadOperand, op.column)),
+    };
+    Ok(PushPopPair::from_code(code))
+}
+
+fn condition(text: &str) -> Option<Condition> {
+    let code = match text {
+        "NZ" => 0,
+        "Z" => 1,
+        "NC" => 2,
+        "C" => 3,
+        "PO" => 4,
+        "PE" => 5,
+        "P" => 6,
+        "M" => 7,
+        _ => return None,
+    };
+    Some(Condition::from_code(code))
+}
+
+fn two_operands<'a>(ops: &[Operand<'a>], mnemonic: Operand) -> Result<(Operand<'a>, Operand<'a>), AsmError> {
+    match ops {
+        [a, b] => Ok((*a, *b)),
+        [_, _, extra, ..] => Err(AsmError::new(AsmErrorCode::Syntax, extra.column)),
+        _ => Err(AsmError::new(AsmErrorCode::Syntax, mnemonic.column)),
+    }
+}
+
+/// Drop a trailing ; comment (but not a ; inside quotes)
+fn strip_comment(line: &str) -> &str {
+    let mut in_quote = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '\'' => in_quote = !in_quote,
+            ';' if !in_quote => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn trim(op: Operand) -> Operand {
+    let lead = op.text.len() - op.text.trim_start().len();
+    Operand { text: op.text.trim(), column: op.column + lead }
+}
+
+/// Split off the first whitespace-delimited word
+fn next_word(op: Operand) -> (Option<Operand>, Operand) {
+    let op = trim(op);
+    if op.text.is_empty() {
+        return (None, op);
+    }
+    let end = op.text.find(char::is_whitespace).unwrap_or(op.text.len());
+    let word = Operand { text: &op.text[..end], column: op.column };
+    let rest = Operand { text: &op.text[end..], column: op.column + end };
+    (Some(word), rest)
+}
+
+/// Split operands on commas outside quotes
+fn split_operands(op: Operand) -> Result<Vec<Operand>, AsmError> {
+    let mut ops = Vec::new();
+    if op.text.is_empty() {
+        return Ok(ops);
+    }
+    let mut in_quote = false;
+    let mut start = 0;
+    for (i, c) in op.text.char_indices() {
+        match c {
+            '\'' => in_quote = !in_quote,
+            ',' if !in_quote => {
+                ops.push(trim(Operand { text: &op.text[start..i], column: op.column + start }));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if in_quote {
+        return Err(AsmError::new(AsmErrorCode::Syntax, op.column + op.text.len()));
+    }
+    ops.push(trim(Operand { text: &op.text[start..], column: op.column + start }));
+    if let Some(empty) = ops.iter().find(|o| o.text.is_empty()) {
+        return Err(AsmError::new(AsmErrorCode::Syntax, empty.column));
+    }
+    Ok(ops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm;
+
+    fn asm(line: &str) -> Result<Vec<u8>, AsmError> {
+        Assembler::new().assemble_line(line)
+    }
+
+    #[test]
+    fn test_basic_instructions() {
+        assert_eq!(asm("MVI A,42H"), Ok(vec![0x3E, 0x42]));
+        assert_eq!(asm("  mov a, m"), Ok(vec![0x7E]));
+        assert_eq!(asm("LXI SP,0F000H"), Ok(vec![0x31, 0x00, 0xF0]));
+        assert_eq!(asm("PUSH PSW"), Ok(vec![0xF5]));
+        assert_eq!(asm("JNZ 1234H ; loop"), Ok(vec![0xC2, 0x34, 0x12]));
+        assert_eq!(asm("RZ"), Ok(vec![0xC8]));
+        assert_eq!(asm("CPI 'A'"), Ok(vec![0xFE, 0x41]));
+        assert_eq!(asm("RST 7"), Ok(vec![0xFF]));
+    }
+
+    #[test]
+    fn test_number_formats() {
+        assert_eq!(asm("MVI B,10"), Ok(vec![0x06, 10]));
+        assert_eq!(asm("MVI B,1010B"), Ok(vec![0x06, 0x0A]));
+        assert_eq!(asm("MVI B,17Q"), Ok(vec![0x06, 0x0F]));
+        assert_eq!(asm("MVI B,-1"), Ok(vec![0x06, 0xFF]));
+        assert_eq!(asm("LXI H,1000H+2"), Ok(vec![0x21, 0x02, 0x10]));
+    }
+
+    #[test]
+    fn test_hex_radix() {
+        let mut a = Assembler::new();
+        a.set_hex_radix(true);
+        assert_eq!(a.assemble_line("MVI A,42"), Ok(vec![0x3E, 0x42]));
+        assert_eq!(a.assemble_line("JMP 0F000"), Ok(vec![0xC3, 0x00, 0xF0]));
+    }
+
+    #[test]
+    fn test_location_counter_and_labels() {
+        let mut a = Assembler::new();
+        a.set_location(0x1000);
+
+        assert_eq!(a.assemble_line("LOOP: DCR B"), Ok(vec![0x05]));
+        assert_eq!(a.location(), 0x1001);
+        assert_eq!(a.assemble_line("JNZ LOOP"), Ok(vec![0xC2, 0x00, 0x10]));
+        assert_eq!(a.assemble_line("JMP $"), Ok(vec![0xC3, 0x04, 0x10]));
+        assert_eq!(a.location(), 0x1007);
+        assert_eq!(a.symbol("loop"), Some(0x1000));
+    }
+
+    #[test]
+    fn test_directives() {
+        let mut a = Assembler::new();
+        assert_eq!(a.assemble_line("ORG 2000H"), Ok(vec![]));
+        assert_eq!(a.location(), 0x2000);
+        assert_eq!(a.assemble_line("MSG: DB 'HI',0DH,0"), Ok(vec![b'H', b'I', 0x0D, 0x00]));
+        assert_eq!(a.assemble_line("DW MSG,1234H"), Ok(vec![0x00, 0x20, 0x34, 0x12]));
+        assert_eq!(a.assemble_line("COUNT EQU 5"), Ok(vec![]));
+        assert_eq!(a.assemble_line("MVI C,COUNT"), Ok(vec![0x0E, 0x05]));
+    }
+
+    #[test]
+    fn test_label_only_line() {
+        let mut a = Assembler::new();
+        a.set_location(0x0100);
+        assert_eq!(a.assemble_line("START:"), Ok(vec![]));
+        assert_eq!(a.symbol("START"), Some(0x0100));
+        assert_eq!(a.assemble_line(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_errors_and_positions() {
+        let e = |code, column| Err(AsmError::new(code, column));
+        assert_eq!(asm("FOO A"), e(AsmErrorCode::UnknownMnemonic, 0));
+        assert_eq!(asm("MOV A,X"), e(AsmErrorCode::BadOperand, 6));
+        assert_eq!(asm("MVI A,12G"), e(AsmErrorCode::BadNumber, 6));
+        assert_eq!(asm("MVI A"), e(AsmErrorCode::Syntax, 0));
+        assert_eq!(asm("JMP NOWHERE"), e(AsmErrorCode::Undefined, 4));
+        assert_eq!(asm("MVI A,100H"), e(AsmErrorCode::Range, 6));
+        assert_eq!(asm("STAX H"), e(AsmErrorCode::BadOperand, 5));
+        assert_eq!(asm("DB '0123456789ABCDEF'"), e(AsmErrorCode::TooLong, 3));
+    }
+
+    #[test]
+    fn test_failed_line_leaves_state() {
+        let mut a = Assembler::new();
+        a.set_location(0x1000);
+        assert!(a.assemble_line("X: MVI A,BAD").is_err());
+        assert_eq!(a.location(), 0x1000);
+        assert_eq!(a.symbol("X"), None);
+
+        assert_eq!(a.assemble_line("X: NOP"), Ok(vec![0x00]));
+        assert_eq!(
+            a.assemble_line("X: NOP"),
+            Err(AsmError::new(AsmErrorCode::Duplicate, 0))
+        );
+    }
+
+    #[test]
+    fn test_round_trip_all_opcodes() {
+        for op in 0..=255u8 {
+            let bytes = [op, 0x34, 0x12];
+            let (text, len) = disasm::disassemble(&bytes).unwrap();
+            assert_eq!(
+                asm(&text),
+                Ok(bytes[..len as usize].to_vec()),
+                "round trip of {:02X} ({})", op, text
+            );
+        }
+    }
+}
@@ -0,0 +1,789 @@
+// cpm.rs - CP/M BDOS trap harness for .COM programs
+//
+// Runs CP/M programs (TST8080, 8080PRE, CPUTEST, 8080EXM, small tools)
+// on the bare CPU, without the monitor ROM.
+//
+// Memory layout:
+//   0x0000: JMP BIOS warm boot  - reaching 0x0000 ends the run
+//   0x0005: JMP BDOS            - calls are trapped in Rust
+//   0x005C: Default FCB (command tail file name)
+//   0x0080: Default DMA buffer / command tail
+//   0x0100: Program (TPA)
+//   0xFE00: BDOS entry (RET, never executed)
+//   0xFF00: BIOS (RETs)
+//
+// BDOS functions:
+//   0 reset, 1 console in, 2 console out, 6 direct console I/O,
+//   9 print string, 10 read buffer, 11 console status, 12 version,
+//   13 reset disks, 14 select disk, 15 open, 16 close, 17/18 search,
+//   19 delete, 20 read seq, 21 write seq, 22 make, 23 rename,
+//   25 current disk, 26 set DMA, 32 user code, 33/34 read/write random,
+//   35 file size, 36 set random record.
+//   Anything else returns 0.
+//
+// Files live in one host directory; drive letters and user numbers are
+// ignored. Names are matched case-insensitively; a name with path
+// separators, dots, wildcards or control characters fails as if the file
+// were missing, so programs can't reach outside. There are no handles:
+// every read or write opens the host file named in the FCB, so the FCB's
+// record fields are the only state.
+
+use crate::cpu::Intel8080;
+use std::collections::VecDeque;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+pub const TPA: u16 = 0x0100;
+pub const BDOS_ENTRY: u16 = 0xFE00;
+pub const BIOS_BASE: u16 = 0xFF00;
+const DEFAULT_DMA: u16 = 0x0080;
+const DEFAULT_FCB: u16 = 0x005C;
+const RECORD: usize = 128;
+
+/// Why a run stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpmExit {
+    WarmBoot,           // Jumped to 0x0000 or called BDOS 0
+    Halted,             // HLT
+    InstructionLimit,
+}
+
+pub struct CpmHarness {
+    cpu: Intel8080,
+    dir: PathBuf,
+    dma: u16,
+    output: Vec<u8>,
+    echo: bool,
+    input: Box<dyn Read>,
+    peeked: Option<u8>,
+    search: VecDeque<String>,
+}
+
+impl CpmHarness {
+    /// New machine with files in `dir`
+    pub fn new(dir: &Path) -> Self {
+        let mut cpu = Intel8080::new();
+        // 0x0000: JMP BIOS+3 (warm boot), 0x0005: JMP BDOS
+        cpu.load_program(&[0xC3, 0x03, (BIOS_BASE >> 8) as u8], 0x0000);
+        cpu.load_program(&[0xC3, BDOS_ENTRY as u8, (BDOS_ENTRY >> 8) as u8], 0x0005);
+        cpu.load_program(&[0xC9], BDOS_ENTRY);
+        cpu.load_program(&[0xC9; 0x33], BIOS_BASE);
+        CpmHarness {
+            cpu,
+            dir: dir.to_path_buf(),
+            dma: DEFAULT_DMA,
+            output: Vec::new(),
+            echo: false,
+            input: Box::new(io::empty()),
+            peeked: None,
+            search: VecDeque::new(),
+        }
+    }
+
+    /// Load a program at 0x0100 with a command tail, and point the CPU at it
+    pub fn load_com(&mut self, program: &[u8], tail: &str) {
+        self.cpu.load_program(program, TPA);
+
+        // Command tail at 0x0080: length, then the text (CCP uppercases it)
+        let tail = tail.trim().to_ascii_uppercase();
+        let tail = if tail.is_empty() { String::new() } else { format!(" {}", tail) };
+        let bytes = &tail.as_bytes()[..tail.len().min(127)];
+        self.cpu.load_program(&[bytes.len() as u8], DEFAULT_DMA);
+        self.cpu.load_program(bytes, DEFAULT_DMA + 1);
+        self.cpu.load_program(&[0x00], DEFAULT_DMA + 1 + bytes.len() as u16);
+
+        // First argument parsed into the default FCB
+        let first = tail.split_whitespace().next().unwrap_or("");
+        self.cpu.load_program(&make_fcb(first), DEFAULT_FCB);
+
+        // CCP leaves a return address to 0x0000 on the stack
+        self.cpu.sp = BDOS_ENTRY;
+        self.push(0x0000);
+        self.cpu.pc = TPA;
+    }
+
+    pub fn load_com_file(&mut self, path: &Path, tail: &str) -> io::Result<usize> {
+        let program = fs::read(path)?;
+        self.load_com(&program, tail);
+        Ok(program.len())
+    }
+
+    /// Where console input comes from (stdin, a script...)
+    pub fn set_input(&mut self, input: Box<dyn Read>) {
+        self.input = input;
+        self.peeked = None;
+    }
+
+    /// Copy console output to stdout as well as capturing it
+    pub fn set_echo(&mut self, echo: bool) {
+        self.echo = echo;
+    }
+
+    /// Everything the program has printed
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    pub fn cpu(&mut self) -> &mut Intel8080 {
+        &mut self.cpu
+    }
+
+    /// Run until warm boot, HLT, or `limit` instructions (if given)
+    pub fn run(&mut self, limit: Option<u64>) -> CpmExit {
+        let mut count = 0u64;
+        loop {
+            match self.cpu.pc {
+                0x0000 => return CpmExit::WarmBoot,
+                0x0005 => {
+                    if !self.bdos() {
+                        return CpmExit::WarmBoot;
+                    }
+                    let ret = self.pop();
+                    self.cpu.pc = ret;
+                    continue;
+                }
+                _ => {}
+            }
+            if self.cpu.halted {
+                return CpmExit::Halted;
+            }
+            if limit.is_some_and(|l| count >= l) {
+                return CpmExit::InstructionLimit;
+            }
+            self.cpu.execute_one();
+            count += 1;
+        }
+    }
+
+    fn push(&mut self, value: u16) {
+        self.cpu.sp = self.cpu.sp.wrapping_sub(2);
+        self.cpu.write_word(self.cpu.sp, value);
+    }
+
+    fn pop(&mut self) -> u16 {
+        let value = self.cpu.read_word(self.cpu.sp);
+        self.cpu.sp = self.cpu.sp.wrapping_add(2);
+        value
+    }
+
+    fn conout(&mut self, c: u8) {
+        self.output.push(c);
+        if self.echo {
+            let mut out = io::stdout();
+            let _ = out.write_all(&[c]);
+            let _ = out.flush();
+        }
+    }
+
+    /// Next input byte; 0x1A (^Z) once input runs out
+    fn conin(&mut self) -> u8 {
+        if let Some(c) = self.peeked.take() {
+            return c;
+        }
+        let mut buf = [0u8; 1];
+        match self.input.read(&mut buf) {
+            Ok(1) => buf[0],
+            _ => 0x1A,
+        }
+    }
+
+    /// Handle the call in C. Returns false for a system reset.
+    fn bdos(&mut self) -> bool {
+        let de = self.cpu.get_de();
+        let e = self.cpu.e;
+        let result: u16 = match self.cpu.c {
+            0 => return false,
+            1 => {
+                let c = self.conin();
+                self.conout(c);
+                c as u16
+            }
+            2 => {
+                self.conout(e);
+                0
+            }
+            6 if e == 0xFF => {
+                let c = self.conin();
+                if c == 0x1A { 0 } else { c as u16 }
+            }
+            6 if e == 0xFE => 0,
+            6 => {
+                self.conout(e);
+                0
+            }
+            9 => {
+                let mut addr = de;
+                loop {
+                    let c = self.cpu.read_byte(addr);
+                    if c == b'$' {
+                        break;
+                    }
+                    self.conout(c);
+                    addr = addr.wrapping_add(1);
+                }
+                0
+            }
+            10 => {
+                self.read_buffer(de);
+                0
+            }
+            11 => {
+                if self.peeked.is_none() {
+                    self.peeked = Some(self.conin()).filter(|&c| c != 0x1A);
+                }
+                if self.peeked.is_some() { 0xFF } else { 0 }
+            }
+            12 => 0x0022,                   // CP/M 2.2
+            13 => {
+                self.dma = DEFAULT_DMA;
+                0
+            }
+            15 => self.open_file(de),
+            17 => self.search_first(de),
+            18 => self.search_next(),
+            19 => self.delete_file(de),
+            20 => self.read_sequential(de),
+            21 => self.write_sequential(de),
+            22 => self.make_file(de),
+            23 => self.rename_file(de),
+            26 => {
+                self.dma = de;
+                0
+            }
+            33 => self.read_random(de),
+            34 => self.write_random(de),
+            35 => self.file_size(de),
+            36 => {
+                let record = self.fcb_sequential_record(de);
+                self.set_random_record(de, record);
+                0
+            }
+            _ => 0,                         // 14, 16, 25, 32 and the rest
+        };
+        // Results come back in HL, with A = L and B = H
+        self.cpu.set_hl(result);
+        self.cpu.a = result as u8;
+        self.cpu.b = (result >> 8) as u8;
+        true
+    }
+
+    fn read_buffer(&mut self, addr: u16) {
+        let max = self.cpu.read_byte(addr) as usize;
+        let mut line = Vec::new();
+        while line.len() < max {
+            match self.conin() {
+                b'\r' | b'\n' | 0x1A => break,
+                c => {
+                    self.conout(c);
+                    line.push(c);
+                }
+            }
+        }
+        self.conout(b'\r');
+        self.conout(b'\n');
+        self.cpu.write_byte(addr.wrapping_add(1), line.len() as u8);
+        for (i, &c) in line.iter().enumerate() {
+            self.cpu.write_byte(addr.wrapping_add(2 + i as u16), c);
+        }
+    }
+
+    // ===== FILES =====
+
+    /// Host name for the FCB at `fcb` (or the rename target 16 bytes on);
+    /// None if it isn't a plain CP/M name that stays in the directory
+    fn fcb_name(&mut self, fcb: u16) -> Option<String> {
+        let field = |cpu: &mut Intel8080, start: u16, len: u16| -> String {
+            (0..len)
+                .map(|i| (cpu.read_byte(fcb.wrapping_add(start + i)) & 0x7F) as char)
+                .collect::<String>()
+                .trim_end()
+                .to_string()
+        };
+        let name = field(&mut self.cpu, 1, 8);
+        let ext = field(&mut self.cpu, 9, 3);
+        let plain = |part: &str| part.chars().all(|c| c.is_ascii_graphic() && !"/\\.:*?<>|\"".contains(c));
+        if name.is_empty() || !plain(&name) || !plain(&ext) {
+            return None;
+        }
+        Some(if ext.is_empty() { name } else { format!("{}.{}", name, ext) })
+    }
+
+    /// Host path for a CP/M name, matching existing files case-insensitively
+    fn host_path(&self, name: &str) -> PathBuf {
+        let found = fs::read_dir(&self.dir).ok().and_then(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .find(|f| f.eq_ignore_ascii_case(name))
+        });
+        self.dir.join(found.unwrap_or_else(|| name.to_string()))
+    }
+
+    fn fcb_path(&mut self, fcb: u16) -> Option<PathBuf> {
+        let name = self.fcb_name(fcb)?;
+        Some(self.host_path(&name))
+    }
+
+    fn clear_extent(&mut self, fcb: u16) {
+        for offset in [12, 13, 14, 32] {
+            self.cpu.write_byte(fcb.wrapping_add(offset), 0);
+        }
+    }
+
+    fn open_file(&mut self, fcb: u16) -> u16 {
+        if self.fcb_path(fcb).is_some_and(|path| path.is_file()) {
+            self.clear_extent(fcb);
+            0
+        } else {
+            0xFF
+        }
+    }
+
+    fn make_file(&mut self, fcb: u16) -> u16 {
+        let Some(path) = self.fcb_path(fcb) else { return 0xFF };
+        match fs::File::create(path) {
+            Ok(_) => {
+                self.clear_extent(fcb);
+                0
+            }
+            Err(_) => 0xFF,
+        }
+    }
+
+    fn delete_file(&mut self, fcb: u16) -> u16 {
+        let pattern = self.fcb_pattern(fcb);
+        let matches = self.matching_files(&pattern);
+        if matches.is_empty() {
+            return 0xFF;
+        }
+        for name in matches {
+            let _ = fs::remove_file(self.dir.join(name));
+        }
+        0
+    }
+
+    fn rename_file(&mut self, fcb: u16) -> u16 {
+        let (Some(from), Some(to)) = (self.fcb_path(fcb), self.fcb_name(fcb.wrapping_add(16))) else {
+            return 0xFF;
+        };
+        if !from.is_file() {
+            return 0xFF;
+        }
+        match fs::rename(from, self.dir.join(to)) {
+            Ok(()) => 0,
+            Err(_) => 0xFF,
+        }
+    }
+
+    /// 11-char name pattern with '?' wildcards
+    fn fcb_pattern(&mut self, fcb: u16) -> Vec<u8> {
+        (1..12).map(|i| self.cpu.read_byte(fcb.wrapping_add(i)) & 0x7F).collect()
+    }
+
+    fn matching_files(&self, pattern: &[u8]) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(&self.dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .filter(|e| e.path().is_file())
+                    .map(|e| e.file_name().to_string_lossy().to_string())
+                    .filter(|name| {
+                        fcb_field(name).is_some_and(|field| {
+                            field.iter().zip(pattern).all(|(&c, &p)| p == b'?' || p == c)
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+
+    fn search_first(&mut self, fcb: u16) -> u16 {
+        let pattern = self.fcb_pattern(fcb);
+        self.search = self.matching_files(&pattern).into();
+        self.search_next()
+    }
+
+    /// Directory entry for the next match goes in slot 0 of the DMA buffer
+    fn search_next(&mut self) -> u16 {
+        let name = match self.search.pop_front() {
+            Some(name) => name,
+            None => return 0xFF,
+        };
+        let field = fcb_field(&name).unwrap_or([b' '; 11]);
+        let records = fs::metadata(self.dir.join(&name))
+            .map(|m| m.len().div_ceil(RECORD as u64))
+            .unwrap_or(0);
+        self.cpu.write_byte(self.dma, 0);
+        for (i, &c) in field.iter().enumerate() {
+            self.cpu.write_byte(self.dma.wrapping_add(1 + i as u16), c);
+        }
+        self.cpu.write_byte(self.dma.wrapping_add(12), 0);
+        self.cpu.write_byte(self.dma.wrapping_add(13), 0);
+        self.cpu.write_byte(self.dma.wrapping_add(14), 0);
+        self.cpu.write_byte(self.dma.wrapping_add(15), records.min(0x80) as u8);
+        for i in 16..32 {
+            self.cpu.write_byte(self.dma.wrapping_add(i), 0);
+        }
+        0
+    }
+
+    fn fcb_sequential_record(&mut self, fcb: u16) -> u32 {
+        let extent = self.cpu.read_byte(fcb.wrapping_add(12)) as u32;
+        let current = self.cpu.read_byte(fcb.wrapping_add(32)) as u32;
+        extent * 128 + current
+    }
+
+    fn set_sequential_record(&mut self, fcb: u16, record: u32) {
+        self.cpu.write_byte(fcb.wrapping_add(12), (record / 128) as u8);
+        self.cpu.write_byte(fcb.wrapping_add(32), (record % 128) as u8);
+    }
+
+    fn random_record(&mut self, fcb: u16) -> u32 {
+        let r0 = self.cpu.read_byte(fcb.wrapping_add(33)) as u32;
+        let r1 = self.cpu.read_byte(fcb.wrapping_add(34)) as u32;
+        r0 | (r1 << 8)
+    }
+
+    fn set_random_record(&mut self, fcb: u16, record: u32) {
+        self.cpu.write_byte(fcb.wrapping_add(33), record as u8);
+        self.cpu.write_byte(fcb.wrapping_add(34), (record >> 8) as u8);
+        self.cpu.write_byte(fcb.wrapping_add(35), (record >> 16) as u8);
+    }
+
+    /// Read record `record` into the DMA buffer: 0 OK, 1 past end of file
+    fn read_record(&mut self, fcb: u16, record: u32) -> u16 {
+        let Some(path) = self.fcb_path(fcb) else { return 1 };
+        let mut buf = [0x1Au8; RECORD];
+        let n = fs::File::open(path).and_then(|mut f| {
+            f.seek(SeekFrom::Start(record as u64 * RECORD as u64))?;
+            let mut total = 0;
+            while total < RECORD {
+                match f.read(&mut buf[total..])? {
+                    0 => break,
+                    n => total += n,
+                }
+            }
+            Ok(total)
+        });
+        match n {
+            Ok(0) | Err(_) => 1,
+            Ok(_) => {
+                for (i, &b) in buf.iter().enumerate() {
+                    self.cpu.write_byte(self.dma.wrapping_add(i as u16), b);
+                }
+                0
+            }
+        }
+    }
+
+    /// Write the DMA buffer to record `record`: 0 OK, 2 disk full (host error)
+    fn write_record(&mut self, fcb: u16, record: u32) -> u16 {
+        let Some(path) = self.fcb_path(fcb) else { return 2 };
+        let buf: Vec<u8> = (0..RECORD as u16).map(|i| self.cpu.read_byte(self.dma.wrapping_add(i))).collect();
+        let result = OpenOptions::new().write(true).create(true).truncate(false).open(path).and_then(|mut f| {
+            f.seek(SeekFrom::Start(record as u64 * RECORD as u64))?;
+            f.write_all(&buf)
+        });
+        if result.is_ok() { 0 } else { 2 }
+    }
+
+    fn read_sequential(&mut self, fcb: u16) -> u16 {
+        let record = self.fcb_sequential_record(fcb);
+        let result = self.read_record(fcb, record);
+        if result == 0 {
+            self.set_sequential_record(fcb, record + 1);
+        }
+        result
+    }
+
+    fn write_sequential(&mut self, fcb: u16) -> u16 {
+        let record = self.fcb_sequential_record(fcb);
+        let result = self.write_record(fcb, record);
+        if result == 0 {
+            self.set_sequential_record(fcb, record + 1);
+        }
+        result
+    }
+
+    /// Random access also sets the sequential position, as CP/M does
+    fn read_random(&mut self, fcb: u16) -> u16 {
+        let record = self.random_record(fcb);
+        let result = self.read_record(fcb, record);
+        self.set_sequential_record(fcb, record);
+        result
+    }
+
+    fn write_random(&mut self, fcb: u16) -> u16 {
+        let record = self.random_record(fcb);
+        let result = self.write_record(fcb, record);
+        self.set_sequential_record(fcb, record);
+        result
+    }
+
+    fn file_size(&mut self, fcb: u16) -> u16 {
+        let Some(path) = self.fcb_path(fcb) else { return 0xFF };
+        match fs::metadata(path) {
+            Ok(m) => {
+                self.set_random_record(fcb, m.len().div_ceil(RECORD as u64) as u32);
+                0
+            }
+            Err(_) => 0xFF,
+        }
+    }
+}
+
+/// Host file name as an 11-byte FCB name field; None if it won't fit 8.3
+fn fcb_field(name: &str) -> Option<[u8; 11]> {
+    let upper = name.to_ascii_uppercase();
+    let (base, ext) = upper.rsplit_once('.').unwrap_or((&upper, ""));
+    if base.is_empty() || base.len() > 8 || ext.len() > 3 || !upper.is_ascii() {
+        return None;
+    }
+    let mut field = [b' '; 11];
+    field[..base.len()].copy_from_slice(base.as_bytes());
+    field[8..8 + ext.len()].copy_from_slice(ext.as_bytes());
+    Some(field)
+}
+
+/// 36-byte FCB for a command-line file name ("*" expands to '?')
+fn make_fcb(arg: &str) -> [u8; 36] {
+    let mut fcb = [0u8; 36];
+    fcb[1..12].fill(b' ');
+    let arg = match arg.as_bytes() {
+        [d @ b'A'..=b'P', b':', ..] => {
+            fcb[0] = d - b'A' + 1;
+            &arg[2..]
+        }
+        _ => arg,
+    };
+    let (base, ext) = arg.split_once('.').unwrap_or((arg, ""));
+    for (field, text, len) in [(1usize, base, 8usize), (9, ext, 3)] {
+        for (i, c) in text.bytes().take(len).enumerate() {
+            if c == b'*' {
+                fcb[field + i..field + len].fill(b'?');
+                break;
+            }
+            fcb[field + i] = c;
+        }
+    }
+    fcb
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+    use std::io::Cursor;
+
+    /// Assemble a test program at 0x0100
+    fn assemble(lines: &[&str]) -> Vec<u8> {
+        let mut asm = Assembler::new();
+        asm.set_location(TPA);
+        let mut program = Vec::new();
+        for line in lines {
+            let offset = (asm.location() - TPA) as usize;
+            let bytes = asm.assemble_line(line).unwrap_or_else(|e| panic!("{}: {:?}", line, e));
+            if program.len() < offset + bytes.len() {
+                program.resize(offset + bytes.len(), 0);
+            }
+            program[offset..offset + bytes.len()].copy_from_slice(&bytes);
+        }
+        program
+    }
+
+    /// Store bytes without moving PC (load_program would)
+    fn poke(cpm: &mut CpmHarness, addr: u16, bytes: &[u8]) {
+        for (i, &b) in bytes.iter().enumerate() {
+            cpm.cpu().write_byte(addr.wrapping_add(i as u16), b);
+        }
+    }
+
+    fn run(program: &[u8], dir: &Path) -> (CpmExit, String) {
+        let mut cpm = CpmHarness::new(dir);
+        cpm.load_com(program, "");
+        let exit = cpm.run(Some(100_000));
+        (exit, String::from_utf8_lossy(cpm.output()).to_string())
+    }
+
+    #[test]
+    fn test_print_string_and_return() {
+        let dir = tempfile::tempdir().unwrap();
+        let program = assemble(&[
+            "MSG  EQU 0110H",
+            "     MVI C,9",
+            "     LXI D,MSG",
+            "     CALL 5",
+            "     MVI C,2",
+            "     MVI E,'!'",
+            "     CALL 5",
+            "     RET",
+            "     ORG 0110H",
+            "     DB 'CPU IS '",
+            "     DB 'OPERATIONAL$'",
+        ]);
+
+        assert_eq!(run(&program, dir.path()), (CpmExit::WarmBoot, "CPU IS OPERATIONAL!".to_string()));
+    }
+
+    #[test]
+    fn test_page_zero_and_stack() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cpm = CpmHarness::new(dir.path());
+        cpm.load_com(&[0xC9], "");
+
+        assert_eq!(cpm.cpu().read_byte(0x0005), 0xC3);
+        assert_eq!(cpm.cpu().read_word(0x0006), BDOS_ENTRY);  // Top of TPA
+        assert_eq!(cpm.cpu().pc, TPA);
+        assert_eq!(cpm.run(None), CpmExit::WarmBoot);
+    }
+
+    #[test]
+    fn test_warm_boot_and_halt() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert_eq!(run(&assemble(&["JMP 0"]), dir.path()).0, CpmExit::WarmBoot);
+        assert_eq!(run(&assemble(&["MVI C,0", "CALL 5", "HLT"]), dir.path()).0, CpmExit::WarmBoot);
+        assert_eq!(run(&assemble(&["HLT"]), dir.path()).0, CpmExit::Halted);
+        assert_eq!(run(&assemble(&["JMP 0100H"]), dir.path()).0, CpmExit::InstructionLimit);
+    }
+
+    #[test]
+    fn test_console_input() {
+        let dir = tempfile::tempdir().unwrap();
+        // Read a line into 0x0200, then echo each char back with function 2
+        let program = assemble(&[
+            "     MVI C,10",
+            "     LXI D,0200H",
+            "     CALL 5",
+            "     MVI C,1",
+            "     CALL 5",
+            "     STA 0210H",
+            "     RET",
+        ]);
+        let mut cpm = CpmHarness::new(dir.path());
+        cpm.load_com(&program, "");
+        cpm.cpu().write_byte(0x0200, 10);  // Buffer size
+        cpm.set_input(Box::new(Cursor::new(b"hello\rx".to_vec())));
+
+        assert_eq!(cpm.run(Some(10_000)), CpmExit::WarmBoot);
+        assert_eq!(cpm.cpu().read_byte(0x0201), 5);
+        assert_eq!(cpm.cpu().read_byte(0x0202), b'h');
+        assert_eq!(cpm.cpu().read_byte(0x0210), b'x');
+        assert_eq!(cpm.output(), b"hello\r\nx");
+    }
+
+    #[test]
+    fn test_file_write_and_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        // FCB for TEST.DAT at 0x0300, data record in DMA at 0x0080
+        let write = assemble(&[
+            "FCB  EQU 0300H",
+            "     MVI C,22",
+            "     LXI D,FCB",
+            "     CALL 5",
+            "     MVI C,21",
+            "     LXI D,FCB",
+            "     CALL 5",
+            "     MVI C,16",
+            "     LXI D,FCB",
+            "     CALL 5",
+            "     RET",
+        ]);
+        let mut cpm = CpmHarness::new(dir.path());
+        cpm.load_com(&write, "");
+        poke(&mut cpm, 0x0300, &make_fcb("TEST.DAT"));
+        poke(&mut cpm, 0x0080, &[b'Z'; RECORD]);
+        assert_eq!(cpm.run(Some(10_000)), CpmExit::WarmBoot);
+        assert_eq!(fs::read(dir.path().join("TEST.DAT")).unwrap(), vec![b'Z'; RECORD]);
+
+        // Read it back into 0x0400 via a second program, then hit EOF
+        let read = assemble(&[
+            "FCB  EQU 0300H",
+            "     MVI C,15",
+            "     LXI D,FCB",
+            "     CALL 5",
+            "     STA 0500H",
+            "     MVI C,26",
+            "     LXI D,0400H",
+            "     CALL 5",
+            "     MVI C,20",
+            "     LXI D,FCB",
+            "     CALL 5",
+            "     STA 0501H",
+            "     MVI C,20",
+            "     LXI D,FCB",
+            "     CALL 5",
+            "     STA 0502H",
+            "     RET",
+        ]);
+        let mut cpm = CpmHarness::new(dir.path());
+        cpm.load_com(&read, "");
+        poke(&mut cpm, 0x0300, &make_fcb("test.dat"));
+        assert_eq!(cpm.run(Some(10_000)), CpmExit::WarmBoot);
+        assert_eq!(cpm.cpu().read_byte(0x0500), 0x00);  // Open OK
+        assert_eq!(cpm.cpu().read_byte(0x0501), 0x00);  // Record read
+        assert_eq!(cpm.cpu().read_byte(0x0502), 0x01);  // EOF
+        assert_eq!(cpm.cpu().read_byte(0x047F), b'Z');
+    }
+
+    #[test]
+    fn test_search_and_command_tail_fcb() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), b"1").unwrap();
+        fs::write(dir.path().join("b.txt"), b"2").unwrap();
+        fs::write(dir.path().join("c.com"), b"3").unwrap();
+
+        let mut cpm = CpmHarness::new(dir.path());
+        cpm.load_com(&[0xC9], "*.TXT");
+        assert_eq!(cpm.cpu().read_byte(0x0080), 6);        // " *.TXT"
+        assert_eq!(cpm.cpu().read_byte(0x005D), b'?');
+
+        assert_eq!(cpm.search_first(DEFAULT_FCB), 0);
+        assert_eq!(cpm.cpu().read_byte(0x0081), b'A');
+        assert_eq!(cpm.search_next(), 0);
+        assert_eq!(cpm.cpu().read_byte(0x0081), b'B');
+        assert_eq!(cpm.search_next(), 0xFF);
+    }
+
+    #[test]
+    fn test_fcb_names_stay_in_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("cpm");
+        fs::create_dir(&root).unwrap();
+        let mut cpm = CpmHarness::new(&root);
+
+        let names: [&[u8; 11]; 4] = [b"../EVIL    ", b"..         ", b"A\x01B        ", b"SUB/X   TXT"];
+        for name in names {
+            let mut fcb = [0u8; 36];
+            fcb[1..12].copy_from_slice(name);
+            poke(&mut cpm, 0x0300, &fcb);
+            assert_eq!(cpm.make_file(0x0300), 0xFF);
+            assert_eq!(cpm.write_record(0x0300, 0), 2);
+        }
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 0);
+
+        // Renaming onto an escaping name fails too
+        fs::write(root.join("OK.TXT"), b"x").unwrap();
+        let mut fcb = [0u8; 36];
+        fcb[..16].copy_from_slice(&make_fcb("OK.TXT")[..16]);
+        fcb[17..28].copy_from_slice(b"../OUT     ");
+        poke(&mut cpm, 0x0300, &fcb);
+        assert_eq!(cpm.rename_file(0x0300), 0xFF);
+        assert!(root.join("OK.TXT").exists());
+    }
+
+    #[test]
+    fn test_fcb_at_top_of_memory_wraps() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cpm = CpmHarness::new(dir.path());
+        poke(&mut cpm, 0xFFF0, &make_fcb("TOP.DAT"));
+        assert_eq!(cpm.make_file(0xFFF0), 0);
+        assert_eq!(cpm.write_sequential(0xFFF0), 0);
+        assert_eq!(cpm.cpu().read_byte(0x0010), 1);        // Current record, 0xFFF0 + 32
+        assert!(dir.path().join("TOP.DAT").exists());
+    }
+}
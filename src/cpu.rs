@@ -1,10 +1,14 @@
 // cpu.rs - Intel 8080 CPU emulator core
+use crate::disasm;
+use crate::formats::Image;
 use crate::memory::{Memory, FlatMemory};
 use crate::io::IoBus;
 use crate::io::devices::timer::Timer;
-use crate::io::IoDevice;
+use crate::io::{BusMaster, Clocked, InterruptSource, IoDevice};
+use std::cell::RefCell;
 use std::io;
 use std::path::Path;
+use std::rc::Rc;
 
 
 use crate::registers::{Register, RegisterPair, PushPopPair, Condition};
@@ -28,25 +32,43 @@ pub struct Intel8080 {
     rom: Vec<u8>,                       // 4KB ROM at 0xF000
     pub rom_overlay_enabled: bool,      // When true, ROM visible at 0x0000 too
     io_bus: IoBus, 
-    pub timer: Timer,
+    pub timer: Rc<RefCell<Timer>>,
+    clocked_devices: Vec<Rc<RefCell<dyn Clocked>>>,
+    bus_masters: Vec<Rc<RefCell<dyn BusMaster>>>,
+    interrupt_sources: Vec<Rc<RefCell<dyn InterruptSource>>>,
 
     pub halted: bool,
     pub interrupts_enabled: bool,
     pub cycles: u64,
 }
 
+impl Default for Intel8080 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Intel8080 {
     pub fn new() -> Self {
+        Self::with_memory(Box::new(FlatMemory::new()))
+    }
+
+    /// CPU on a custom memory map (machine profiles with their own decoding)
+    pub fn with_memory(memory: Box<dyn Memory>) -> Self {
+        let timer = Rc::new(RefCell::new(Timer::new()));
         Intel8080 {
             a: 0, b: 0, c: 0, d: 0, e: 0, h: 0, l: 0,
             flags: FLAG_BIT_1,
             sp: 0xF000,
             pc: 0x0000,             // 8080 starts at 0x0000 on reset
-            memory: Box::new(FlatMemory::new()),
+            memory,
             rom: Vec::new(),
             rom_overlay_enabled: false, // OFF by default, reset() enables it
             io_bus: IoBus::new(),
-            timer: Timer::new(),
+            timer: timer.clone(),
+            clocked_devices: Vec::new(),
+            bus_masters: Vec::new(),
+            interrupt_sources: vec![timer],
             halted: false,
             interrupts_enabled: false,
             cycles: 0,
@@ -56,6 +78,27 @@ impl Intel8080 {
     pub fn io_bus_mut(&mut self) -> &mut IoBus {
         &mut self.io_bus
     }
+
+    /// Attach a device that is ticked with the cycle count of every instruction
+    pub fn attach_clocked(&mut self, device: Rc<RefCell<dyn Clocked>>) {
+        self.clocked_devices.push(device);
+    }
+
+    /// Attach a device that gets the memory bus after every instruction
+    pub fn attach_bus_master(&mut self, device: Rc<RefCell<dyn BusMaster>>) {
+        self.bus_masters.push(device);
+    }
+
+    /// Attach a device that can interrupt the CPU. Sources are checked in
+    /// the order attached; the built-in timer (RST 7) is attached first.
+    pub fn attach_interrupt_source(&mut self, device: Rc<RefCell<dyn InterruptSource>>) {
+        self.interrupt_sources.push(device);
+    }
+
+    /// Stop checking a source, e.g. to wire the timer to a PIC instead
+    pub fn detach_interrupt_source(&mut self, device: &Rc<RefCell<dyn InterruptSource>>) {
+        self.interrupt_sources.retain(|source| !Rc::ptr_eq(source, device));
+    }
     // ============================================
     // LAYER 1: Direct register access
     // ============================================
@@ -256,7 +299,7 @@ impl Intel8080 {
                 self.flags |= FLAG_ZERO; 
             }
             if result & 0x80 != 0 { self.flags |= FLAG_SIGN; }
-            if result.count_ones() % 2 == 0 { self.flags |= FLAG_PARITY; }
+            if result.count_ones().is_multiple_of(2) { self.flags |= FLAG_PARITY; }
             if carry { self.flags |= FLAG_CARRY; }
             println!("  Final flags: {:08b}", self.flags);
         }
@@ -265,7 +308,7 @@ impl Intel8080 {
             
             if result == 0 { self.flags |= FLAG_ZERO; }
             if result & 0x80 != 0 { self.flags |= FLAG_SIGN; }
-            if result.count_ones() % 2 == 0 { self.flags |= FLAG_PARITY; }
+            if result.count_ones().is_multiple_of(2) { self.flags |= FLAG_PARITY; }
             if carry { self.flags |= FLAG_CARRY; }
             if aux_carry { self.flags |= FLAG_AUX_CARRY; }
         }
@@ -275,7 +318,7 @@ impl Intel8080 {
         
         if result == 0 { self.flags |= FLAG_ZERO; }
         if result & 0x80 != 0 { self.flags |= FLAG_SIGN; }
-        if result.count_ones() % 2 == 0 { self.flags |= FLAG_PARITY; }
+        if result.count_ones().is_multiple_of(2) { self.flags |= FLAG_PARITY; }
         // Carry and aux carry are cleared
     }
     // ============================================
@@ -288,7 +331,7 @@ impl Intel8080 {
         }
     }
 //
-    fn handle_interrupt(&mut self) {
+    fn handle_interrupt(&mut self, vector: u16) {
         // Disable interrupts (8080 does this automatically)
         self.interrupts_enabled = false;
         
@@ -296,11 +339,16 @@ impl Intel8080 {
         self.sp = self.sp.wrapping_sub(2);
         self.write_word(self.sp, self.pc);
         
-        // Jump to interrupt vector (typically RST 7 = 0x0038)
-        self.pc = 0x0038;
-        
-        // Clear the interrupt
-        self.timer.interrupt_pending = false;
+        // Jump to interrupt vector (RST n = n * 8)
+        self.pc = vector;
+    }
+
+    /// Vector of the interrupt to take now, acknowledging its source
+    fn pending_interrupt(&mut self) -> Option<u16> {
+        self.interrupt_sources.iter().find_map(|source| {
+            let mut source = source.borrow_mut();
+            source.interrupt_requested().then(|| source.acknowledge())
+        })
     }
 
     pub fn perform_nop(&mut self) -> u8{
@@ -756,8 +804,8 @@ impl Intel8080 {
 
     pub fn perform_out(&mut self) -> u8{
         let port = self.fetch_byte();
-        if port >= 0x30 && port <= 0x32 {
-            self.timer.write(port, self.a);
+        if (0x30..=0x32).contains(&port) {
+            self.timer.borrow_mut().write(port, self.a);
         } else if port == 0xFE {
             // System control port
             match self.a {
@@ -773,8 +821,8 @@ impl Intel8080 {
 
     pub fn perform_in(&mut self) -> u8{
         let port = self.fetch_byte();
-        self.a = if port >= 0x30 && port <= 0x32 {
-            self.timer.read(port)
+        self.a = if (0x30..=0x32).contains(&port) {
+            self.timer.borrow_mut().read(port)
         } else if port == 0xFF {
             // System status port - bit 0 = overlay state
             if self.rom_overlay_enabled { 0x01 } else { 0x00 }
@@ -824,8 +872,10 @@ impl Intel8080 {
     }
     
     pub fn execute_one(&mut self) -> u8 {
-        if self.interrupts_enabled && self.timer.interrupt_pending {
-            self.handle_interrupt();
+        if self.interrupts_enabled {
+            if let Some(vector) = self.pending_interrupt() {
+                self.handle_interrupt(vector);
+            }
         }
         
         let opcode = self.fetch_byte();
@@ -945,9 +995,22 @@ impl Intel8080 {
             _ => panic!("Unknown opcode: 0x{:02X} at PC: 0x{:04X}", 
                        opcode, self.pc.wrapping_sub(1)),
         };
-        self.timer.tick(cycles as u64);
+        // Bus masters run between instructions; cycles they steal pass
+        // like instruction time
+        let mut elapsed = cycles as u64;
+        if !self.bus_masters.is_empty() {
+            let masters = std::mem::take(&mut self.bus_masters);
+            for device in &masters {
+                elapsed += device.borrow_mut().service(self) as u64;
+            }
+            self.bus_masters = masters;
+        }
+        self.timer.borrow_mut().tick(elapsed);
+        for device in &self.clocked_devices {
+            device.borrow_mut().tick(elapsed);
+        }
 
-        self.cycles += cycles as u64;  // <-- ADD THIS
+        self.cycles += elapsed;
 
         cycles
     }
@@ -957,57 +1020,12 @@ impl Intel8080 {
     // ============================================
     
     pub fn disassemble_at(&mut self, addr: u16) -> (String, u8) {
-        let opcode = self.read_byte(addr);  //self.memory[addr as usize];
-        
-        match opcode {
-            0x00 => ("NOP".to_string(), 1),
-            0x01 => {
-                let word = self.read_word(addr.wrapping_add(1));
-                (format!("LXI B,{:04X}h", word), 3)
-            }
-            0x06 => {
-                let byte = self.read_byte(addr.wrapping_add(1));
-                //let byte = self.memory[addr.wrapping_add(1) as usize];
-                (format!("MVI B,{:02X}h", byte), 2)
-            }
-            0x21 => {
-                let word = self.read_word(addr.wrapping_add(1));
-                (format!("LXI H,{:04X}h", word), 3)
-            }
-            0x22 => {
-                let word = self.read_word(addr.wrapping_add(1));
-                (format!("SHLD {:04X}h", word), 3)
-            }
-            0x2A => {
-                let word = self.read_word(addr.wrapping_add(1));
-                (format!("LHLD {:04X}h", word), 3)
-            }
-            0x32 => {
-                let word = self.read_word(addr.wrapping_add(1));
-                (format!("STA {:04X}h", word), 3)
-            }
-            0x3A => {
-                let word = self.read_word(addr.wrapping_add(1));
-                (format!("LDA {:04X}h", word), 3)
-            }
-            0x3E => {
-                let byte = self.read_byte(addr.wrapping_add(1));
-                //let byte = self.memory[addr.wrapping_add(1) as usize];
-                (format!("MVI A,{:02X}h", byte), 2)
-            }
-            0x76 => ("HLT".to_string(), 1),
-            0x77 => ("MOV M,A".to_string(), 1),
-            0x78 => ("MOV A,B".to_string(), 1),
-            0x7E => ("MOV A,M".to_string(), 1),
-            0x80 => ("ADD B".to_string(), 1),
-            0xC1 => ("POP B".to_string(), 1),
-            0xC3 => {
-                let word = self.read_word(addr.wrapping_add(1));
-                (format!("JMP {:04X}h", word), 3)
-            }
-            0xC5 => ("PUSH B".to_string(), 1),
-            _ => (format!("DB {:02X}h", opcode), 1),
-        }
+        let bytes = [
+            self.read_byte(addr),
+            self.read_byte(addr.wrapping_add(1)),
+            self.read_byte(addr.wrapping_add(2)),
+        ];
+        disasm::disassemble(&bytes).expect("three bytes always decode")
     }
     
     pub fn trace(&mut self) {
@@ -1059,7 +1077,7 @@ impl Intel8080 {
             for i in 0..8 {
                 let byte = self.read_byte(addr.wrapping_add(i));
                 //let byte = self.memory[addr.wrapping_add(i) as usize];
-                let ch = if byte >= 0x20 && byte <= 0x7E { byte as char } else { '.' };
+                let ch = if (0x20..=0x7E).contains(&byte) { byte as char } else { '.' };
                 print!("{}", ch);
             }
             println!("|");
@@ -1098,6 +1116,19 @@ pub fn load_program_from_file(&mut self, path: &Path, start_address: u16) -> io:
     Ok(program.len())
 }
 
+/// Load an Intel HEX / S-record / binary image, bypassing ROM protection.
+/// PC moves to the image's start address if it has one.
+pub fn load_image(&mut self, image: &Image) {
+    for (address, data) in &image.segments {
+        for (i, &byte) in data.iter().enumerate() {
+            self.memory.write(address.wrapping_add(i as u16), byte);
+        }
+    }
+    if let Some(start) = image.start {
+        self.pc = start;
+    }
+}
+
 /// Reset CPU to power-on state
 pub fn reset(&mut self) {
     self.a = 0; self.b = 0; self.c = 0; self.d = 0;
@@ -1121,4 +1152,20 @@ pub fn load_rom_from_file(&mut self, path: &Path) -> io::Result<usize> {
     self.rom = std::fs::read(path)?;
     Ok(self.rom.len())
 }
-}
\ No newline at end of file
+}
+/// The CPU's view of memory, ROM mapping and protection included.
+/// This is what bus masters see.
+impl Memory for Intel8080 {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.read_byte(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.write_byte(addr, value)
+    }
+
+    /// Into RAM, as load_image does, even under ROM
+    fn load(&mut self, addr: u16, value: u8) {
+        self.memory.write(addr, value)
+    }
+}
@@ -0,0 +1,206 @@
+// disasm.rs - Intel 8080 disassembler
+//
+// Period formatting: uppercase mnemonics, mnemonic padded to five columns,
+// hex with an H suffix and a leading 0 when the first digit is A-F.
+//
+//   3E 42     -> MVI  A,42H
+//   C3 00 F0  -> JMP  0F000H
+//   C9        -> RET
+//
+// Undocumented opcodes disassemble as DB.
+
+use crate::registers::{Condition, PushPopPair, Register, RegisterPair};
+
+/// Instruction length in bytes (1-3) for an opcode
+pub fn instruction_length(opcode: u8) -> u8 {
+    match opcode {
+        // MVI r / immediate ALU / IN / OUT
+        b if (b & 0xC7) == 0x06 => 2,
+        0xC6 | 0xCE | 0xD6 | 0xDE | 0xE6 | 0xEE | 0xF6 | 0xFE => 2,
+        0xD3 | 0xDB => 2,
+        // LXI / SHLD / LHLD / STA / LDA
+        b if (b & 0xCF) == 0x01 => 3,
+        0x22 | 0x2A | 0x32 | 0x3A => 3,
+        // JMP / CALL / Jcc / Ccc
+        0xC3 | 0xCD => 3,
+        b if (b & 0xC7) == 0xC2 || (b & 0xC7) == 0xC4 => 3,
+        _ => 1,
+    }
+}
+
+/// Format a byte as period hex: 42H, 0FFH
+pub fn hex8(value: u8) -> String {
+    let digits = format!("{:02X}", value);
+    if digits.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        format!("0{}H", digits)
+    } else {
+        format!("{}H", digits)
+    }
+}
+
+/// Format a word as period hex: 1000H, 0F000H
+pub fn hex16(value: u16) -> String {
+    let digits = format!("{:04X}", value);
+    if digits.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        format!("0{}H", digits)
+    } else {
+        format!("{}H", digits)
+    }
+}
+
+fn fmt(mnemonic: &str, operands: &str) -> String {
+    if operands.is_empty() {
+        mnemonic.to_string()
+    } else {
+        format!("{:<5}{}", mnemonic, operands)
+    }
+}
+
+/// Disassemble one instruction from the start of `bytes`.
+///
+/// Returns the text and the number of bytes consumed, or `Err(needed)`
+/// when fewer bytes were supplied than the instruction needs.
+pub fn disassemble(bytes: &[u8]) -> Result<(String, u8), u8> {
+    let opcode = match bytes.first() {
+        Some(&b) => b,
+        None => return Err(1),
+    };
+    let len = instruction_length(opcode);
+    if bytes.len() < len as usize {
+        return Err(len);
+    }
+    let d8 = || hex8(bytes[1]);
+    let d16 = || hex16(u16::from_le_bytes([bytes[1], bytes[2]]));
+
+    let reg = |code: u8| Register::from_code(code).name();
+    let pair = |code: u8| RegisterPair::from_code(code).mnemonic();
+
+    let text = match opcode {
+        0x00 => fmt("NOP", ""),
+        0x76 => fmt("HLT", ""),
+
+        // MOV 01DDDSSS
+        0x40..=0x7F => fmt("MOV", &format!("{},{}", reg(opcode >> 3), reg(opcode))),
+
+        // ALU 10AAASSS
+        0x80..=0xBF => {
+            const OPS: [&str; 8] = ["ADD", "ADC", "SUB", "SBB", "ANA", "XRA", "ORA", "CMP"];
+            fmt(OPS[((opcode >> 3) & 0x07) as usize], reg(opcode))
+        }
+
+        b if (b & 0xC7) == 0x06 => fmt("MVI", &format!("{},{}", reg(b >> 3), d8())),
+        b if (b & 0xC7) == 0x04 => fmt("INR", reg(b >> 3)),
+        b if (b & 0xC7) == 0x05 => fmt("DCR", reg(b >> 3)),
+        b if (b & 0xCF) == 0x01 => fmt("LXI", &format!("{},{}", pair(b >> 4), d16())),
+        b if (b & 0xCF) == 0x09 => fmt("DAD", pair(b >> 4)),
+        b if (b & 0xCF) == 0x03 => fmt("INX", pair(b >> 4)),
+        b if (b & 0xCF) == 0x0B => fmt("DCX", pair(b >> 4)),
+        b if (b & 0xCF) == 0xC5 => fmt("PUSH", PushPopPair::from_code(b >> 4).mnemonic()),
+        b if (b & 0xCF) == 0xC1 => fmt("POP", PushPopPair::from_code(b >> 4).mnemonic()),
+
+        b if (b & 0xC7) == 0xC2 => {
+            fmt(&format!("J{}", Condition::from_code(b >> 3).name()), &d16())
+        }
+        b if (b & 0xC7) == 0xC4 => {
+            fmt(&format!("C{}", Condition::from_code(b >> 3).name()), &d16())
+        }
+        b if (b & 0xC7) == 0xC0 => fmt(&format!("R{}", Condition::from_code(b >> 3).name()), ""),
+        b if (b & 0xC7) == 0xC7 => fmt("RST", &((b >> 3) & 0x07).to_string()),
+
+        0xC3 => fmt("JMP", &d16()),
+        0xCD => fmt("CALL", &d16()),
+        0xC9 => fmt("RET", ""),
+
+        0x02 => fmt("STAX", "B"),
+        0x12 => fmt("STAX", "D"),
+        0x0A => fmt("LDAX", "B"),
+        0x1A => fmt("LDAX", "D"),
+        0x22 => fmt("SHLD", &d16()),
+        0x2A => fmt("LHLD", &d16()),
+        0x32 => fmt("STA", &d16()),
+        0x3A => fmt("LDA", &d16()),
+
+        0xC6 => fmt("ADI", &d8()),
+        0xCE => fmt("ACI", &d8()),
+        0xD6 => fmt("SUI", &d8()),
+        0xDE => fmt("SBI", &d8()),
+        0xE6 => fmt("ANI", &d8()),
+        0xEE => fmt("XRI", &d8()),
+        0xF6 => fmt("ORI", &d8()),
+        0xFE => fmt("CPI", &d8()),
+
+        0x07 => fmt("RLC", ""),
+        0x0F => fmt("RRC", ""),
+        0x17 => fmt("RAL", ""),
+        0x1F => fmt("RAR", ""),
+        0x27 => fmt("DAA", ""),
+        0x2F => fmt("CMA", ""),
+        0x37 => fmt("STC", ""),
+        0x3F => fmt("CMC", ""),
+
+        0xD3 => fmt("OUT", &d8()),
+        0xDB => fmt("IN", &d8()),
+        0xE3 => fmt("XTHL", ""),
+        0xE9 => fmt("PCHL", ""),
+        0xEB => fmt("XCHG", ""),
+        0xF9 => fmt("SPHL", ""),
+        0xF3 => fmt("DI", ""),
+        0xFB => fmt("EI", ""),
+
+        // Undocumented
+        _ => fmt("DB", &hex8(opcode)),
+    };
+
+    Ok((text, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roadmap_examples() {
+        assert_eq!(disassemble(&[0x3E, 0x42]), Ok(("MVI  A,42H".to_string(), 2)));
+        assert_eq!(disassemble(&[0xC9]), Ok(("RET".to_string(), 1)));
+    }
+
+    #[test]
+    fn test_leading_zero_on_letter_hex() {
+        assert_eq!(disassemble(&[0xC3, 0x00, 0xF0]), Ok(("JMP  0F000H".to_string(), 3)));
+        assert_eq!(disassemble(&[0xFE, 0x0D]), Ok(("CPI  0DH".to_string(), 2)));
+        assert_eq!(disassemble(&[0xD3, 0xFE]), Ok(("OUT  0FEH".to_string(), 2)));
+    }
+
+    #[test]
+    fn test_register_pairs_use_intel_names() {
+        assert_eq!(disassemble(&[0x21, 0x34, 0x12]).unwrap().0, "LXI  H,1234H");
+        assert_eq!(disassemble(&[0x31, 0x00, 0xF0]).unwrap().0, "LXI  SP,0F000H");
+        assert_eq!(disassemble(&[0xF5]).unwrap().0, "PUSH PSW");
+        assert_eq!(disassemble(&[0x19]).unwrap().0, "DAD  D");
+    }
+
+    #[test]
+    fn test_conditionals_and_rst() {
+        assert_eq!(disassemble(&[0xCA, 0x00, 0x10]).unwrap().0, "JZ   1000H");
+        assert_eq!(disassemble(&[0xF4, 0x00, 0x10]).unwrap().0, "CP   1000H");
+        assert_eq!(disassemble(&[0xE8]).unwrap().0, "RPE");
+        assert_eq!(disassemble(&[0xFF]).unwrap().0, "RST  7");
+    }
+
+    #[test]
+    fn test_short_input_reports_needed_length() {
+        assert_eq!(disassemble(&[]), Err(1));
+        assert_eq!(disassemble(&[0x3E]), Err(2));
+        assert_eq!(disassemble(&[0xCD, 0x00]), Err(3));
+    }
+
+    #[test]
+    fn test_all_opcodes_have_text() {
+        for op in 0..=255u8 {
+            let (text, len) = disassemble(&[op, 0x00, 0x00]).unwrap();
+            assert!(!text.is_empty());
+            assert_eq!(len, instruction_length(op));
+        }
+        assert_eq!(disassemble(&[0xCB]).unwrap().0, "DB   0CBH");
+    }
+}
@@ -0,0 +1,556 @@
+// formats.rs - Program image formats
+//
+// Intel HEX:   :LLAAAATT<data>CC
+//   00 = data, 01 = end of file, 03 = start segment address (sets PC)
+//   Checksum: all bytes including CC sum to 0
+//
+// Motorola S-record:  S<type>LL<address><data>CC
+//   S0 = header (ignored), S1 = data (16-bit address), S5 = record count,
+//   S9 = start address (sets PC). S2/S3 and S8/S7 are accepted as long as
+//   the address fits in 64KB.
+//   Checksum: ones' complement of the sum of length, address and data
+//
+// Raw binaries have no address information, so the caller supplies one.
+
+use crate::cpu::Intel8080;
+use crate::memory::Memory;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// Data bytes per record when writing
+const RECORD_BYTES: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Binary,
+    IntelHex,
+    SRecord,
+}
+
+impl Format {
+    /// Pick a format from the file extension; anything unknown is binary
+    pub fn from_path(path: &Path) -> Format {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        match ext.as_str() {
+            "hex" | "ihx" | "ihex" => Format::IntelHex,
+            "s19" | "s28" | "s37" | "srec" | "mot" => Format::SRecord,
+            _ => Format::Binary,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatErrorKind {
+    MissingStartCode,           // Line doesn't start with ':' or 'S'
+    BadHexDigit,
+    BadLength,                  // Length byte doesn't match the line
+    BadChecksum { expected: u8, found: u8 },
+    UnsupportedType(u8),
+    AddressOverflow,            // Data runs past 0xFFFF
+    MissingEnd,                 // No EOF (01) record
+}
+
+/// A format error and the 1-based line it was found on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatError {
+    pub line: usize,
+    pub kind: FormatErrorKind,
+}
+
+impl fmt::Display for FormatErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatErrorKind::MissingStartCode => write!(f, "missing start code"),
+            FormatErrorKind::BadHexDigit => write!(f, "bad hex digit"),
+            FormatErrorKind::BadLength => write!(f, "record length mismatch"),
+            FormatErrorKind::BadChecksum { expected, found } => {
+                write!(f, "checksum {:02X}, expected {:02X}", found, expected)
+            }
+            FormatErrorKind::UnsupportedType(t) => write!(f, "unsupported record type {:02X}", t),
+            FormatErrorKind::AddressOverflow => write!(f, "address beyond 64KB"),
+            FormatErrorKind::MissingEnd => write!(f, "missing end-of-file record"),
+        }
+    }
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.kind)
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+/// One Intel HEX record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HexRecord {
+    pub record_type: u8,
+    pub address: u16,
+    pub data: Vec<u8>,
+}
+
+/// Loaded program: data runs in file order, plus an optional entry point
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Image {
+    pub segments: Vec<(u16, Vec<u8>)>,
+    pub start: Option<u16>,
+}
+
+impl Image {
+    /// Total data bytes
+    pub fn len(&self) -> usize {
+        self.segments.iter().map(|(_, data)| data.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Append data, merging with the previous run when contiguous
+    fn add(&mut self, address: u16, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        if let Some((last_addr, last)) = self.segments.last_mut() {
+            if *last_addr as usize + last.len() == address as usize {
+                last.extend_from_slice(data);
+                return;
+            }
+        }
+        self.segments.push((address, data.to_vec()));
+    }
+}
+
+fn hex_bytes(text: &str) -> Result<Vec<u8>, FormatErrorKind> {
+    if !text.is_ascii() || !text.len().is_multiple_of(2) {
+        return Err(FormatErrorKind::BadHexDigit);
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).map_err(|_| FormatErrorKind::BadHexDigit))
+        .collect()
+}
+
+fn check_fits(address: u32, len: usize) -> Result<(), FormatErrorKind> {
+    if address as usize + len > 0x10000 {
+        Err(FormatErrorKind::AddressOverflow)
+    } else {
+        Ok(())
+    }
+}
+
+/// Parse and checksum one Intel HEX record line
+pub fn parse_hex_record(line: &str) -> Result<HexRecord, FormatErrorKind> {
+    let body = line.trim().strip_prefix(':').ok_or(FormatErrorKind::MissingStartCode)?;
+    let bytes = hex_bytes(body)?;
+    if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+        return Err(FormatErrorKind::BadLength);
+    }
+
+    let (payload, checksum) = bytes.split_at(bytes.len() - 1);
+    let sum = payload.iter().fold(0u8, |acc, &b| acc.wrapping_add(b));
+    let expected = sum.wrapping_neg();
+    if expected != checksum[0] {
+        return Err(FormatErrorKind::BadChecksum { expected, found: checksum[0] });
+    }
+
+    let record = HexRecord {
+        record_type: bytes[3],
+        address: u16::from_be_bytes([bytes[1], bytes[2]]),
+        data: payload[4..].to_vec(),
+    };
+    match record.record_type {
+        0x00 => check_fits(record.address as u32, record.data.len())?,
+        0x01 => {}
+        0x03 if record.data.len() == 4 => {}
+        0x03 => return Err(FormatErrorKind::BadLength),
+        t => return Err(FormatErrorKind::UnsupportedType(t)),
+    }
+    Ok(record)
+}
+
+/// Entry point from a type 03 record (CS:IP, folded into 64KB)
+pub fn hex_start_address(record: &HexRecord) -> u16 {
+    let cs = u16::from_be_bytes([record.data[0], record.data[1]]);
+    let ip = u16::from_be_bytes([record.data[2], record.data[3]]);
+    cs.wrapping_shl(4).wrapping_add(ip)
+}
+
+pub fn parse_intel_hex(text: &str) -> Result<Image, FormatError> {
+    let mut image = Image::default();
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = parse_hex_record(line).map_err(|kind| FormatError { line: i + 1, kind })?;
+        match record.record_type {
+            0x00 => image.add(record.address, &record.data),
+            0x01 => return Ok(image),
+            _ => image.start = Some(hex_start_address(&record)),
+        }
+    }
+    Err(FormatError {
+        line: text.lines().count(),
+        kind: FormatErrorKind::MissingEnd,
+    })
+}
+
+pub fn parse_srecord(text: &str) -> Result<Image, FormatError> {
+    let mut image = Image::default();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        parse_srecord_line(line, &mut image).map_err(|kind| FormatError { line: i + 1, kind })?;
+    }
+    Ok(image)
+}
+
+fn parse_srecord_line(line: &str, image: &mut Image) -> Result<(), FormatErrorKind> {
+    let rest = line.strip_prefix(['S', 's']).ok_or(FormatErrorKind::MissingStartCode)?;
+    let record_type = rest.chars().next().and_then(|c| c.to_digit(10)).ok_or(FormatErrorKind::BadHexDigit)? as u8;
+    let bytes = hex_bytes(&rest[1..])?;
+    if bytes.is_empty() || bytes.len() != bytes[0] as usize + 1 {
+        return Err(FormatErrorKind::BadLength);
+    }
+
+    let (payload, checksum) = bytes.split_at(bytes.len() - 1);
+    let sum = payload.iter().fold(0u8, |acc, &b| acc.wrapping_add(b));
+    let expected = !sum;
+    if expected != checksum[0] {
+        return Err(FormatErrorKind::BadChecksum { expected, found: checksum[0] });
+    }
+
+    let address_len = match record_type {
+        0 | 1 | 5 | 9 => 2,
+        2 | 6 | 8 => 3,
+        3 | 7 => 4,
+        t => return Err(FormatErrorKind::UnsupportedType(t)),
+    };
+    if payload.len() < 1 + address_len {
+        return Err(FormatErrorKind::BadLength);
+    }
+    let address = payload[1..=address_len]
+        .iter()
+        .fold(0u32, |acc, &b| (acc << 8) | b as u32);
+    let data = &payload[1 + address_len..];
+
+    match record_type {
+        1..=3 => {
+            check_fits(address, data.len())?;
+            image.add(address as u16, data);
+        }
+        7..=9 => {
+            check_fits(address, 0)?;
+            image.start = Some(address as u16);
+        }
+        _ => {}     // S0 header, S5/S6 count
+    }
+    Ok(())
+}
+
+fn hex_line(record_type: u8, address: u16, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8, (address >> 8) as u8, address as u8, record_type];
+    bytes.extend_from_slice(data);
+    let sum = bytes.iter().fold(0u8, |acc, &b| acc.wrapping_add(b));
+    bytes.push(sum.wrapping_neg());
+    let digits: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    format!(":{}\n", digits)
+}
+
+fn srecord_line(record_type: u8, address: u16, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8 + 3, (address >> 8) as u8, address as u8];
+    bytes.extend_from_slice(data);
+    let sum = bytes.iter().fold(0u8, |acc, &b| acc.wrapping_add(b));
+    bytes.push(!sum);
+    let digits: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    format!("S{}{}\n", record_type, digits)
+}
+
+/// Intel HEX for `data` loaded at `origin`, with a type 03 record if `start` is given
+pub fn write_intel_hex(data: &[u8], origin: u16, start: Option<u16>) -> String {
+    let mut out = String::new();
+    for (i, chunk) in data.chunks(RECORD_BYTES).enumerate() {
+        out += &hex_line(0x00, origin.wrapping_add((i * RECORD_BYTES) as u16), chunk);
+    }
+    if let Some(pc) = start {
+        out += &hex_line(0x03, 0, &[0, 0, (pc >> 8) as u8, pc as u8]);
+    }
+    out += &hex_line(0x01, 0, &[]);
+    out
+}
+
+/// S-records for `data` loaded at `origin`; S9 carries `start` (0 if none)
+pub fn write_srecord(data: &[u8], origin: u16, start: Option<u16>) -> String {
+    let mut out = srecord_line(0, 0, b"intel8080");
+    let chunks = data.chunks(RECORD_BYTES);
+    let count = chunks.len();
+    for (i, chunk) in chunks.enumerate() {
+        out += &srecord_line(1, origin.wrapping_add((i * RECORD_BYTES) as u16), chunk);
+    }
+    out += &srecord_line(5, count as u16, &[]);
+    out += &srecord_line(9, start.unwrap_or(0), &[]);
+    out
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Format(FormatError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "{}", e),
+            LoadError::Format(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+impl From<FormatError> for LoadError {
+    fn from(e: FormatError) -> Self {
+        LoadError::Format(e)
+    }
+}
+
+/// Read a program file; binaries are placed at `binary_address`
+pub fn read_image(path: &Path, binary_address: u16) -> Result<Image, LoadError> {
+    match Format::from_path(path) {
+        Format::Binary => {
+            let data = std::fs::read(path)?;
+            let mut image = Image::default();
+            image.add(binary_address, &data);
+            Ok(image)
+        }
+        Format::IntelHex => Ok(parse_intel_hex(&std::fs::read_to_string(path)?)?),
+        Format::SRecord => Ok(parse_srecord(&std::fs::read_to_string(path)?)?),
+    }
+}
+
+/// Load a program file into memory (bypassing ROM protection) and set PC
+/// from its start address, if it has one. Returns the image loaded.
+pub fn load_file(cpu: &mut Intel8080, path: &Path, binary_address: u16) -> Result<Image, LoadError> {
+    let image = read_image(path, binary_address)?;
+    cpu.load_image(&image);
+    Ok(image)
+}
+
+/// Load a program file into a running machine's memory, into RAM past
+/// ROM protection like load_file (PC is left alone). Returns the image
+/// loaded; `hidden_bytes` tells whether all of it can be seen.
+pub fn load_into(memory: &mut dyn Memory, path: &Path, binary_address: u16) -> Result<Image, LoadError> {
+    let image = read_image(path, binary_address)?;
+    for (address, data) in &image.segments {
+        for (i, &byte) in data.iter().enumerate() {
+            memory.load(address.wrapping_add(i as u16), byte);
+        }
+    }
+    Ok(image)
+}
+
+/// Bytes of a loaded image that memory does not read back, e.g. RAM
+/// hidden under ROM or the boot overlay
+pub fn hidden_bytes(memory: &mut dyn Memory, image: &Image) -> usize {
+    image.segments.iter()
+        .flat_map(|(address, data)| data.iter().enumerate().map(move |(i, &byte)| (address.wrapping_add(i as u16), byte)))
+        .filter(|&(address, byte)| memory.read(address) != byte)
+        .count()
+}
+
+/// Reset the CPU, then load programs for a cold start. PC ends up at the
+/// last start address the images carry, with the ROM overlay off so the
+/// program sees its own RAM; without one the monitor boots from 0000.
+pub fn boot(cpu: &mut Intel8080, images: &[Image]) {
+    cpu.reset();
+    for image in images {
+        cpu.load_image(image);
+    }
+    if images.iter().any(|image| image.start.is_some()) {
+        cpu.rom_overlay_enabled = false;
+    }
+}
+
+/// Save memory `start..=end` as the CPU sees it, in the format the extension names
+pub fn save_file(memory: &mut dyn Memory, path: &Path, start: u16, end: u16, entry: Option<u16>) -> io::Result<usize> {
+    if start > end {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "start address is past the end address"));
+    }
+    let data: Vec<u8> = (start..=end).map(|addr| memory.read(addr)).collect();
+    match Format::from_path(path) {
+        Format::Binary => std::fs::write(path, &data)?,
+        Format::IntelHex => std::fs::write(path, write_intel_hex(&data, start, entry))?,
+        Format::SRecord => std::fs::write(path, write_srecord(&data, start, entry))?,
+    }
+    Ok(data.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO_HEX: &str = "\
+:0A0100003E48D3013E49D3017600CA
+:00000001FF
+";
+
+    #[test]
+    fn test_parse_intel_hex() {
+        let image = parse_intel_hex(HELLO_HEX).unwrap();
+        assert_eq!(image.segments, vec![(0x0100, vec![0x3E, 0x48, 0xD3, 0x01, 0x3E, 0x49, 0xD3, 0x01, 0x76, 0x00])]);
+        assert_eq!(image.start, None);
+        assert_eq!(image.len(), 10);
+    }
+
+    #[test]
+    fn test_intel_hex_errors_report_line() {
+        let bad_sum = HELLO_HEX.replace("CA\n", "CB\n");
+        assert_eq!(
+            parse_intel_hex(&bad_sum).unwrap_err(),
+            FormatError { line: 1, kind: FormatErrorKind::BadChecksum { expected: 0xCA, found: 0xCB } }
+        );
+
+        let text = format!(":0100000000FF\n{}", ":020000040000FA\n");
+        assert_eq!(parse_intel_hex(&text).unwrap_err().kind, FormatErrorKind::UnsupportedType(0x04));
+        assert_eq!(parse_intel_hex(&text).unwrap_err().line, 2);
+
+        assert_eq!(parse_intel_hex(":0100000000FF\n").unwrap_err().kind, FormatErrorKind::MissingEnd);
+        assert_eq!(parse_hex_record("0100000000FF"), Err(FormatErrorKind::MissingStartCode));
+        assert_eq!(parse_hex_record(":0200000000FF"), Err(FormatErrorKind::BadLength));
+        assert_eq!(parse_hex_record(":02FFFF00000000"), Err(FormatErrorKind::AddressOverflow));
+    }
+
+    #[test]
+    fn test_intel_hex_round_trip_with_start() {
+        let data: Vec<u8> = (0..40).collect();
+        let text = write_intel_hex(&data, 0x2000, Some(0x2010));
+
+        assert!(text.ends_with(":00000001FF\n"));
+        let image = parse_intel_hex(&text).unwrap();
+        assert_eq!(image.segments, vec![(0x2000, data)]);
+        assert_eq!(image.start, Some(0x2010));
+    }
+
+    #[test]
+    fn test_srecord_round_trip() {
+        let data: Vec<u8> = (0..20).map(|i| i * 3).collect();
+        let text = write_srecord(&data, 0x0100, Some(0x0100));
+
+        assert!(text.starts_with("S0"));
+        assert!(text.contains("S5030002FA\n"));
+        let image = parse_srecord(&text).unwrap();
+        assert_eq!(image.segments, vec![(0x0100, data)]);
+        assert_eq!(image.start, Some(0x0100));
+    }
+
+    #[test]
+    fn test_srecord_errors() {
+        let err = parse_srecord("S0030000FC\nS1040100C932\n").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(matches!(err.kind, FormatErrorKind::BadChecksum { .. }));
+
+        assert_eq!(parse_srecord("S2060100000000F8\n").unwrap_err().kind, FormatErrorKind::AddressOverflow);
+        assert_eq!(parse_srecord("S4030000FC\n").unwrap_err().kind, FormatErrorKind::UnsupportedType(4));
+    }
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(Format::from_path(Path::new("prog.HEX")), Format::IntelHex);
+        assert_eq!(Format::from_path(Path::new("prog.s19")), Format::SRecord);
+        assert_eq!(Format::from_path(Path::new("prog.com")), Format::Binary);
+        assert_eq!(Format::from_path(Path::new("prog")), Format::Binary);
+    }
+
+    #[test]
+    fn test_load_and_save_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let hex = dir.path().join("hello.hex");
+        std::fs::write(&hex, write_intel_hex(&[0x3E, 0x42, 0x76], 0x0200, Some(0x0200))).unwrap();
+
+        let mut cpu = Intel8080::new();
+        let image = load_file(&mut cpu, &hex, 0x0100).unwrap();
+        assert_eq!(image.len(), 3);
+        assert_eq!(cpu.pc, 0x0200);
+        assert_eq!(cpu.read_byte(0x0201), 0x42);
+
+        let srec = dir.path().join("copy.s19");
+        assert_eq!(save_file(&mut cpu, &srec, 0x0200, 0x0202, None).unwrap(), 3);
+        let image = read_image(&srec, 0).unwrap();
+        assert_eq!(image.segments, vec![(0x0200, vec![0x3E, 0x42, 0x76])]);
+
+        let bin = dir.path().join("copy.bin");
+        save_file(&mut cpu, &bin, 0x0200, 0x0202, None).unwrap();
+        assert_eq!(read_image(&bin, 0x0300).unwrap().segments, vec![(0x0300, vec![0x3E, 0x42, 0x76])]);
+    }
+
+    #[test]
+    fn test_boot_starts_at_entry_point() {
+        let hex = write_intel_hex(&[0x00, 0x76], 0x0200, Some(0x0201));
+        let data = Image { segments: vec![(0x0300, vec![0x55])], start: None };
+        let mut cpu = Intel8080::new();
+        cpu.load_rom(&[0xC3, 0x00, 0xF0]);          // Monitor stand-in
+        cpu.pc = 0x1234;
+
+        boot(&mut cpu, &[parse_intel_hex(&hex).unwrap(), data.clone()]);
+        assert_eq!(cpu.pc, 0x0201);
+        assert!(!cpu.rom_overlay_enabled);
+        assert_eq!(cpu.read_byte(0x0300), 0x55);
+        cpu.execute_one();
+        assert!(cpu.halted);
+
+        // No start address: the monitor boots from the overlay at 0000
+        boot(&mut cpu, &[data]);
+        assert_eq!(cpu.pc, 0x0000);
+        assert!(cpu.rom_overlay_enabled);
+        assert_eq!(cpu.read_byte(0x0000), 0xC3);
+    }
+
+    #[test]
+    fn test_load_into_goes_under_rom() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("low.hex");
+        let hex: String = [(0x0000, &[0x11, 0x22][..]), (0x1200, &[0x33]), (0xF000, &[0x44])].iter()
+            .flat_map(|&(origin, data)| write_intel_hex(data, origin, None).lines().map(str::to_string).collect::<Vec<_>>())
+            .filter(|line| line != ":00000001FF")
+            .map(|line| line + "\n")
+            .collect();
+        std::fs::write(&path, hex + ":00000001FF\n").unwrap();
+
+        let mut cpu = Intel8080::new();
+        cpu.load_rom(&[0xC3, 0x00, 0xF0]);
+        cpu.reset();                                // Boot overlay on
+        cpu.pc = 0x0100;
+        let image = load_into(&mut cpu, &path, 0).unwrap();
+        assert_eq!(cpu.pc, 0x0100);
+        assert_eq!(hidden_bytes(&mut cpu, &image), 3);
+        assert_eq!(cpu.read_byte(0x1200), 0x33);
+
+        cpu.rom_overlay_enabled = false;
+        assert_eq!(cpu.read_byte(0x0001), 0x22);
+        assert_eq!(hidden_bytes(&mut cpu, &image), 1);      // Still under the ROM at F000
+    }
+
+    #[test]
+    fn test_save_rejects_reversed_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("none.bin");
+        let mut cpu = Intel8080::new();
+        let err = save_file(&mut cpu, &path, 0x0200, 0x01FF, None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,149 @@
+// host.rs - Emulator (host) commands
+//
+// A line typed at the console that starts with ':' is for the emulator,
+// not the 8080 (see DESIGN_DECISIONS.md). Devices that need host controls
+// register a command name here; the console hands complete lines over.
+//
+//   :help             List commands
+//   :<name> [args]    Run a registered command
+//
+// Handlers get the words after the name and return the text to show.
+//
+// Commands run while the 8080 is in the middle of an IN from the console,
+// so they can't touch its memory directly. Those that need it (:load,
+// :save) queue a job on MemoryJobs, a bus master that runs it as soon as
+// the instruction finishes and prints the result.
+
+use crate::io::BusMaster;
+use crate::memory::Memory;
+use std::io::Write;
+
+pub type Handler = Box<dyn FnMut(&[&str]) -> Result<String, String>>;
+
+pub type MemoryJob = Box<dyn FnOnce(&mut dyn Memory) -> Result<String, String>>;
+
+struct Command {
+    name: String,
+    usage: String,
+    handler: Handler,
+}
+
+#[derive(Default)]
+pub struct HostCommands {
+    commands: Vec<Command>,
+}
+
+impl HostCommands {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a command; `usage` is its line in :help
+    pub fn register(&mut self, name: &str, usage: &str, handler: Handler) {
+        self.commands.push(Command {
+            name: name.to_ascii_lowercase(),
+            usage: usage.to_string(),
+            handler,
+        });
+    }
+
+    /// Run one command line (with or without the ':'), returning the reply
+    pub fn execute(&mut self, line: &str) -> String {
+        let line = line.trim();
+        let mut words = line.strip_prefix(':').unwrap_or(line).split_whitespace();
+        let Some(name) = words.next().map(str::to_ascii_lowercase) else {
+            return String::new();
+        };
+        let args: Vec<&str> = words.collect();
+        if name == "help" || name == "?" {
+            let mut usage: Vec<&str> = self.commands.iter().map(|c| c.usage.as_str()).collect();
+            usage.sort_unstable();
+            return usage.join("\n");
+        }
+        match self.commands.iter_mut().find(|c| c.name == name) {
+            Some(command) => (command.handler)(&args).unwrap_or_else(|e| format!("Error: {}", e)),
+            None => format!("Unknown command :{} (try :help)", name),
+        }
+    }
+}
+
+/// Host work that needs the 8080's memory, run between instructions
+#[derive(Default)]
+pub struct MemoryJobs {
+    jobs: Vec<MemoryJob>,
+}
+
+impl MemoryJobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn queue(&mut self, job: MemoryJob) {
+        self.jobs.push(job);
+    }
+
+    /// Run queued jobs in order, returning their replies
+    pub fn run(&mut self, memory: &mut dyn Memory) -> Vec<String> {
+        self.jobs.drain(..)
+            .map(|job| job(memory).unwrap_or_else(|e| format!("Error: {}", e)))
+            .collect()
+    }
+}
+
+impl BusMaster for MemoryJobs {
+    fn service(&mut self, memory: &mut dyn Memory) -> u32 {
+        if !self.jobs.is_empty() {
+            for reply in self.run(memory) {
+                print!("{}\r\n", reply);
+            }
+            std::io::stdout().flush().ok();
+        }
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_dispatch_and_args() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let log = seen.clone();
+        let mut host = HostCommands::new();
+        host.register("echo", ":echo WORDS...", Box::new(move |args| {
+            log.borrow_mut().push(args.len());
+            Ok(args.join(" "))
+        }));
+
+        assert_eq!(host.execute(":echo  a   b"), "a b");
+        assert_eq!(host.execute("  :ECHO c "), "c");
+        assert_eq!(host.execute("echo"), "");
+        assert_eq!(*seen.borrow(), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn test_errors_and_help() {
+        let mut host = HostCommands::new();
+        host.register("zap", ":zap", Box::new(|_| Err("no target".to_string())));
+        host.register("beep", ":beep", Box::new(|_| Ok(String::new())));
+
+        assert_eq!(host.execute(":zap"), "Error: no target");
+        assert_eq!(host.execute(":bogus"), "Unknown command :bogus (try :help)");
+        assert_eq!(host.execute(":help"), ":beep\n:zap");
+        assert_eq!(host.execute(":"), "");
+    }
+
+    #[test]
+    fn test_memory_jobs_run_in_order() {
+        let mut memory = crate::memory::FlatMemory::new();
+        let mut jobs = MemoryJobs::new();
+        jobs.queue(Box::new(|m| { m.write(0x10, 0x42); Ok("poked".to_string()) }));
+        jobs.queue(Box::new(|m| Err(format!("saw {:02X}", m.read(0x10)))));
+
+        assert_eq!(jobs.run(&mut memory), vec!["poked", "Error: saw 42"]);
+        assert!(jobs.run(&mut memory).is_empty());
+    }
+}
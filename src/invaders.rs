@@ -0,0 +1,362 @@
+// invaders.rs - Taito Space Invaders machine profile
+//
+// Memory map:
+//   0x0000-0x1FFF: ROM (8KB: invaders.h, .g, .f, .e) - writes ignored
+//   0x2000-0x23FF: RAM
+//   0x2400-0x3FFF: Video RAM (256x224, 1 bit per pixel)
+//   0x4000-0xFFFF: Mirrors of 0x0000-0x3FFF
+//
+// I/O ports:
+//   IN 1:  Player 1 / coin     IN 2:  Player 2 / DIP switches
+//   IN 3:  Shift register result
+//   OUT 2: Shift amount (bits 0-2)
+//   OUT 3: Sound bank 1        OUT 5: Sound bank 2
+//   OUT 4: Shift data (new byte in the high half, old high moves down)
+//   OUT 6: Watchdog (ignored)
+//
+// Input port 1: bit 0 coin, 1 P2 start, 2 P1 start, 3 always 1,
+//               4 P1 fire, 5 P1 left, 6 P1 right
+// Input port 2: bits 0-1 ships (DIP), 2 tilt, 3 bonus life (DIP),
+//               4 P2 fire, 5 P2 left, 6 P2 right, 7 coin info (DIP)
+//
+// Interrupts: the board jams RST 1 when the beam reaches mid-screen and
+// RST 2 at vblank, 60 frames a second on a 2 MHz CPU.
+//
+// Video RAM holds the monitor's scanlines: 224 lines of 32 bytes, least
+// significant bit first. The monitor is mounted on its side, so the
+// upright picture is that frame turned a quarter turn counter-clockwise.
+
+use crate::cpu::Intel8080;
+use crate::io::{Clocked, InterruptSource, IoDevice};
+use crate::memory::Memory;
+use crate::video::Frame;
+use std::cell::RefCell;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+pub const ROM_SIZE: usize = 0x2000;
+pub const CYCLES_PER_FRAME: u64 = 2_000_000 / 60;
+pub const WIDTH: usize = 256;
+pub const HEIGHT: usize = 224;
+const VRAM: u16 = 0x2400;
+
+/// ROM set files, in address order
+pub const ROM_FILES: [&str; 4] = ["invaders.h", "invaders.g", "invaders.f", "invaders.e"];
+
+/// Cabinet controls
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    Coin,
+    Start1,
+    Start2,
+    Fire1,
+    Left1,
+    Right1,
+    Fire2,
+    Left2,
+    Right2,
+    Tilt,
+}
+
+impl Button {
+    /// Input port and bit
+    fn wiring(self) -> (u8, u8) {
+        match self {
+            Button::Coin => (1, 0),
+            Button::Start2 => (1, 1),
+            Button::Start1 => (1, 2),
+            Button::Fire1 => (1, 4),
+            Button::Left1 => (1, 5),
+            Button::Right1 => (1, 6),
+            Button::Tilt => (2, 2),
+            Button::Fire2 => (2, 4),
+            Button::Left2 => (2, 5),
+            Button::Right2 => (2, 6),
+        }
+    }
+}
+
+struct InvadersMemory {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+}
+
+impl Memory for InvadersMemory {
+    fn read(&mut self, addr: u16) -> u8 {
+        let addr = (addr & 0x3FFF) as usize;
+        if addr < ROM_SIZE { self.rom[addr] } else { self.ram[addr - ROM_SIZE] }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        let addr = (addr & 0x3FFF) as usize;
+        if addr >= ROM_SIZE {
+            self.ram[addr - ROM_SIZE] = value;
+        }
+    }
+}
+
+/// Shift register, inputs, sound latches and beam timing
+struct Board {
+    shift: u16,
+    shift_offset: u8,
+    inputs: [u8; 2],
+    sound: [u8; 2],
+    beam: u64,
+    pending: Option<u16>,
+}
+
+impl IoDevice for Board {
+    fn read(&mut self, port: u8) -> u8 {
+        match port {
+            1 => self.inputs[0] | 0x08,
+            2 => self.inputs[1],
+            3 => (self.shift >> (8 - self.shift_offset)) as u8,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) {
+        match port {
+            2 => self.shift_offset = value & 0x07,
+            3 => self.sound[0] = value,
+            4 => self.shift = (value as u16) << 8 | self.shift >> 8,
+            5 => self.sound[1] = value,
+            _ => {}
+        }
+    }
+}
+
+impl Clocked for Board {
+    fn tick(&mut self, cycles: u64) {
+        let before = self.beam;
+        self.beam += cycles;
+        if before < CYCLES_PER_FRAME / 2 && self.beam >= CYCLES_PER_FRAME / 2 {
+            self.pending = Some(0x0008);    // RST 1
+        }
+        if self.beam >= CYCLES_PER_FRAME {
+            self.beam -= CYCLES_PER_FRAME;
+            self.pending = Some(0x0010);    // RST 2
+        }
+    }
+}
+
+impl InterruptSource for Board {
+    fn interrupt_requested(&mut self) -> bool {
+        self.pending.is_some()
+    }
+
+    fn acknowledge(&mut self) -> u16 {
+        self.pending.take().unwrap_or(0x0010)
+    }
+}
+
+pub struct Invaders {
+    cpu: Intel8080,
+    board: Rc<RefCell<Board>>,
+    frame_end: u64,
+}
+
+impl Invaders {
+    /// Machine running `rom` (up to 8KB, from 0x0000)
+    pub fn new(rom: &[u8]) -> Self {
+        let mut image = vec![0u8; ROM_SIZE];
+        let len = rom.len().min(ROM_SIZE);
+        image[..len].copy_from_slice(&rom[..len]);
+        let memory = InvadersMemory { rom: image, ram: vec![0; 0x4000 - ROM_SIZE] };
+
+        let board = Rc::new(RefCell::new(Board {
+            shift: 0,
+            shift_offset: 0,
+            inputs: [0; 2],
+            sound: [0; 2],
+            beam: 0,
+            pending: None,
+        }));
+        let mut cpu = Intel8080::with_memory(Box::new(memory));
+        for port in 1..=6 {
+            cpu.io_bus_mut().map_port(port, board.clone());
+        }
+        cpu.attach_clocked(board.clone());
+        cpu.attach_interrupt_source(board.clone());
+        cpu.pc = 0x0000;
+        cpu.sp = 0x2400;
+        Invaders { cpu, board, frame_end: 0 }
+    }
+
+    /// Load the ROM from one 8KB file, or a directory holding the
+    /// invaders.h/.g/.f/.e set
+    pub fn from_path(path: &Path) -> io::Result<Self> {
+        let rom = if path.is_dir() {
+            let mut rom = Vec::with_capacity(ROM_SIZE);
+            for name in ROM_FILES {
+                rom.extend(std::fs::read(path.join(name))?);
+            }
+            rom
+        } else {
+            std::fs::read(path)?
+        };
+        if rom.len() > ROM_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "ROM larger than 8KB"));
+        }
+        Ok(Self::new(&rom))
+    }
+
+    pub fn cpu(&mut self) -> &mut Intel8080 {
+        &mut self.cpu
+    }
+
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        let (port, bit) = button.wiring();
+        let mut board = self.board.borrow_mut();
+        let input = &mut board.inputs[port as usize - 1];
+        if pressed {
+            *input |= 1 << bit;
+        } else {
+            *input &= !(1 << bit);
+        }
+    }
+
+    /// DIP switches on input port 2: ships (3-6), bonus life at 1000
+    /// rather than 1500, coin info hidden
+    pub fn set_dip_switches(&mut self, ships: u8, early_bonus: bool, hide_coin_info: bool) {
+        let mut board = self.board.borrow_mut();
+        let input = &mut board.inputs[1];
+        *input &= !0x8B;
+        *input |= ships.clamp(3, 6) - 3;
+        if early_bonus {
+            *input |= 0x08;
+        }
+        if hide_coin_info {
+            *input |= 0x80;
+        }
+    }
+
+    /// Sound latches (ports 3 and 5)
+    pub fn sound(&self) -> [u8; 2] {
+        self.board.borrow().sound
+    }
+
+    /// Run one 1/60 s frame of CPU time (stops early on HLT)
+    pub fn run_frame(&mut self) {
+        self.frame_end += CYCLES_PER_FRAME;
+        while self.cpu.cycles < self.frame_end && !self.cpu.halted {
+            self.cpu.execute_one();
+        }
+    }
+
+    /// Video RAM as stored: 256 pixels across each of 224 scanlines
+    pub fn framebuffer(&mut self) -> Frame {
+        let mut frame = Frame::new(WIDTH, HEIGHT);
+        for line in 0..HEIGHT {
+            for byte in 0..WIDTH / 8 {
+                let value = self.cpu.read_byte(VRAM + (line * WIDTH / 8 + byte) as u16);
+                for bit in 0..8 {
+                    frame.set(byte * 8 + bit, line, value & (1 << bit) != 0);
+                }
+            }
+        }
+        frame
+    }
+
+    /// The picture as the player sees it: 224 wide, 256 tall
+    pub fn screen(&mut self) -> Frame {
+        self.framebuffer().rotated_ccw()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shift_register() {
+        let mut machine = Invaders::new(&[]);
+        let cpu = machine.cpu();
+        cpu.io_bus_mut().write(4, 0xAB);
+        cpu.io_bus_mut().write(4, 0xCD);    // Register = CDAB
+        cpu.io_bus_mut().write(2, 0);
+        assert_eq!(cpu.io_bus_mut().read(3), 0xCD);
+        cpu.io_bus_mut().write(2, 4);
+        assert_eq!(cpu.io_bus_mut().read(3), 0xDA);
+        cpu.io_bus_mut().write(2, 7);
+        assert_eq!(cpu.io_bus_mut().read(3), 0xD5);
+    }
+
+    #[test]
+    fn test_buttons_and_dip_switches() {
+        let mut machine = Invaders::new(&[]);
+        assert_eq!(machine.cpu().io_bus_mut().read(1), 0x08);
+
+        machine.set_button(Button::Coin, true);
+        machine.set_button(Button::Fire1, true);
+        assert_eq!(machine.cpu().io_bus_mut().read(1), 0x19);
+        machine.set_button(Button::Coin, false);
+        assert_eq!(machine.cpu().io_bus_mut().read(1), 0x18);
+
+        machine.set_button(Button::Right2, true);
+        machine.set_dip_switches(5, true, false);
+        assert_eq!(machine.cpu().io_bus_mut().read(2), 0x4A);
+    }
+
+    #[test]
+    fn test_memory_map() {
+        let mut machine = Invaders::new(&[0x3C]);
+        let cpu = machine.cpu();
+        cpu.write_byte(0x0000, 0xFF);       // ROM
+        assert_eq!(cpu.read_byte(0x0000), 0x3C);
+        assert_eq!(cpu.read_byte(0x4000), 0x3C);
+        cpu.write_byte(0x2100, 0x55);
+        assert_eq!(cpu.read_byte(0x6100), 0x55);
+    }
+
+    #[test]
+    fn test_mid_screen_and_vblank_interrupts() {
+        // 0000: EI / JMP 0001
+        // 0008 (RST 1): LXI H,2000 / INR M / EI / RET
+        // 0010 (RST 2): LXI H,2001 / INR M / EI / RET
+        let mut rom = vec![0u8; 0x18];
+        rom[0x00..0x04].copy_from_slice(&[0xFB, 0xC3, 0x01, 0x00]);
+        rom[0x08..0x0E].copy_from_slice(&[0x21, 0x00, 0x20, 0x34, 0xFB, 0xC9]);
+        rom[0x10..0x16].copy_from_slice(&[0x21, 0x01, 0x20, 0x34, 0xFB, 0xC9]);
+        let mut machine = Invaders::new(&rom);
+
+        for _ in 0..3 {
+            machine.run_frame();
+        }
+        assert_eq!(machine.cpu().read_byte(0x2000), 3);
+        assert_eq!(machine.cpu().read_byte(0x2001), 2);   // Third is due next frame
+    }
+
+    #[test]
+    fn test_framebuffer_and_screen() {
+        // MVI A,81H / STA 2400H / HLT
+        let mut machine = Invaders::new(&[0x3E, 0x81, 0x32, 0x00, 0x24, 0x76]);
+        machine.run_frame();
+
+        let frame = machine.framebuffer();
+        assert_eq!((frame.width(), frame.height()), (256, 224));
+        assert!(frame.get(0, 0) && frame.get(7, 0));
+        assert_eq!(frame.lit(), 2);
+
+        let screen = machine.screen();
+        assert_eq!((screen.width(), screen.height()), (224, 256));
+        assert!(screen.get(0, 255) && screen.get(0, 248));
+        assert!(screen.to_blocks().lines().last().unwrap().starts_with('▖'));
+    }
+
+    #[test]
+    fn test_rom_set_from_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        for (i, name) in ROM_FILES.iter().enumerate() {
+            std::fs::write(dir.path().join(name), vec![i as u8; 0x800]).unwrap();
+        }
+        let mut machine = Invaders::from_path(dir.path()).unwrap();
+        assert_eq!(machine.cpu().read_byte(0x07FF), 0);
+        assert_eq!(machine.cpu().read_byte(0x0800), 1);
+        assert_eq!(machine.cpu().read_byte(0x1800), 3);
+
+        std::fs::remove_file(dir.path().join("invaders.e")).unwrap();
+        assert!(Invaders::from_path(dir.path()).is_err());
+    }
+}
@@ -0,0 +1,9 @@
+use crate::memory::Memory;
+
+/// Bus master trait - for devices that read or write memory themselves
+/// (loaders, DMA). The CPU hands over the bus after every instruction.
+/// Accesses go through the CPU's view of memory, so ROM stays protected.
+pub trait BusMaster {
+    /// Use the bus; returns the cycles the CPU was held off it
+    fn service(&mut self, memory: &mut dyn Memory) -> u32;
+}
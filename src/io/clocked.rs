@@ -0,0 +1,6 @@
+/// Clocked device trait - for devices that need to see emulated time.
+/// Kept separate from IoDevice so port-only devices stay two methods.
+pub trait Clocked {
+    /// Called by the CPU after every instruction with the cycles it took
+    fn tick(&mut self, cycles: u64);
+}
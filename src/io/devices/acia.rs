@@ -0,0 +1,268 @@
+// acia.rs - Motorola 6850 ACIA (MITS 88-2SIO serial port)
+//
+// Port base+0: Status (read) / Control (write)
+// Port base+1: RX data (read) / TX data (write)
+//
+// The 88-2SIO puts its first port at 0x10/0x11, which is where Altair
+// BASIC looks. The second port (0x12/0x13) is another instance.
+//
+// Control register:
+//   Bits 0-1: Counter divide - 00 /1, 01 /16, 10 /64, 11 master reset
+//   Bits 2-4: Word select
+//             000 7E2  001 7O2  010 7E1  011 7O1
+//             100 8N2  101 8N1  110 8E1  111 8O1
+//   Bits 5-6: TX control - 01 = TX interrupt enabled, 11 = send break
+//   Bit 7:    RX interrupt enable
+//
+// Status register:
+//   Bit 0: RDRF - receive data register full
+//   Bit 1: TDRE - transmit data register empty
+//   Bit 2: DCD  - carrier lost (always 0)
+//   Bit 3: CTS  - clear to send lost (always 0)
+//   Bit 4: FE   - framing error (always 0)
+//   Bit 5: OVRN - receiver overrun (always 0)
+//   Bit 6: PE   - parity error (always 0)
+//   Bit 7: IRQ  - interrupt request
+//
+// Bytes move instantly, so TDRE is always set outside master reset. The
+// wire is a SerialBackend; RX data is pulled from it when software looks.
+// 7-bit word formats strip bit 7 both ways. After master reset the chip
+// sits idle (status 0x00) until a new control word arrives.
+//
+// With an interrupt source attached, IRQ calls the configured RST vector
+// (default RST 7). It stays asserted while RX data is waiting (or TDRE
+// with TX interrupts on), like the real line.
+
+use crate::io::serial::SerialBackend;
+use crate::io::{InterruptSource, IoDevice};
+
+pub const STATUS_RDRF: u8 = 0x01;
+pub const STATUS_TDRE: u8 = 0x02;
+pub const STATUS_IRQ: u8 = 0x80;
+
+const CONTROL_MASTER_RESET: u8 = 0x03;
+const CONTROL_TX_MASK: u8 = 0x60;
+const CONTROL_TX_INT: u8 = 0x20;
+const CONTROL_RX_INT: u8 = 0x80;
+
+pub const DEFAULT_BASE: u8 = 0x10;
+
+pub struct Acia6850 {
+    base: u8,
+    backend: Box<dyn SerialBackend>,
+    control: u8,
+    in_reset: bool,
+    rx_data: Option<u8>,
+    vector: u16,
+}
+
+impl Acia6850 {
+    /// ACIA on `base`/`base + 1`, talking to `backend`
+    pub fn new(base: u8, backend: Box<dyn SerialBackend>) -> Self {
+        Acia6850 {
+            base,
+            backend,
+            control: 0x15,      // 8N1, /16 - what Altair software sets anyway
+            in_reset: false,
+            rx_data: None,
+            vector: 0x0038,
+        }
+    }
+
+    /// RST vector (0-7) used when this ACIA interrupts the CPU
+    pub fn set_interrupt_vector(&mut self, rst: u8) {
+        self.vector = (rst as u16 & 0x07) * 8;
+    }
+
+    pub fn control(&self) -> u8 {
+        self.control
+    }
+
+    fn seven_bit(&self) -> bool {
+        self.control & 0x10 == 0
+    }
+
+    /// Latch the next byte from the wire if the data register is free
+    fn poll_rx(&mut self) {
+        if self.in_reset || self.rx_data.is_some() {
+            return;
+        }
+        self.rx_data = self.backend.read_byte().map(|b| if self.seven_bit() { b & 0x7F } else { b });
+    }
+
+    fn irq(&self) -> bool {
+        if self.in_reset {
+            return false;
+        }
+        let rx = self.control & CONTROL_RX_INT != 0 && self.rx_data.is_some();
+        let tx = self.control & CONTROL_TX_MASK == CONTROL_TX_INT;
+        rx || tx
+    }
+
+    fn status(&mut self) -> u8 {
+        self.poll_rx();
+        if self.in_reset {
+            return 0x00;
+        }
+        let mut status = STATUS_TDRE;
+        if self.rx_data.is_some() {
+            status |= STATUS_RDRF;
+        }
+        if self.irq() {
+            status |= STATUS_IRQ;
+        }
+        status
+    }
+
+    fn write_control(&mut self, value: u8) {
+        self.in_reset = value & 0x03 == CONTROL_MASTER_RESET;
+        if self.in_reset {
+            self.rx_data = None;
+        }
+        self.control = value;
+    }
+}
+
+impl IoDevice for Acia6850 {
+    fn read(&mut self, port: u8) -> u8 {
+        match port.wrapping_sub(self.base) {
+            0 => self.status(),
+            1 => {
+                self.poll_rx();
+                self.rx_data.take().unwrap_or(0x00)
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) {
+        match port.wrapping_sub(self.base) {
+            0 => self.write_control(value),
+            1 if !self.in_reset => {
+                let value = if self.seven_bit() { value & 0x7F } else { value };
+                self.backend.write_byte(value);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl InterruptSource for Acia6850 {
+    fn interrupt_requested(&mut self) -> bool {
+        // Only touch the wire when RX interrupts are on, so an idle ACIA
+        // sharing the terminal never takes keys meant for the console
+        if self.control & CONTROL_RX_INT != 0 {
+            self.poll_rx();
+        }
+        self.irq()
+    }
+
+    fn acknowledge(&mut self) -> u16 {
+        self.vector
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Intel8080;
+    use crate::io::serial::BufferBackend;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn acia() -> (Acia6850, BufferBackend) {
+        let wire = BufferBackend::new();
+        (Acia6850::new(0x10, Box::new(wire.clone())), wire)
+    }
+
+    #[test]
+    fn test_transmit() {
+        let (mut dev, wire) = acia();
+
+        assert_eq!(dev.read(0x10), STATUS_TDRE);
+        dev.write(0x11, b'O');
+        dev.write(0x11, b'K');
+        assert_eq!(wire.take_output(), b"OK");
+    }
+
+    #[test]
+    fn test_receive_sets_and_clears_rdrf() {
+        let (mut dev, wire) = acia();
+
+        wire.push_input(b"AB");
+        assert_eq!(dev.read(0x10), STATUS_TDRE | STATUS_RDRF);
+        assert_eq!(dev.read(0x11), b'A');
+        assert_eq!(dev.read(0x10) & STATUS_RDRF, STATUS_RDRF);
+        assert_eq!(dev.read(0x11), b'B');
+        assert_eq!(dev.read(0x10), STATUS_TDRE);
+    }
+
+    #[test]
+    fn test_master_reset_idles_until_configured() {
+        let (mut dev, wire) = acia();
+        wire.push_input(b"X");
+
+        dev.write(0x10, 0x03);              // Master reset
+        assert_eq!(dev.read(0x10), 0x00);
+        dev.write(0x11, b'!');
+        assert!(wire.take_output().is_empty());
+
+        dev.write(0x10, 0x11);              // 8N2, /16
+        assert_eq!(dev.read(0x10), STATUS_TDRE | STATUS_RDRF);
+        assert_eq!(dev.read(0x11), b'X');
+    }
+
+    #[test]
+    fn test_seven_bit_word_strips_bit_7() {
+        let (mut dev, wire) = acia();
+
+        dev.write(0x10, 0x09);              // 7E1
+        wire.push_input(&[0xC1]);
+        assert_eq!(dev.read(0x11), 0x41);
+        dev.write(0x11, 0xC2);
+        assert_eq!(wire.take_output(), vec![0x42]);
+    }
+
+    #[test]
+    fn test_irq_status_bit() {
+        let (mut dev, wire) = acia();
+
+        dev.write(0x10, 0x95);              // 8N1, RX interrupt enable
+        assert_eq!(dev.read(0x10) & STATUS_IRQ, 0);
+        wire.push_input(b"Z");
+        assert_eq!(dev.read(0x10) & STATUS_IRQ, STATUS_IRQ);
+        dev.read(0x11);
+        assert_eq!(dev.read(0x10) & STATUS_IRQ, 0);
+
+        dev.write(0x10, 0x35);              // TX interrupt: TDRE always asserts
+        assert_eq!(dev.read(0x10) & STATUS_IRQ, STATUS_IRQ);
+    }
+
+    #[test]
+    fn test_rx_interrupt_calls_vector() {
+        // 0000: EI / JMP 0001   0028 (RST 5): IN 11H / HLT
+        let mut cpu = Intel8080::new();
+        cpu.load_program(&[0xDB, 0x11, 0x76], 0x0028);
+        cpu.load_program(&[0xFB, 0xC3, 0x01, 0x00], 0x0000);
+        cpu.sp = 0x8000;
+
+        let wire = BufferBackend::new();
+        let mut dev = Acia6850::new(0x10, Box::new(wire.clone()));
+        dev.set_interrupt_vector(5);
+        dev.write(0x10, 0x95);
+        let dev = Rc::new(RefCell::new(dev));
+        cpu.io_bus_mut().map_port(0x10, dev.clone());
+        cpu.io_bus_mut().map_port(0x11, dev.clone());
+        cpu.attach_interrupt_source(dev.clone());
+
+        for _ in 0..10 {
+            cpu.execute_one();
+        }
+        assert_eq!(cpu.pc, 0x0001);         // Spinning, nothing received
+
+        wire.push_input(b"K");
+        cpu.run();
+        assert_eq!(cpu.a, b'K');
+        assert!(!dev.borrow_mut().interrupt_requested());
+    }
+}
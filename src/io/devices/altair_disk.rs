@@ -0,0 +1,533 @@
+// altair_disk.rs - MITS 88-DCDD 8" floppy disk controller
+//
+// Ports are relative to a configurable base (MITS default 0x08, which is
+// Storage here, so the emulator maps it at 0x88):
+//
+// Port base+0: Status (read) / Drive select (write)
+// Port base+1: Sector position (read) / Drive control (write)
+// Port base+2: Read data (read) / Write data (write)
+//
+// Drive select (write base+0):
+//   Bits 0-3: Drive number (0-15)
+//   Bit 7:    1 = deselect (controller off)
+//
+// Status (read base+0) - active low, 0 = true:
+//   Bit 0: ENWD  - ready for a write byte
+//   Bit 1: MH    - head movement allowed
+//   Bit 2: HS    - head loaded
+//   Bit 5: INTE  - interrupts enabled
+//   Bit 6: TRK0  - head on track 0
+//   Bit 7: NRDA  - read data available
+//   Bits 3-4 read 0. No drive selected reads 0xFF.
+//
+// Drive control (write base+1):
+//   Bit 0: Step in (toward track 76)
+//   Bit 1: Step out (toward track 0)
+//   Bit 2: Head load
+//   Bit 3: Head unload
+//   Bit 4: Interrupt enable
+//   Bit 5: Interrupt disable
+//   Bit 6: Head current switch (ignored)
+//   Bit 7: Write enable - start writing the current sector
+//
+// Sector position (read base+1), head loaded:
+//   Bit 0:    Sector true (0 = at the start of the sector)
+//   Bits 1-5: Sector number (0-31)
+//   Bits 6-7: 1
+//   Each read moves the disk on: sector true alternates, and a new sector
+//   comes round every other read. Head unloaded reads 0xFF.
+//
+// Images are standard .dsk files: 77 tracks x 32 sectors x 137 bytes
+// (337,568 bytes), track-major. Sector writes go straight to the file.
+// Write-protected drives ignore write enable; their image is opened
+// read-only, and clearing the protection reopens it for writing.
+
+use crate::io::IoDevice;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_BASE: u8 = 0x08;
+pub const DRIVES: usize = 16;
+pub const TRACKS: usize = 77;
+pub const SECTORS: usize = 32;
+pub const SECTOR_BYTES: usize = 137;
+pub const IMAGE_BYTES: usize = TRACKS * SECTORS * SECTOR_BYTES;
+
+const STATUS_ENWD: u8 = 0x01;
+const STATUS_MOVE_HEAD: u8 = 0x02;
+const STATUS_HEAD: u8 = 0x04;
+const STATUS_INTE: u8 = 0x20;
+const STATUS_TRACK0: u8 = 0x40;
+const STATUS_NRDA: u8 = 0x80;
+
+const CONTROL_STEP_IN: u8 = 0x01;
+const CONTROL_STEP_OUT: u8 = 0x02;
+const CONTROL_HEAD_LOAD: u8 = 0x04;
+const CONTROL_HEAD_UNLOAD: u8 = 0x08;
+const CONTROL_INT_ENABLE: u8 = 0x10;
+const CONTROL_INT_DISABLE: u8 = 0x20;
+const CONTROL_WRITE_ENABLE: u8 = 0x80;
+
+struct Drive {
+    file: File,
+    path: PathBuf,
+    data: Vec<u8>,
+    write_protect: bool,
+    track: usize,
+    head_loaded: bool,
+}
+
+pub struct AltairDisk {
+    base: u8,
+    drives: Vec<Option<Drive>>,
+    selected: Option<usize>,
+    sector: usize,
+    sector_true: bool,
+    read_pos: usize,
+    writing: bool,
+    write_buf: Vec<u8>,
+    interrupts: bool,
+}
+
+impl AltairDisk {
+    /// Controller answering on `base`..=`base + 2`
+    pub fn new(base: u8) -> Self {
+        AltairDisk {
+            base,
+            drives: (0..DRIVES).map(|_| None).collect(),
+            selected: None,
+            sector: 0,
+            sector_true: false,
+            read_pos: 0,
+            writing: false,
+            write_buf: Vec::with_capacity(SECTOR_BYTES),
+            interrupts: false,
+        }
+    }
+
+    pub fn base(&self) -> u8 {
+        self.base
+    }
+
+    /// Mount a .dsk image. Short images read as zeros past the end.
+    pub fn mount(&mut self, drive: usize, path: &Path, write_protect: bool) -> io::Result<()> {
+        if drive >= DRIVES {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "no such drive"));
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(!write_protect)
+            .open(path)?;
+        let mut data = Vec::with_capacity(IMAGE_BYTES);
+        file.read_to_end(&mut data)?;
+        if data.len() > IMAGE_BYTES {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "image larger than 77 tracks"));
+        }
+        data.resize(IMAGE_BYTES, 0);
+        self.unmount(drive);
+        self.drives[drive] = Some(Drive {
+            file,
+            path: path.to_path_buf(),
+            data,
+            write_protect,
+            track: 0,
+            head_loaded: false,
+        });
+        Ok(())
+    }
+
+    pub fn unmount(&mut self, drive: usize) {
+        if self.selected == Some(drive) {
+            self.finish_write();
+            self.selected = None;
+        }
+        if let Some(slot) = self.drives.get_mut(drive) {
+            *slot = None;
+        }
+    }
+
+    pub fn is_mounted(&self, drive: usize) -> bool {
+        self.drives.get(drive).is_some_and(|d| d.is_some())
+    }
+
+    /// Set or clear the write-protect tab. Clearing it reopens the image
+    /// for writing; if that fails the drive stays protected.
+    pub fn set_write_protect(&mut self, drive: usize, write_protect: bool) -> io::Result<()> {
+        let Some(Some(d)) = self.drives.get_mut(drive) else {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "no disk in drive"));
+        };
+        if d.write_protect && !write_protect {
+            d.file = OpenOptions::new().read(true).write(true).open(&d.path)?;
+        }
+        d.write_protect = write_protect;
+        Ok(())
+    }
+
+    pub fn is_write_protected(&self, drive: usize) -> bool {
+        matches!(self.drives.get(drive), Some(Some(d)) if d.write_protect)
+    }
+
+    /// Current track of a drive, for host-side inspection
+    pub fn track(&self, drive: usize) -> Option<usize> {
+        self.drives.get(drive)?.as_ref().map(|d| d.track)
+    }
+
+    fn drive(&mut self) -> Option<&mut Drive> {
+        self.drives.get_mut(self.selected?)?.as_mut()
+    }
+
+    fn offset(track: usize, sector: usize) -> usize {
+        (track * SECTORS + sector) * SECTOR_BYTES
+    }
+
+    fn select(&mut self, value: u8) {
+        self.finish_write();
+        let drive = (value & 0x0F) as usize;
+        self.selected = if value & 0x80 == 0 && self.is_mounted(drive) {
+            Some(drive)
+        } else {
+            None
+        };
+        self.read_pos = 0;
+    }
+
+    fn control(&mut self, value: u8) {
+        if self.drive().is_none() {
+            return;
+        }
+        if value & (CONTROL_STEP_IN | CONTROL_STEP_OUT | CONTROL_HEAD_UNLOAD) != 0 {
+            self.finish_write();
+        }
+        let drive = self.drive().expect("checked above");
+        if value & CONTROL_STEP_IN != 0 && drive.track < TRACKS - 1 {
+            drive.track += 1;
+        }
+        if value & CONTROL_STEP_OUT != 0 && drive.track > 0 {
+            drive.track -= 1;
+        }
+        if value & CONTROL_HEAD_LOAD != 0 {
+            drive.head_loaded = true;
+        }
+        if value & CONTROL_HEAD_UNLOAD != 0 {
+            drive.head_loaded = false;
+        }
+        let writable = drive.head_loaded && !drive.write_protect;
+        if value & CONTROL_INT_ENABLE != 0 {
+            self.interrupts = true;
+        }
+        if value & CONTROL_INT_DISABLE != 0 {
+            self.interrupts = false;
+        }
+        if value & CONTROL_WRITE_ENABLE != 0 && writable {
+            self.writing = true;
+            self.write_buf.clear();
+        }
+    }
+
+    fn status(&mut self) -> u8 {
+        let writing = self.writing;
+        let interrupts = self.interrupts;
+        let read_pos = self.read_pos;
+        let drive = match self.drive() {
+            Some(d) => d,
+            None => return 0xFF,
+        };
+        // Build the true bits, then invert: the bus is active low
+        let mut active = STATUS_MOVE_HEAD;
+        if writing {
+            active |= STATUS_ENWD;
+        }
+        if drive.head_loaded {
+            active |= STATUS_HEAD;
+            if !writing && read_pos < SECTOR_BYTES {
+                active |= STATUS_NRDA;
+            }
+        }
+        if interrupts {
+            active |= STATUS_INTE;
+        }
+        if drive.track == 0 {
+            active |= STATUS_TRACK0;
+        }
+        !active & 0xE7
+    }
+
+    fn sector_position(&mut self) -> u8 {
+        match self.drive() {
+            Some(d) if d.head_loaded => {}
+            _ => return 0xFF,
+        }
+        self.sector_true = !self.sector_true;
+        if self.sector_true {
+            self.finish_write();
+            self.sector = (self.sector + 1) % SECTORS;
+            self.read_pos = 0;
+        }
+        0xC0 | ((self.sector as u8) << 1) | if self.sector_true { 0 } else { 1 }
+    }
+
+    fn read_data(&mut self) -> u8 {
+        let sector = self.sector;
+        let pos = self.read_pos;
+        let drive = match self.drive() {
+            Some(d) if d.head_loaded && pos < SECTOR_BYTES => d,
+            _ => return 0x00,
+        };
+        let value = drive.data[Self::offset(drive.track, sector) + pos];
+        self.read_pos += 1;
+        value
+    }
+
+    fn write_data(&mut self, value: u8) {
+        if !self.writing {
+            return;
+        }
+        self.write_buf.push(value);
+        if self.write_buf.len() == SECTOR_BYTES {
+            self.finish_write();
+        }
+    }
+
+    /// Commit the sector being written (short writes are zero padded)
+    fn finish_write(&mut self) {
+        if !self.writing {
+            return;
+        }
+        self.writing = false;
+        let mut buf = std::mem::take(&mut self.write_buf);
+        if buf.is_empty() {
+            return;
+        }
+        buf.resize(SECTOR_BYTES, 0);
+        let sector = self.sector;
+        if let Some(drive) = self.drive() {
+            let offset = Self::offset(drive.track, sector);
+            drive.data[offset..offset + SECTOR_BYTES].copy_from_slice(&buf);
+            let _ = drive.file.seek(SeekFrom::Start(offset as u64))
+                .and_then(|_| drive.file.write_all(&buf));
+        }
+        self.write_buf = buf;
+        self.write_buf.clear();
+    }
+}
+
+impl Default for AltairDisk {
+    fn default() -> Self {
+        Self::new(DEFAULT_BASE)
+    }
+}
+
+impl IoDevice for AltairDisk {
+    fn read(&mut self, port: u8) -> u8 {
+        match port.wrapping_sub(self.base) {
+            0 => self.status(),
+            1 => self.sector_position(),
+            2 => self.read_data(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) {
+        match port.wrapping_sub(self.base) {
+            0 => self.select(value),
+            1 => self.control(value),
+            2 => self.write_data(value),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: u8 = 0x88;
+
+    /// Image where every byte of a sector holds (track + sector) as a marker
+    fn make_image(dir: &Path, name: &str) -> std::path::PathBuf {
+        let mut data = vec![0u8; IMAGE_BYTES];
+        for track in 0..TRACKS {
+            for sector in 0..SECTORS {
+                let offset = AltairDisk::offset(track, sector);
+                data[offset..offset + SECTOR_BYTES].fill((track + sector) as u8);
+            }
+        }
+        let path = dir.join(name);
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    fn ready(disk: &mut AltairDisk, drive: u8) {
+        disk.write(BASE, drive);
+        disk.write(BASE + 1, CONTROL_HEAD_LOAD);
+    }
+
+    /// Spin until `sector` is under the head with sector true
+    fn seek_sector(disk: &mut AltairDisk, sector: u8) {
+        loop {
+            let pos = disk.read(BASE + 1);
+            if pos & 0x01 == 0 && (pos >> 1) & 0x1F == sector {
+                return;
+            }
+        }
+    }
+
+    #[test]
+    fn test_status_when_deselected() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut disk = AltairDisk::new(BASE);
+
+        assert_eq!(disk.read(BASE), 0xFF);
+        disk.mount(0, &make_image(dir.path(), "a.dsk"), false).unwrap();
+        disk.write(BASE, 0x80);     // Deselect
+        assert_eq!(disk.read(BASE), 0xFF);
+        disk.write(BASE, 0x01);     // Empty drive
+        assert_eq!(disk.read(BASE), 0xFF);
+    }
+
+    #[test]
+    fn test_select_and_head_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut disk = AltairDisk::new(BASE);
+        disk.mount(0, &make_image(dir.path(), "a.dsk"), false).unwrap();
+
+        disk.write(BASE, 0x00);
+        let status = disk.read(BASE);
+        assert_eq!(status & STATUS_MOVE_HEAD, 0);
+        assert_eq!(status & STATUS_TRACK0, 0);
+        assert_ne!(status & STATUS_HEAD, 0);        // Head not loaded
+        assert_eq!(disk.read(BASE + 1), 0xFF);
+
+        disk.write(BASE + 1, CONTROL_HEAD_LOAD);
+        assert_eq!(disk.read(BASE) & STATUS_HEAD, 0);
+    }
+
+    #[test]
+    fn test_track_stepping() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut disk = AltairDisk::new(BASE);
+        disk.mount(0, &make_image(dir.path(), "a.dsk"), false).unwrap();
+        disk.write(BASE, 0x00);
+
+        disk.write(BASE + 1, CONTROL_STEP_OUT);
+        assert_eq!(disk.track(0), Some(0));
+        for _ in 0..100 {
+            disk.write(BASE + 1, CONTROL_STEP_IN);
+        }
+        assert_eq!(disk.track(0), Some(76));
+        assert_ne!(disk.read(BASE) & STATUS_TRACK0, 0);
+        disk.write(BASE + 1, CONTROL_STEP_OUT);
+        assert_eq!(disk.track(0), Some(75));
+    }
+
+    #[test]
+    fn test_sector_position_cycles() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut disk = AltairDisk::new(BASE);
+        disk.mount(0, &make_image(dir.path(), "a.dsk"), false).unwrap();
+        ready(&mut disk, 0);
+
+        let first = disk.read(BASE + 1);
+        assert_eq!(first & 0xC1, 0xC0);             // Sector true
+        assert_eq!(disk.read(BASE + 1), first | 0x01);
+        let next = disk.read(BASE + 1);
+        assert_eq!((next >> 1) & 0x1F, ((first >> 1) + 1) & 0x1F);
+    }
+
+    #[test]
+    fn test_read_sector() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut disk = AltairDisk::new(BASE);
+        disk.mount(0, &make_image(dir.path(), "a.dsk"), false).unwrap();
+        ready(&mut disk, 0);
+        disk.write(BASE + 1, CONTROL_STEP_IN);
+        disk.write(BASE + 1, CONTROL_STEP_IN);
+
+        seek_sector(&mut disk, 5);
+        assert_eq!(disk.read(BASE) & STATUS_NRDA, 0);
+        let bytes: Vec<u8> = (0..SECTOR_BYTES).map(|_| disk.read(BASE + 2)).collect();
+        assert_eq!(bytes, vec![7u8; SECTOR_BYTES]);  // Track 2 + sector 5
+        assert_ne!(disk.read(BASE) & STATUS_NRDA, 0);
+    }
+
+    #[test]
+    fn test_write_sector_reaches_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = make_image(dir.path(), "a.dsk");
+        let mut disk = AltairDisk::new(BASE);
+        disk.mount(0, &path, false).unwrap();
+        ready(&mut disk, 0);
+
+        seek_sector(&mut disk, 3);
+        disk.write(BASE + 1, CONTROL_WRITE_ENABLE);
+        assert_eq!(disk.read(BASE) & STATUS_ENWD, 0);
+        for i in 0..SECTOR_BYTES {
+            disk.write(BASE + 2, i as u8);
+        }
+        assert_ne!(disk.read(BASE) & STATUS_ENWD, 0);   // Sector done
+
+        let image = std::fs::read(&path).unwrap();
+        let offset = AltairDisk::offset(0, 3);
+        assert_eq!(image.len(), IMAGE_BYTES);
+        assert_eq!(image[offset], 0);
+        assert_eq!(image[offset + 136], 136);
+        assert_eq!(image[offset + SECTOR_BYTES], 4);   // Next sector untouched
+    }
+
+    #[test]
+    fn test_write_protect_and_unmount() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = make_image(dir.path(), "a.dsk");
+        let mut disk = AltairDisk::new(BASE);
+        disk.mount(0, &path, true).unwrap();
+        assert!(disk.is_write_protected(0));
+        ready(&mut disk, 0);
+
+        seek_sector(&mut disk, 0);
+        disk.write(BASE + 1, CONTROL_WRITE_ENABLE);
+        assert_ne!(disk.read(BASE) & STATUS_ENWD, 0);   // Refused
+        disk.write(BASE + 2, 0xAA);
+        assert_eq!(std::fs::read(&path).unwrap()[0], 0);
+
+        disk.unmount(0);
+        assert!(!disk.is_mounted(0));
+        assert_eq!(disk.read(BASE), 0xFF);
+        assert!(disk.set_write_protect(0, false).is_err());
+    }
+
+    #[test]
+    fn test_clearing_write_protect_reopens_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = make_image(dir.path(), "a.dsk");
+        let mut disk = AltairDisk::new(BASE);
+        disk.mount(0, &path, true).unwrap();
+        disk.set_write_protect(0, false).unwrap();
+        ready(&mut disk, 0);
+
+        seek_sector(&mut disk, 0);
+        disk.write(BASE + 1, CONTROL_WRITE_ENABLE);
+        for _ in 0..SECTOR_BYTES {
+            disk.write(BASE + 2, 0xAA);
+        }
+        assert_eq!(std::fs::read(&path).unwrap()[0], 0xAA);
+
+        // An image that can't be reopened stays protected
+        disk.mount(1, &path, true).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(disk.set_write_protect(1, false).is_err());
+        assert!(disk.is_write_protected(1));
+    }
+
+    #[test]
+    fn test_mount_rejects_oversized_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("big.dsk");
+        std::fs::write(&path, vec![0u8; IMAGE_BYTES + 1]).unwrap();
+
+        let mut disk = AltairDisk::default();
+        assert!(disk.mount(0, &path, false).is_err());
+        assert!(disk.mount(16, &path, false).is_err());
+        assert_eq!(disk.base(), 0x08);
+    }
+}
@@ -0,0 +1,224 @@
+// assembler.rs - Assembler coprocessor for the monitor A command
+//
+// Port 0x28: Text char (write) - one source line, up to 80 chars
+// Port 0x29: Command (write)
+// Port 0x2A: Status (read) - bits 0-3 = bytes assembled, bits 4-7 = error code
+// Port 0x2B: Opcode byte (read) - auto-advance, 0x00 past the end
+// Port 0x2C: Error position (read) - 0-based column of the bad token
+// Port 0x2D: Location counter low (read/write)
+// Port 0x2E: Location counter high (read/write)
+//
+// Commands:
+//   0x01: Assemble (consumes the text, advances the location counter)
+//   0x02: Reset (clear text and results, keep labels)
+//   0x03: New session (forget labels, location counter = 0)
+//   0x04: Hex radix - bare numbers are hex (default, monitor convention)
+//   0x05: Decimal radix - bare numbers are decimal
+//
+// Error codes: see asm::AsmErrorCode
+
+use crate::asm::Assembler;
+use crate::io::IoDevice;
+
+const MAX_LINE: usize = 80;
+
+pub struct AssemblerDevice {
+    assembler: Assembler,
+    text: Vec<u8>,
+    output: Vec<u8>,
+    output_pos: usize,
+    error_code: u8,
+    error_pos: u8,
+}
+
+impl AssemblerDevice {
+    pub fn new() -> Self {
+        let mut assembler = Assembler::new();
+        assembler.set_hex_radix(true);
+        AssemblerDevice {
+            assembler,
+            text: Vec::with_capacity(MAX_LINE),
+            output: Vec::new(),
+            output_pos: 0,
+            error_code: 0,
+            error_pos: 0,
+        }
+    }
+
+    /// Labels and location counter, for host-side inspection
+    pub fn assembler(&self) -> &Assembler {
+        &self.assembler
+    }
+
+    fn do_assemble(&mut self) {
+        let line = String::from_utf8_lossy(&self.text).to_string();
+        self.clear_results();
+        match self.assembler.assemble_line(&line) {
+            Ok(bytes) => self.output = bytes,
+            Err(e) => {
+                self.error_code = e.code as u8;
+                self.error_pos = e.column.min(255) as u8;
+            }
+        }
+        self.text.clear();
+    }
+
+    fn clear_results(&mut self) {
+        self.output.clear();
+        self.output_pos = 0;
+        self.error_code = 0;
+        self.error_pos = 0;
+    }
+
+    fn status(&self) -> u8 {
+        (self.error_code << 4) | (self.output.len() as u8 & 0x0F)
+    }
+
+    fn next_byte(&mut self) -> u8 {
+        match self.output.get(self.output_pos) {
+            Some(&b) => {
+                self.output_pos += 1;
+                b
+            }
+            None => 0x00,
+        }
+    }
+}
+
+impl Default for AssemblerDevice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IoDevice for AssemblerDevice {
+    fn read(&mut self, port: u8) -> u8 {
+        match port {
+            0x2A => self.status(),
+            0x2B => self.next_byte(),
+            0x2C => self.error_pos,
+            0x2D => self.assembler.location() as u8,
+            0x2E => (self.assembler.location() >> 8) as u8,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) {
+        match port {
+            // CR and null end the line - nothing to store
+            0x28 if value != 0x00 && value != 0x0D && self.text.len() < MAX_LINE => {
+                self.text.push(value);
+            }
+            0x29 => match value {
+                0x01 => self.do_assemble(),
+                0x02 => {
+                    self.text.clear();
+                    self.clear_results();
+                }
+                0x03 => {
+                    self.text.clear();
+                    self.clear_results();
+                    self.assembler.clear();
+                }
+                0x04 => self.assembler.set_hex_radix(true),
+                0x05 => self.assembler.set_hex_radix(false),
+                _ => {}
+            },
+            0x2D => {
+                let lc = self.assembler.location();
+                self.assembler.set_location((lc & 0xFF00) | value as u16);
+            }
+            0x2E => {
+                let lc = self.assembler.location();
+                self.assembler.set_location((lc & 0x00FF) | ((value as u16) << 8));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::AsmErrorCode;
+
+    fn assemble(dev: &mut AssemblerDevice, line: &str) -> (u8, Vec<u8>) {
+        for b in line.bytes() {
+            dev.write(0x28, b);
+        }
+        dev.write(0x28, 0x0D);
+        dev.write(0x29, 0x01);
+        let status = dev.read(0x2A);
+        let bytes = (0..status & 0x0F).map(|_| dev.read(0x2B)).collect();
+        (status, bytes)
+    }
+
+    fn set_lc(dev: &mut AssemblerDevice, addr: u16) {
+        dev.write(0x2D, addr as u8);
+        dev.write(0x2E, (addr >> 8) as u8);
+    }
+
+    #[test]
+    fn test_assemble_mvi() {
+        let mut dev = AssemblerDevice::new();
+
+        assert_eq!(assemble(&mut dev, "MVI A,42H"), (0x02, vec![0x3E, 0x42]));
+        assert_eq!(dev.read(0x2B), 0x00);  // Past end
+    }
+
+    #[test]
+    fn test_monitor_session_at_1000() {
+        let mut dev = AssemblerDevice::new();
+        set_lc(&mut dev, 0x1000);
+
+        assert_eq!(assemble(&mut dev, "LOOP: MVI A,42").1, vec![0x3E, 0x42]);
+        assert_eq!((dev.read(0x2E), dev.read(0x2D)), (0x10, 0x02));
+        assert_eq!(assemble(&mut dev, "JMP LOOP").1, vec![0xC3, 0x00, 0x10]);
+        assert_eq!(assemble(&mut dev, "RET").1, vec![0xC9]);
+        assert_eq!((dev.read(0x2E), dev.read(0x2D)), (0x10, 0x06));
+    }
+
+    #[test]
+    fn test_error_status_and_position() {
+        let mut dev = AssemblerDevice::new();
+        set_lc(&mut dev, 0x1000);
+
+        let (status, bytes) = assemble(&mut dev, "MOV A,Q");
+        assert_eq!(status, (AsmErrorCode::BadOperand as u8) << 4);
+        assert!(bytes.is_empty());
+        assert_eq!(dev.read(0x2C), 6);
+        assert_eq!(dev.read(0x2D), 0x00);  // Location counter unchanged
+    }
+
+    #[test]
+    fn test_decimal_radix() {
+        let mut dev = AssemblerDevice::new();
+
+        dev.write(0x29, 0x05);
+        assert_eq!(assemble(&mut dev, "MVI A,10").1, vec![0x3E, 10]);
+        dev.write(0x29, 0x04);
+        assert_eq!(assemble(&mut dev, "MVI A,10").1, vec![0x3E, 0x10]);
+    }
+
+    #[test]
+    fn test_new_session_forgets_labels() {
+        let mut dev = AssemblerDevice::new();
+
+        assemble(&mut dev, "HERE: NOP");
+        dev.write(0x29, 0x03);
+        let (status, _) = assemble(&mut dev, "JMP HERE");
+        assert_eq!(status >> 4, AsmErrorCode::Undefined as u8);
+        assert_eq!(dev.read(0x2D), 0x00);
+    }
+
+    #[test]
+    fn test_reset_discards_partial_line() {
+        let mut dev = AssemblerDevice::new();
+
+        for b in b"GARBAGE" {
+            dev.write(0x28, *b);
+        }
+        dev.write(0x29, 0x02);
+        assert_eq!(assemble(&mut dev, "NOP"), (0x01, vec![0x00]));
+    }
+}
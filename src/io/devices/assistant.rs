@@ -0,0 +1,567 @@
+// assistant.rs - LLM assistant device (Claude API)
+//
+// Port 0x38: Prompt char (write)
+// Port 0x39: Command (write)
+// Port 0x3A: Status (read)
+// Port 0x3B: Response char (read) - auto-advance, 0x00 when done
+//
+// Commands:
+//   0x01: Send (submit prompt)
+//   0x02: Clear (reset buffers)
+//
+// Status:
+//   0x00: Idle
+//   0x01: Waiting (request in flight)
+//   0x02: Ready (response available)
+//   0x03: Done (no more response chars)
+//   0x80: Backend error
+//   0x81: Not configured (no API key, or unusable endpoint)
+//   0x82: Connect failed
+//   0x83: I/O or protocol error
+//   0x84: API returned an error status
+//   0x85: Empty prompt
+//
+// The 8080 doesn't know what answers. A backend trait sits behind the
+// ports: ClaudeBackend for real runs, EchoBackend and ScriptedBackend for
+// tests. Requests run on a background thread, like the HTTP device.
+//
+// The HTTP client has no TLS, so the Claude backend talks to a local proxy
+// (DEFAULT_ENDPOINT unless the config says otherwise) that forwards to
+// https://api.anthropic.com.
+//
+// Response text is made terminal-safe: LF becomes CR LF, non-ASCII
+// becomes '?'.
+
+use super::http;
+use crate::io::IoDevice;
+use crate::json::{self, Json};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub const STATUS_IDLE: u8 = 0x00;
+pub const STATUS_WAITING: u8 = 0x01;
+pub const STATUS_READY: u8 = 0x02;
+pub const STATUS_DONE: u8 = 0x03;
+pub const ERR_BACKEND: u8 = 0x80;
+pub const ERR_NOT_CONFIGURED: u8 = 0x81;
+pub const ERR_CONNECT: u8 = 0x82;
+pub const ERR_IO: u8 = 0x83;
+pub const ERR_API: u8 = 0x84;
+pub const ERR_EMPTY_PROMPT: u8 = 0x85;
+
+const MAX_PROMPT: usize = 1024;
+
+/// Plain-HTTP proxy in front of the Messages API
+pub const DEFAULT_ENDPOINT: &str = "http://localhost:8787/v1/messages";
+
+pub const DEFAULT_SYSTEM_PROMPT: &str = "\
+You are answering questions typed on an Intel 8080 computer. It runs a 4KB \
+monitor ROM with a 24-bit linear storage device, and reaches you through an \
+I/O port coprocessor. Your reply is printed on an ASCII terminal: plain text \
+only, no markdown, no Unicode, short lines, and keep it brief.";
+
+/// Something that answers prompts
+pub trait AssistantBackend: Send + Sync {
+    /// Answer `prompt`. Errors are device status codes (0x80+).
+    fn ask(&self, system_prompt: &str, prompt: &str) -> Result<String, u8>;
+}
+
+/// Echoes the prompt back
+pub struct EchoBackend;
+
+impl AssistantBackend for EchoBackend {
+    fn ask(&self, _system_prompt: &str, prompt: &str) -> Result<String, u8> {
+        Ok(prompt.to_string())
+    }
+}
+
+/// Replies from a script, in order, and records the prompts it saw
+pub struct ScriptedBackend {
+    replies: Mutex<VecDeque<Result<String, u8>>>,
+    prompts: Mutex<Vec<String>>,
+}
+
+impl ScriptedBackend {
+    pub fn new(replies: Vec<Result<String, u8>>) -> Self {
+        ScriptedBackend {
+            replies: Mutex::new(replies.into()),
+            prompts: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn prompts(&self) -> Vec<String> {
+        self.prompts.lock().unwrap().clone()
+    }
+}
+
+impl AssistantBackend for ScriptedBackend {
+    fn ask(&self, _system_prompt: &str, prompt: &str) -> Result<String, u8> {
+        self.prompts.lock().unwrap().push(prompt.to_string());
+        self.replies.lock().unwrap().pop_front().unwrap_or(Err(ERR_BACKEND))
+    }
+}
+
+/// Settings from the "claude" section of the config file:
+///
+/// ```json
+/// { "claude": {
+///     "api_key_env": "ANTHROPIC_API_KEY",
+///     "model": "claude-sonnet-4-20250514",
+///     "system_prompt_path": "./claude_system.txt",
+///     "endpoint": "http://localhost:8787/v1/messages",
+///     "max_tokens": 1024 } }
+/// ```
+///
+/// The key comes from "api_key" if present, else the environment variable
+/// named by "api_key_env". It never goes anywhere near the ROM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssistantConfig {
+    pub api_key: Option<String>,
+    pub model: String,
+    pub endpoint: String,
+    pub system_prompt: String,
+    pub max_tokens: u32,
+}
+
+impl Default for AssistantConfig {
+    fn default() -> Self {
+        AssistantConfig {
+            api_key: None,
+            model: "claude-sonnet-4-20250514".to_string(),
+            endpoint: DEFAULT_ENDPOINT.to_string(),
+            system_prompt: DEFAULT_SYSTEM_PROMPT.to_string(),
+            max_tokens: 1024,
+        }
+    }
+}
+
+impl AssistantConfig {
+    /// Load from a config file, reading the key from the process environment
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let json = Json::parse(&text)?;
+        let base = path.parent().unwrap_or(Path::new("."));
+        Self::from_json(&json, base, |name| std::env::var(name).ok())
+    }
+
+    /// Build from parsed config. `env` looks up environment variables and
+    /// `base` resolves a relative system_prompt_path.
+    pub fn from_json(
+        json: &Json,
+        base: &Path,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, String> {
+        let mut config = AssistantConfig::default();
+        let section = match json.get("claude") {
+            Some(s) => s,
+            None => return Ok(config),
+        };
+        let string = |key: &str| section.get(key).and_then(Json::as_str);
+
+        let key_env = string("api_key_env").unwrap_or("ANTHROPIC_API_KEY");
+        config.api_key = string("api_key")
+            .map(str::to_string)
+            .or_else(|| env(key_env))
+            .filter(|k| !k.is_empty());
+        if let Some(model) = string("model") {
+            config.model = model.to_string();
+        }
+        if let Some(endpoint) = string("endpoint") {
+            config.endpoint = endpoint.to_string();
+        }
+        if let Some(n) = section.get("max_tokens").and_then(Json::as_f64) {
+            config.max_tokens = n as u32;
+        }
+        if let Some(path) = string("system_prompt_path") {
+            config.system_prompt = std::fs::read_to_string(base.join(path))
+                .map_err(|e| format!("{}: {}", path, e))?;
+        }
+        Ok(config)
+    }
+}
+
+/// Anthropic Messages API over the HTTP device's client.
+/// Plain http:// only, so point "endpoint" at a local TLS proxy.
+pub struct ClaudeBackend {
+    config: AssistantConfig,
+}
+
+impl ClaudeBackend {
+    pub fn new(config: AssistantConfig) -> Self {
+        ClaudeBackend { config }
+    }
+}
+
+impl AssistantBackend for ClaudeBackend {
+    fn ask(&self, system_prompt: &str, prompt: &str) -> Result<String, u8> {
+        let key = self.config.api_key.as_ref().ok_or(ERR_NOT_CONFIGURED)?;
+        let body = format!(
+            "{{\"model\":{},\"max_tokens\":{},\"system\":{},\"messages\":[{{\"role\":\"user\",\"content\":{}}}]}}",
+            json::quote(&self.config.model),
+            self.config.max_tokens,
+            json::quote(system_prompt),
+            json::quote(prompt),
+        );
+        let headers = [
+            ("x-api-key".to_string(), key.clone()),
+            ("anthropic-version".to_string(), "2023-06-01".to_string()),
+            ("content-type".to_string(), "application/json".to_string()),
+        ];
+        let response = http::request("POST", &self.config.endpoint, &headers, body.as_bytes())
+            .map_err(|code| match code {
+                http::ERR_CONNECT => ERR_CONNECT,
+                http::ERR_BAD_URL | http::ERR_UNSUPPORTED => ERR_NOT_CONFIGURED,
+                _ => ERR_IO,
+            })?;
+        if response.status != 200 {
+            return Err(ERR_API);
+        }
+
+        let json = Json::parse(&String::from_utf8_lossy(&response.body)).map_err(|_| ERR_IO)?;
+        let text: String = json
+            .get("content")
+            .and_then(Json::as_array)
+            .ok_or(ERR_IO)?
+            .iter()
+            .filter(|block| block.get("type").and_then(Json::as_str) == Some("text"))
+            .filter_map(|block| block.get("text").and_then(Json::as_str))
+            .collect();
+        Ok(text)
+    }
+}
+
+pub struct AssistantDevice {
+    backend: Arc<dyn AssistantBackend>,
+    system_prompt: String,
+    prompt: Vec<u8>,
+    status: u8,
+    pending: Option<Receiver<Result<String, u8>>>,
+    response: Vec<u8>,
+    response_pos: usize,
+}
+
+impl AssistantDevice {
+    pub fn new(backend: Arc<dyn AssistantBackend>) -> Self {
+        AssistantDevice {
+            backend,
+            system_prompt: DEFAULT_SYSTEM_PROMPT.to_string(),
+            prompt: Vec::new(),
+            status: STATUS_IDLE,
+            pending: None,
+            response: Vec::new(),
+            response_pos: 0,
+        }
+    }
+
+    /// Claude backend and system prompt from a config
+    pub fn from_config(config: AssistantConfig) -> Self {
+        let system_prompt = config.system_prompt.clone();
+        let mut device = Self::new(Arc::new(ClaudeBackend::new(config)));
+        device.set_system_prompt(&system_prompt);
+        device
+    }
+
+    pub fn set_system_prompt(&mut self, prompt: &str) {
+        self.system_prompt = prompt.to_string();
+    }
+
+    fn send(&mut self) {
+        let prompt = String::from_utf8_lossy(&self.prompt).trim().to_string();
+        self.prompt.clear();
+        self.response.clear();
+        self.response_pos = 0;
+        if prompt.is_empty() {
+            self.status = ERR_EMPTY_PROMPT;
+            return;
+        }
+
+        let backend = Arc::clone(&self.backend);
+        let system_prompt = self.system_prompt.clone();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = tx.send(backend.ask(&system_prompt, &prompt));
+        });
+        self.pending = Some(rx);
+        self.status = STATUS_WAITING;
+    }
+
+    fn clear(&mut self) {
+        self.prompt.clear();
+        self.pending = None;
+        self.response.clear();
+        self.response_pos = 0;
+        self.status = STATUS_IDLE;
+    }
+
+    /// Pick up a finished request from the background thread
+    fn poll(&mut self) {
+        let result = match &self.pending {
+            Some(rx) => match rx.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => Err(ERR_BACKEND),
+            },
+            None => return,
+        };
+        self.pending = None;
+        match result {
+            Ok(text) => {
+                self.response = terminal_text(&text);
+                self.response_pos = 0;
+                self.update_status();
+            }
+            Err(code) => self.status = code,
+        }
+    }
+
+    fn update_status(&mut self) {
+        self.status = if self.response_pos < self.response.len() {
+            STATUS_READY
+        } else {
+            STATUS_DONE
+        };
+    }
+
+    fn next_char(&mut self) -> u8 {
+        self.poll();
+        match self.response.get(self.response_pos) {
+            Some(&c) => {
+                self.response_pos += 1;
+                self.update_status();
+                c
+            }
+            None => 0x00,
+        }
+    }
+}
+
+impl IoDevice for AssistantDevice {
+    fn read(&mut self, port: u8) -> u8 {
+        match port {
+            0x3A => {
+                self.poll();
+                self.status
+            }
+            0x3B => self.next_char(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) {
+        match port {
+            0x38 if value != 0x00 && self.prompt.len() < MAX_PROMPT => self.prompt.push(value),
+            0x39 => match value {
+                0x01 if self.status != STATUS_WAITING => self.send(),
+                0x02 => self.clear(),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+/// LF to CR LF, drop stray CR, tabs to spaces, non-ASCII to '?'
+fn terminal_text(text: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\n' => out.extend_from_slice(b"\r\n"),
+            '\r' => {}
+            '\t' => out.push(b' '),
+            c if c.is_ascii() && !c.is_ascii_control() => out.push(c as u8),
+            _ => out.push(b'?'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::time::{Duration, Instant};
+
+    fn send_prompt(dev: &mut AssistantDevice, prompt: &str) {
+        for b in prompt.bytes() {
+            dev.write(0x38, b);
+        }
+        dev.write(0x38, 0x00);
+        dev.write(0x39, 0x01);
+    }
+
+    fn wait(dev: &mut AssistantDevice) -> u8 {
+        let start = Instant::now();
+        while dev.read(0x3A) == STATUS_WAITING {
+            assert!(start.elapsed() < Duration::from_secs(5), "request timed out");
+            thread::sleep(Duration::from_millis(1));
+        }
+        dev.read(0x3A)
+    }
+
+    /// Read the response the way the ROM does: until status says Done
+    fn read_response(dev: &mut AssistantDevice) -> String {
+        let mut out = String::new();
+        while dev.read(0x3A) == STATUS_READY {
+            out.push(dev.read(0x3B) as char);
+        }
+        out
+    }
+
+    /// Holds every request until the test releases it
+    struct GatedBackend {
+        gate: Mutex<Receiver<()>>,
+    }
+
+    impl AssistantBackend for GatedBackend {
+        fn ask(&self, _system_prompt: &str, prompt: &str) -> Result<String, u8> {
+            self.gate.lock().unwrap().recv().map_err(|_| ERR_BACKEND)?;
+            Ok(prompt.to_uppercase())
+        }
+    }
+
+    #[test]
+    fn test_echo_round_trip() {
+        let mut dev = AssistantDevice::new(Arc::new(EchoBackend));
+        assert_eq!(dev.read(0x3A), STATUS_IDLE);
+
+        send_prompt(&mut dev, "What is the 8080?");
+        assert_eq!(wait(&mut dev), STATUS_READY);
+        assert_eq!(read_response(&mut dev), "What is the 8080?");
+        assert_eq!(dev.read(0x3A), STATUS_DONE);
+        assert_eq!(dev.read(0x3B), 0x00);
+    }
+
+    #[test]
+    fn test_waiting_until_backend_answers() {
+        let (release, gate) = mpsc::channel();
+        let mut dev = AssistantDevice::new(Arc::new(GatedBackend { gate: Mutex::new(gate) }));
+
+        send_prompt(&mut dev, "hello");
+        assert_eq!(dev.read(0x3A), STATUS_WAITING);
+        assert_eq!(dev.read(0x3B), 0x00);
+        dev.write(0x39, 0x01);  // Ignored while waiting
+
+        release.send(()).unwrap();
+        assert_eq!(wait(&mut dev), STATUS_READY);
+        assert_eq!(read_response(&mut dev), "HELLO");
+    }
+
+    #[test]
+    fn test_scripted_replies_and_errors() {
+        let backend = Arc::new(ScriptedBackend::new(vec![
+            Ok("Line one\nLine two \u{2014} done".to_string()),
+            Err(ERR_API),
+        ]));
+        let mut dev = AssistantDevice::new(backend.clone());
+
+        send_prompt(&mut dev, "first");
+        wait(&mut dev);
+        assert_eq!(read_response(&mut dev), "Line one\r\nLine two ? done");
+
+        send_prompt(&mut dev, "second");
+        assert_eq!(wait(&mut dev), ERR_API);
+
+        assert_eq!(backend.prompts(), vec!["first", "second"]);
+    }
+
+    #[test]
+    fn test_empty_prompt_and_clear() {
+        let mut dev = AssistantDevice::new(Arc::new(EchoBackend));
+
+        dev.write(0x39, 0x01);
+        assert_eq!(dev.read(0x3A), ERR_EMPTY_PROMPT);
+
+        dev.write(0x38, b'x');
+        dev.write(0x39, 0x02);
+        assert_eq!(dev.read(0x3A), STATUS_IDLE);
+        dev.write(0x39, 0x01);
+        assert_eq!(dev.read(0x3A), ERR_EMPTY_PROMPT);  // Prompt was cleared
+    }
+
+    #[test]
+    fn test_config_key_handling() {
+        let base = Path::new(".");
+        let env = |name: &str| (name == "MY_KEY").then(|| "from-env".to_string());
+
+        let json = Json::parse(r#"{"claude": {"api_key_env": "MY_KEY", "model": "m1"}}"#).unwrap();
+        let config = AssistantConfig::from_json(&json, base, env).unwrap();
+        assert_eq!(config.api_key.as_deref(), Some("from-env"));
+        assert_eq!(config.model, "m1");
+
+        let json = Json::parse(r#"{"claude": {"api_key": "in-file", "api_key_env": "MY_KEY"}}"#).unwrap();
+        let config = AssistantConfig::from_json(&json, base, env).unwrap();
+        assert_eq!(config.api_key.as_deref(), Some("in-file"));
+
+        let json = Json::parse(r#"{"console": {}}"#).unwrap();
+        let config = AssistantConfig::from_json(&json, base, env).unwrap();
+        assert_eq!(config, AssistantConfig::default());
+        assert_eq!(config.endpoint, DEFAULT_ENDPOINT);             // Reachable without TLS
+        assert!(http::parse_url(&config.endpoint).is_ok());
+    }
+
+    #[test]
+    fn test_config_system_prompt_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("system.txt"), "Be terse.").unwrap();
+        std::fs::write(
+            dir.path().join("config.json"),
+            r#"{"claude": {"system_prompt_path": "system.txt"}}"#,
+        ).unwrap();
+
+        let config = AssistantConfig::load(&dir.path().join("config.json")).unwrap();
+        assert_eq!(config.system_prompt, "Be terse.");
+    }
+
+    #[test]
+    fn test_claude_backend_without_key() {
+        let mut dev = AssistantDevice::from_config(AssistantConfig::default());
+
+        send_prompt(&mut dev, "hi");
+        assert_eq!(wait(&mut dev), ERR_NOT_CONFIGURED);
+    }
+
+    #[test]
+    fn test_claude_backend_against_local_stub() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/v1/messages", listener.local_addr().unwrap());
+        let (tx, requests) = mpsc::channel();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            while let Ok(n) = stream.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+                if String::from_utf8_lossy(&request).contains("\"messages\"") {
+                    break;
+                }
+            }
+            let body = r#"{"content":[{"type":"text","text":"The 8080 is an 8-bit CPU."}]}"#;
+            write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+            tx.send(String::from_utf8_lossy(&request).to_string()).unwrap();
+        });
+
+        let config = AssistantConfig {
+            api_key: Some("test-key".to_string()),
+            endpoint,
+            ..AssistantConfig::default()
+        };
+        let mut dev = AssistantDevice::from_config(config);
+        send_prompt(&mut dev, "What is the 8080?");
+        assert_eq!(wait(&mut dev), STATUS_READY);
+        assert_eq!(read_response(&mut dev), "The 8080 is an 8-bit CPU.");
+
+        let request = requests.recv().unwrap();
+        assert!(request.starts_with("POST /v1/messages HTTP/1.1\r\n"));
+        assert!(request.contains("x-api-key: test-key\r\n"));
+        assert!(request.contains("\"content\":\"What is the 8080?\""));
+        assert!(request.contains("\"system\":\"You are answering"));
+    }
+}
@@ -0,0 +1,372 @@
+// ay.rs - General Instrument AY-3-8910 programmable sound generator
+//
+// Port base+0: Register select (write) / Selected register (read)
+// Port base+1: Register data (write) / Selected register (read)
+//
+// Registers:
+//   R0-R1:  Channel A tone period (12 bits, fine then coarse)
+//   R2-R3:  Channel B tone period
+//   R4-R5:  Channel C tone period
+//   R6:     Noise period (5 bits)
+//   R7:     Mixer, active low - bits 0-2 tone A-C off, bits 3-5 noise A-C off,
+//           bits 6-7 I/O port A/B output
+//   R8-R10: Channel A-C amplitude (bits 0-3), bit 4 = use envelope
+//   R11-12: Envelope period (16 bits)
+//   R13:    Envelope shape - bit 0 hold, 1 alternate, 2 attack, 3 continue.
+//           Writing restarts the envelope.
+//   R14-15: I/O ports A/B (input ports read 0xFF)
+//
+// Frequencies, for a chip clock f:
+//   Tone:     f / (16 * period)
+//   Noise:    17-bit LFSR shifted at f / (16 * period)
+//   Envelope: 16 steps per cycle, cycles at f / (256 * period)
+//
+// Audio is rendered from emulated cycles: the chip steps every 8 clocks
+// and each output sample averages the steps it covers. Samples collect
+// in a Wav (unsigned output, silence = 0).
+
+use crate::io::devices::time::DEFAULT_CPU_HZ;
+use crate::io::{Clocked, IoDevice};
+use crate::wav::Wav;
+use std::io;
+use std::path::Path;
+
+pub const DEFAULT_CLOCK_HZ: u64 = 1_000_000;
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+
+/// DAC output for each 4-bit level (logarithmic, 1.0 = full scale)
+const VOLUME: [f32; 16] = [
+    0.0, 0.0100, 0.0145, 0.0211, 0.0307, 0.0455, 0.0645, 0.1074,
+    0.1266, 0.2050, 0.2922, 0.3728, 0.4925, 0.6353, 0.8056, 1.0,
+];
+
+/// Writable bits of each register
+const MASKS: [u8; 16] = [
+    0xFF, 0x0F, 0xFF, 0x0F, 0xFF, 0x0F, 0x1F, 0xFF,
+    0x1F, 0x1F, 0x1F, 0xFF, 0xFF, 0x0F, 0xFF, 0xFF,
+];
+
+const MIXER: usize = 7;
+const ENVELOPE_SHAPE: usize = 13;
+
+pub struct Ay38910 {
+    base: u8,
+    regs: [u8; 16],
+    selected: usize,
+    clock_hz: u64,
+    cpu_hz: u64,
+    clock_frac: u64,
+    sample_frac: u64,
+    tone_counter: [u16; 3],
+    tone_out: [bool; 3],
+    prescale: bool,
+    noise_counter: u8,
+    noise_lfsr: u32,
+    env_counter: u32,
+    env_step: u8,
+    env_attack: bool,
+    env_hold: Option<u8>,
+    level_sum: f32,
+    level_count: u32,
+    wav: Wav,
+}
+
+impl Ay38910 {
+    /// Chip on `base`/`base + 1` clocked at `clock_hz`, rendering at
+    /// `sample_rate`
+    pub fn new(base: u8, clock_hz: u64, sample_rate: u32) -> Self {
+        Ay38910 {
+            base,
+            regs: [0; 16],
+            selected: 0,
+            clock_hz,
+            cpu_hz: DEFAULT_CPU_HZ,
+            clock_frac: 0,
+            sample_frac: 0,
+            tone_counter: [0; 3],
+            tone_out: [false; 3],
+            prescale: false,
+            noise_counter: 0,
+            noise_lfsr: 1,
+            env_counter: 0,
+            env_step: 0,
+            env_attack: false,
+            env_hold: Some(0),
+            level_sum: 0.0,
+            level_count: 0,
+            wav: Wav::new(sample_rate),
+        }
+    }
+
+    /// CPU clock the cycle counts are measured in
+    pub fn set_cpu_hz(&mut self, hz: u64) {
+        self.cpu_hz = hz;
+    }
+
+    pub fn register(&self, index: usize) -> u8 {
+        self.regs[index & 0x0F]
+    }
+
+    /// Audio rendered so far
+    pub fn wav(&self) -> &Wav {
+        &self.wav
+    }
+
+    /// Take the audio rendered so far, starting a new buffer
+    pub fn take_wav(&mut self) -> Wav {
+        let rate = self.wav.sample_rate;
+        std::mem::replace(&mut self.wav, Wav::new(rate))
+    }
+
+    pub fn save_wav(&self, path: &Path) -> io::Result<()> {
+        self.wav.save(path)
+    }
+
+    fn write_register(&mut self, value: u8) {
+        let index = self.selected;
+        self.regs[index] = value & MASKS[index];
+        if index == ENVELOPE_SHAPE {
+            self.env_counter = 0;
+            self.env_step = 0;
+            self.env_attack = value & 0x04 != 0;
+            self.env_hold = None;
+        }
+    }
+
+    fn read_register(&self) -> u8 {
+        match self.selected {
+            // I/O ports in input mode: nothing drives the pins
+            14 if self.regs[MIXER] & 0x40 == 0 => 0xFF,
+            15 if self.regs[MIXER] & 0x80 == 0 => 0xFF,
+            index => self.regs[index],
+        }
+    }
+
+    fn tone_period(&self, channel: usize) -> u16 {
+        let period = (self.regs[channel * 2 + 1] as u16) << 8 | self.regs[channel * 2] as u16;
+        period.max(1)
+    }
+
+    fn envelope_level(&self) -> u8 {
+        self.env_hold.unwrap_or(if self.env_attack { self.env_step } else { 15 - self.env_step })
+    }
+
+    fn step_envelope(&mut self) {
+        if self.env_hold.is_some() {
+            return;
+        }
+        self.env_step += 1;
+        if self.env_step < 16 {
+            return;
+        }
+        let shape = self.regs[ENVELOPE_SHAPE];
+        let end = if self.env_attack { 15 } else { 0 };
+        if shape & 0x08 == 0 {
+            self.env_hold = Some(0);
+        } else if shape & 0x01 != 0 {
+            self.env_hold = Some(if shape & 0x02 != 0 { 15 - end } else { end });
+        } else {
+            if shape & 0x02 != 0 {
+                self.env_attack = !self.env_attack;
+            }
+            self.env_step = 0;
+        }
+    }
+
+    /// Advance 8 chip clocks
+    fn step(&mut self) {
+        for channel in 0..3 {
+            self.tone_counter[channel] += 1;
+            if self.tone_counter[channel] >= self.tone_period(channel) {
+                self.tone_counter[channel] = 0;
+                self.tone_out[channel] = !self.tone_out[channel];
+            }
+        }
+
+        // Noise and envelope run at half the rate
+        self.prescale = !self.prescale;
+        if self.prescale {
+            return;
+        }
+        self.noise_counter += 1;
+        if self.noise_counter >= self.regs[6].max(1) {
+            self.noise_counter = 0;
+            let feedback = (self.noise_lfsr ^ (self.noise_lfsr >> 3)) & 1;
+            self.noise_lfsr = (self.noise_lfsr >> 1) | (feedback << 16);
+        }
+        self.env_counter += 1;
+        let env_period = ((self.regs[12] as u32) << 8 | self.regs[11] as u32).max(1);
+        if self.env_counter >= env_period {
+            self.env_counter = 0;
+            self.step_envelope();
+        }
+    }
+
+    /// Mixed output of the three channels, 0.0 to 1.0
+    fn output(&self) -> f32 {
+        let mixer = self.regs[MIXER];
+        let noise = self.noise_lfsr & 1 != 0;
+        let mut total = 0.0;
+        for channel in 0..3 {
+            let tone_on = self.tone_out[channel] || mixer & (0x01 << channel) != 0;
+            let noise_on = noise || mixer & (0x08 << channel) != 0;
+            if tone_on && noise_on {
+                let amplitude = self.regs[8 + channel];
+                let level = if amplitude & 0x10 != 0 { self.envelope_level() } else { amplitude & 0x0F };
+                total += VOLUME[level as usize];
+            }
+        }
+        total / 3.0
+    }
+}
+
+impl IoDevice for Ay38910 {
+    fn read(&mut self, port: u8) -> u8 {
+        match port.wrapping_sub(self.base) {
+            0 | 1 => self.read_register(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) {
+        match port.wrapping_sub(self.base) {
+            0 => self.selected = (value & 0x0F) as usize,
+            1 => self.write_register(value),
+            _ => {}
+        }
+    }
+}
+
+impl Clocked for Ay38910 {
+    fn tick(&mut self, cycles: u64) {
+        let step_cycles = self.cpu_hz * 8;
+        let sample_rate = self.wav.sample_rate as u64;
+        for _ in 0..cycles {
+            self.clock_frac += self.clock_hz;
+            while self.clock_frac >= step_cycles {
+                self.clock_frac -= step_cycles;
+                self.step();
+                self.level_sum += self.output();
+                self.level_count += 1;
+            }
+            self.sample_frac += sample_rate;
+            if self.sample_frac >= self.cpu_hz {
+                self.sample_frac -= self.cpu_hz;
+                let level = if self.level_count > 0 {
+                    self.level_sum / self.level_count as f32
+                } else {
+                    self.output()
+                };
+                self.wav.samples.push((level * i16::MAX as f32) as i16);
+                self.level_sum = 0.0;
+                self.level_count = 0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: u8 = 0xA0;
+
+    fn chip(regs: &[(u8, u8)]) -> Ay38910 {
+        let mut ay = Ay38910::new(BASE, 2_000_000, 44_100);
+        for &(reg, value) in regs {
+            ay.write(BASE, reg);
+            ay.write(BASE + 1, value);
+        }
+        ay
+    }
+
+    /// Render `seconds` of audio
+    fn render(ay: &mut Ay38910, seconds: f64) -> Vec<i16> {
+        ay.tick((DEFAULT_CPU_HZ as f64 * seconds) as u64);
+        ay.take_wav().samples
+    }
+
+    /// Rising crossings of half the peak
+    fn cycles_counted(samples: &[i16]) -> usize {
+        let threshold = samples.iter().copied().max().unwrap_or(0) / 2;
+        samples.windows(2).filter(|w| w[0] < threshold && w[1] >= threshold).count()
+    }
+
+    #[test]
+    fn test_register_select_and_masks() {
+        let mut ay = chip(&[(1, 0xFF), (6, 0xFF), (7, 0xC0), (14, 0x5A)]);
+        assert_eq!(ay.register(1), 0x0F);
+        assert_eq!(ay.register(6), 0x1F);
+        ay.write(BASE, 14);
+        assert_eq!(ay.read(BASE), 0x5A);    // Port A set to output
+
+        let mut ay = chip(&[(14, 0x5A)]);
+        ay.write(BASE, 14);
+        assert_eq!(ay.read(BASE + 1), 0xFF);
+    }
+
+    #[test]
+    fn test_tone_frequency() {
+        // 2 MHz / (16 * 0x11C) = 440.1 Hz on channel A, tone only
+        let mut ay = chip(&[(0, 0x1C), (1, 0x01), (7, 0x3E), (8, 0x0F)]);
+        let samples = render(&mut ay, 1.0);
+        assert_eq!(samples.len(), 44_100);
+        let count = cycles_counted(&samples);
+        assert!((438..=442).contains(&count), "{} cycles", count);
+        assert!(samples.iter().all(|&s| s >= 0));
+    }
+
+    #[test]
+    fn test_mixer_and_amplitude_silence() {
+        let mut ay = chip(&[(0, 100), (7, 0x3F), (8, 0x0F)]);     // Tone and noise off: steady
+        assert!(render(&mut ay, 0.1).iter().all(|&s| s == render_level(15)));
+
+        let mut ay = chip(&[(0, 100), (7, 0x3E), (8, 0x00)]);     // Amplitude 0
+        assert!(render(&mut ay, 0.1).iter().all(|&s| s == 0));
+    }
+
+    /// A channel with tone and noise both off outputs its level steadily
+    fn render_level(level: usize) -> i16 {
+        (VOLUME[level] / 3.0 * i16::MAX as f32) as i16
+    }
+
+    #[test]
+    fn test_noise_is_deterministic() {
+        let regs = [(6, 0x08), (7, 0x37), (8, 0x0F)];
+        let a = render(&mut chip(&regs), 0.2);
+        let b = render(&mut chip(&regs), 0.2);
+        assert_eq!(a, b);
+        // FNV-1a over the samples: changes if the generator changes
+        let hash = a.iter().fold(0xCBF2_9CE4_8422_2325u64, |h, &s| {
+            (h ^ s as u16 as u64).wrapping_mul(0x0100_0000_01B3)
+        });
+        assert_eq!(hash, 0xFFC8_927B_BEB2_6ED1, "{:#X}", hash);
+        // Noise is not a tone: many distinct levels, irregular crossings
+        let mut levels: Vec<i16> = a.clone();
+        levels.sort();
+        levels.dedup();
+        assert!(levels.len() > 10);
+    }
+
+    #[test]
+    fn test_envelope_attack_and_hold() {
+        // Shape 0D: rise once, then hold at full. Period 160: a cycle of
+        // 256 * 160 chip clocks = 20.48 ms at 2 MHz
+        let mut ay = chip(&[(7, 0x3F), (8, 0x10), (11, 160), (13, 0x0D)]);
+        let samples = render(&mut ay, 0.05);
+        assert!(samples.windows(2).all(|w| w[1] >= w[0]));
+        assert!(samples[0] < render_level(2));
+        assert_eq!(*samples.last().unwrap(), render_level(15));
+        let rise_end = samples.iter().position(|&s| s == render_level(15)).unwrap();
+        assert!((800..=950).contains(&rise_end), "{}", rise_end);
+    }
+
+    #[test]
+    fn test_envelope_sawtooth_repeats() {
+        // Shape 08: falling sawtooth, 20.48 ms per cycle = 48.8 Hz
+        let mut ay = chip(&[(7, 0x3F), (8, 0x10), (11, 160), (13, 0x08)]);
+        let samples = render(&mut ay, 1.0);
+        let count = cycles_counted(&samples);
+        assert!((47..=50).contains(&count), "{} cycles", count);
+    }
+}
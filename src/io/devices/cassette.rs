@@ -0,0 +1,288 @@
+// cassette.rs - Kansas City Standard cassette interface
+//
+// Port base+0: Status (read) / Control (write)
+// Port base+1: RX data (read) / TX data (write)
+//
+// A custom interface, by default at 0x06/0x07 where the MITS 88-ACR sat,
+// but not register compatible with it: the 88-ACR is an 88-SIO with
+// active-low flags and no motor control, so Altair loaders written for
+// it will not run here. The tape is a WAV file in KCS (300 baud) or CUTS
+// (1200 baud) format; see kcs.rs.
+//
+// Control register:
+//   Bit 0: Play - motor on, bytes arrive from tape
+//   Bit 1: Record - motor on, written bytes go to tape
+//   0 stops the motor; the tape stays where it is.
+//
+// Status register:
+//   Bit 0: RXRDY   - byte waiting in the data register
+//   Bit 1: TXRDY   - ready for the next byte to record
+//   Bit 5: OVERRUN - a byte arrived before the last one was read
+//                    (cleared by reading status)
+//   Bit 7: END     - no more bytes on the tape
+//
+// Time is emulated: while the motor runs, the tape moves with CPU cycles
+// and each byte lands when its stop bits have passed the head, exactly
+// as far into the run as it was into the recording. Recording is paced
+// the same way - TXRDY drops for one byte time (11 bits) after each
+// write. Recorded bytes are written back to back after a mark leader.
+
+use crate::io::devices::time::DEFAULT_CPU_HZ;
+use crate::io::{Clocked, IoDevice};
+use crate::kcs::{self, Baud, TapeByte};
+use crate::wav::Wav;
+use std::io;
+use std::path::Path;
+
+pub const STATUS_RXRDY: u8 = 0x01;
+pub const STATUS_TXRDY: u8 = 0x02;
+pub const STATUS_OVERRUN: u8 = 0x20;
+pub const STATUS_END: u8 = 0x80;
+
+pub const CONTROL_PLAY: u8 = 0x01;
+pub const CONTROL_RECORD: u8 = 0x02;
+
+pub const DEFAULT_BASE: u8 = 0x06;
+
+pub struct Cassette {
+    base: u8,
+    baud: Baud,
+    cpu_hz: u64,
+    control: u8,
+    tape: Vec<TapeByte>,
+    next: usize,
+    tape_cycles: u64,
+    rx_data: Option<u8>,
+    overrun: bool,
+    tx_busy: u64,
+    recorded: Vec<u8>,
+}
+
+impl Cassette {
+    /// Empty cassette deck on `base`/`base + 1`
+    pub fn new(base: u8, baud: Baud) -> Self {
+        Cassette {
+            base,
+            baud,
+            cpu_hz: DEFAULT_CPU_HZ,
+            control: 0,
+            tape: Vec::new(),
+            next: 0,
+            tape_cycles: 0,
+            rx_data: None,
+            overrun: false,
+            tx_busy: 0,
+            recorded: Vec::new(),
+        }
+    }
+
+    pub fn set_cpu_hz(&mut self, hz: u64) {
+        self.cpu_hz = hz;
+    }
+
+    /// Put a tape in the deck, rewound
+    pub fn insert(&mut self, wav: &Wav) {
+        self.tape = kcs::decode_timed(wav, self.baud);
+        self.rewind();
+    }
+
+    pub fn load(&mut self, path: &Path) -> io::Result<()> {
+        self.insert(&Wav::load(path)?);
+        Ok(())
+    }
+
+    pub fn rewind(&mut self) {
+        self.next = 0;
+        self.tape_cycles = 0;
+        self.rx_data = None;
+        self.overrun = false;
+    }
+
+    /// Seconds of tape that have passed the head
+    pub fn position(&self) -> f64 {
+        self.tape_cycles as f64 / self.cpu_hz as f64
+    }
+
+    /// Bytes on the tape in the deck
+    pub fn tape_len(&self) -> usize {
+        self.tape.len()
+    }
+
+    pub fn recorded(&self) -> &[u8] {
+        &self.recorded
+    }
+
+    /// What was recorded, as cassette audio
+    pub fn recording(&self) -> Wav {
+        kcs::encode(&self.recorded, self.baud, kcs::DEFAULT_SAMPLE_RATE)
+    }
+
+    pub fn save_recording(&self, path: &Path) -> io::Result<()> {
+        self.recording().save(path)
+    }
+
+    /// CPU cycles to send one framed byte
+    fn byte_cycles(&self) -> u64 {
+        self.cpu_hz * 11 / self.baud.bits_per_second() as u64
+    }
+
+    fn status(&mut self) -> u8 {
+        let mut status = 0;
+        if self.rx_data.is_some() {
+            status |= STATUS_RXRDY;
+        }
+        if self.tx_busy == 0 {
+            status |= STATUS_TXRDY;
+        }
+        if std::mem::take(&mut self.overrun) {
+            status |= STATUS_OVERRUN;
+        }
+        if self.next >= self.tape.len() {
+            status |= STATUS_END;
+        }
+        status
+    }
+}
+
+impl IoDevice for Cassette {
+    fn read(&mut self, port: u8) -> u8 {
+        match port.wrapping_sub(self.base) {
+            0 => self.status(),
+            1 => self.rx_data.take().unwrap_or(0x00),
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) {
+        match port.wrapping_sub(self.base) {
+            0 => self.control = value & (CONTROL_PLAY | CONTROL_RECORD),
+            1 if self.control & CONTROL_RECORD != 0 && self.tx_busy == 0 => {
+                self.recorded.push(value);
+                self.tx_busy = self.byte_cycles();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Clocked for Cassette {
+    fn tick(&mut self, cycles: u64) {
+        if self.control & CONTROL_RECORD != 0 {
+            self.tx_busy = self.tx_busy.saturating_sub(cycles);
+        }
+        if self.control & CONTROL_PLAY == 0 {
+            return;
+        }
+        self.tape_cycles += cycles;
+        let position = self.position();
+        while let Some(byte) = self.tape.get(self.next).filter(|b| b.time <= position) {
+            if self.rx_data.is_some() {
+                self.overrun = true;
+            }
+            self.rx_data = Some(byte.value);
+            self.next += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Intel8080;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn deck(data: &[u8], baud: Baud) -> Cassette {
+        let mut deck = Cassette::new(DEFAULT_BASE, baud);
+        deck.insert(&kcs::encode(data, baud, 22_050));
+        deck
+    }
+
+    #[test]
+    fn test_bytes_arrive_at_tape_speed() {
+        let mut deck = deck(b"HI", Baud::B300);
+        assert_eq!(deck.tape_len(), 2);
+        assert_eq!(deck.read(0x06), STATUS_TXRDY);
+
+        // Motor off: nothing moves
+        deck.tick(10_000_000);
+        assert_eq!(deck.position(), 0.0);
+
+        deck.write(0x06, CONTROL_PLAY);
+        deck.tick(2_000_000 * 2);           // Through the 2 s leader
+        assert_eq!(deck.read(0x06) & STATUS_RXRDY, 0);
+        deck.tick(2_000_000 * 11 / 300 + 1000);     // Plus half a millisecond
+        assert_eq!(deck.read(0x06) & STATUS_RXRDY, STATUS_RXRDY);
+        assert_eq!(deck.read(0x07), b'H');
+        assert_eq!(deck.read(0x06) & STATUS_RXRDY, 0);
+        deck.tick(2_000_000 * 11 / 300);
+        assert_eq!(deck.read(0x07), b'I');
+        assert_eq!(deck.read(0x06) & STATUS_END, STATUS_END);
+    }
+
+    #[test]
+    fn test_overrun() {
+        let mut deck = deck(b"ABC", Baud::B1200);
+        deck.write(0x06, CONTROL_PLAY);
+        deck.tick(2_000_000 * 3);
+        assert_eq!(deck.read(0x06), STATUS_RXRDY | STATUS_TXRDY | STATUS_OVERRUN | STATUS_END);
+        assert_eq!(deck.read(0x06) & STATUS_OVERRUN, 0);
+        assert_eq!(deck.read(0x07), b'C');
+    }
+
+    #[test]
+    fn test_record_paced_and_encoded() {
+        let mut deck = Cassette::new(DEFAULT_BASE, Baud::B1200);
+        deck.write(0x07, b'X');             // Not recording: dropped
+        deck.write(0x06, CONTROL_RECORD);
+        deck.write(0x07, b'O');
+        assert_eq!(deck.read(0x06) & STATUS_TXRDY, 0);
+        deck.write(0x07, b'!');             // Still sending: dropped
+        deck.tick(2_000_000 * 11 / 1200);
+        assert_eq!(deck.read(0x06) & STATUS_TXRDY, STATUS_TXRDY);
+        deck.write(0x07, b'K');
+        assert_eq!(deck.recorded(), b"OK");
+        assert_eq!(kcs::decode(&deck.recording(), Baud::B1200), b"OK");
+    }
+
+    #[test]
+    fn test_rewind() {
+        let mut deck = deck(b"Z", Baud::B1200);
+        deck.write(0x06, CONTROL_PLAY);
+        deck.tick(2_000_000 * 3);
+        assert_eq!(deck.read(0x07), b'Z');
+        deck.rewind();
+        assert_eq!(deck.read(0x06) & STATUS_END, 0);
+        deck.tick(2_000_000 * 3);
+        assert_eq!(deck.read(0x07), b'Z');
+    }
+
+    #[test]
+    fn test_cpu_loader() {
+        // Read 3 bytes from tape to 0x2000 by polling RXRDY, then halt
+        //       LXI H,2000 / MVI C,3 / MVI A,1 / OUT 06
+        // WAIT: IN 06 / RRC / JNC WAIT / IN 07 / MOV M,A / INX H
+        //       DCR C / JNZ WAIT / HLT
+        let program = [
+            0x21, 0x00, 0x20, 0x0E, 0x03, 0x3E, 0x01, 0xD3, 0x06,
+            0xDB, 0x06, 0x0F, 0xD2, 0x09, 0x00, 0xDB, 0x07, 0x77, 0x23,
+            0x0D, 0xC2, 0x09, 0x00, 0x76,
+        ];
+        let mut cpu = Intel8080::new();
+        cpu.load_program(&program, 0x0000);
+        let deck = Rc::new(RefCell::new(deck(&[0xC3, 0x00, 0x01], Baud::B1200)));
+        cpu.io_bus_mut().map_port(0x06, deck.clone());
+        cpu.io_bus_mut().map_port(0x07, deck.clone());
+        cpu.attach_clocked(deck.clone());
+
+        while !cpu.halted && cpu.cycles < 20_000_000 {
+            cpu.execute_one();
+        }
+        assert!(cpu.halted);
+        assert_eq!(cpu.read_byte(0x2000), 0xC3);
+        assert_eq!(cpu.read_byte(0x2002), 0x01);
+        // Leader plus three bytes of tape time
+        let seconds = cpu.cycles as f64 / 2_000_000.0;
+        assert!((seconds - (kcs::LEADER_SECONDS + 33.0 / 1200.0)).abs() < 0.001);
+    }
+}
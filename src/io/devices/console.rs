@@ -3,26 +3,59 @@
 // Port 0x00: Data Out   - write to output char
 // Port 0x01: Data In    - read to get input char
 // Port 0x02: Status     - bit 0 = RX ready, bit 1 = TX ready
+//
+// With host commands attached, a line typed from the keyboard that starts
+// with ':' goes to the emulator instead of the 8080: it is echoed and
+// edited here, run on Enter, and the reply printed. The 8080 then gets a
+// bare CR so the program re-prompts.
+//
+// Ctrl-C (here or on a serial port sharing the terminal) asks the emulator
+// to quit: it raises the console's QuitFlag, which main shares with the
+// serial backend and checks to stop the run loop, then shuts down as it
+// does after HLT.
 
+use crate::host::HostCommands;
 use crate::io::IoDevice;
 use crossterm::event::{poll, read, Event, KeyCode, KeyEvent, KeyEventKind};
 use crossterm::event::{KeyModifiers};
-use crossterm::terminal::disable_raw_mode;
+use std::cell::{Cell, RefCell};
 use std::collections::VecDeque;
 use std::io::Write;
+use std::rc::Rc;
 use std::time::Duration;
 
+/// Set when Ctrl-C has been pressed
+pub type QuitFlag = Rc<Cell<bool>>;
+
 pub struct Console {
     input_buffer: VecDeque<u8>,
+    host: Option<Rc<RefCell<HostCommands>>>,
+    host_line: Option<String>,      // A ':' line being typed
+    line_start: bool,
+    quit: QuitFlag,
 }
 
 impl Console {
     pub fn new() -> Self {
         Console {
             input_buffer: VecDeque::new(),
+            host: None,
+            host_line: None,
+            line_start: true,
+            quit: QuitFlag::default(),
         }
     }
 
+    /// Flag raised by Ctrl-C, to share with other terminal readers
+    pub fn quit_flag(&self) -> QuitFlag {
+        self.quit.clone()
+    }
+
+    /// Handle ':' lines with these emulator commands
+    pub fn set_host_commands(&mut self, host: Rc<RefCell<HostCommands>>) {
+        self.host = Some(host);
+    }
+
     /// Queue a character for input (useful for testing or pasting)
     pub fn queue_input(&mut self, c: u8) {
         self.input_buffer.push_back(c);
@@ -34,6 +67,59 @@ impl Console {
     }
 }
 
+impl Console {
+    /// A byte typed at the keyboard
+    fn key(&mut self, c: u8) {
+        let text = match self.host_line.as_mut() {
+            None if c == b':' && self.line_start && self.host.is_some() => {
+                self.host_line = Some(String::new());
+                ":".to_string()
+            }
+            None => {
+                self.line_start = c == 0x0D;
+                self.input_buffer.push_back(c);
+                return;
+            }
+            Some(line) => match c {
+                0x0D => {
+                    let line = self.host_line.take().unwrap_or_default();
+                    let reply = self.host.as_ref()
+                        .map(|host| host.borrow_mut().execute(&line))
+                        .unwrap_or_default();
+                    self.input_buffer.push_back(0x0D);
+                    self.line_start = true;
+                    let mut text = "\r\n".to_string();
+                    for reply_line in reply.lines() {
+                        text.push_str(reply_line);
+                        text.push_str("\r\n");
+                    }
+                    text
+                }
+                0x08 | 0x7F => match line.pop() {
+                    Some(_) => "\x08 \x08".to_string(),
+                    None => {
+                        self.host_line = None;      // Backed out of the ':'
+                        "\x08 \x08".to_string()
+                    }
+                },
+                0x20..=0x7E => {
+                    line.push(c as char);
+                    (c as char).to_string()
+                }
+                _ => return,
+            },
+        };
+        print!("{}", text);
+        std::io::stdout().flush().ok();
+    }
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl IoDevice for Console {
     fn read(&mut self, port: u8) -> u8 {
         match port {
@@ -41,12 +127,10 @@ impl IoDevice for Console {
             0x02 => {
                 // Drain all pending events into buffer
                 while poll(Duration::from_millis(1)).unwrap_or(false) {
-                    if let Ok(event) = read() {
-                        if let Event::Key(key_event) = event {
-                            if key_event.kind == KeyEventKind::Press {  // ADD THIS
-                                if let Some(c) = key_to_byte(key_event) {
-                                    self.input_buffer.push_back(c);
-                                }
+                    if let Ok(Event::Key(key_event)) = read() {
+                        if key_event.kind == KeyEventKind::Press {
+                            if let Some(c) = key_to_byte(key_event, &self.quit) {
+                                self.key(c);
                             }
                         }
                     }
@@ -71,12 +155,11 @@ impl IoDevice for Console {
     }
 }
 
-fn key_to_byte(key_event: KeyEvent) -> Option<u8> {
+pub(crate) fn key_to_byte(key_event: KeyEvent, quit: &Cell<bool>) -> Option<u8> {
     match key_event.code {
         KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
-            // Restore terminal before exit
-            let _ = disable_raw_mode();
-            std::process::exit(0);
+            quit.set(true);
+            None
         }
         KeyCode::Char(c) => Some(c as u8),
         KeyCode::Enter => Some(0x0D),
@@ -84,3 +167,59 @@ fn key_to_byte(key_event: KeyEvent) -> Option<u8> {
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_keys(console: &mut Console, keys: &[u8]) {
+        for &c in keys {
+            console.key(c);
+        }
+    }
+
+    #[test]
+    fn test_host_line_is_not_seen_by_8080() {
+        let host = Rc::new(RefCell::new(HostCommands::new()));
+        let seen = Rc::new(RefCell::new(String::new()));
+        let log = seen.clone();
+        host.borrow_mut().register("say", ":say", Box::new(move |args| {
+            log.borrow_mut().push_str(&args.join(" "));
+            Ok(String::new())
+        }));
+        let mut console = Console::new();
+        console.set_host_commands(host);
+
+        type_keys(&mut console, b":sax\x08y hi\rD 100:\r");
+        assert_eq!(*seen.borrow(), "hi");
+        let to_8080: Vec<u8> = console.input_buffer.iter().copied().collect();
+        assert_eq!(to_8080, b"\rD 100:\r");
+    }
+
+    #[test]
+    fn test_colon_passes_through_without_host() {
+        let mut console = Console::new();
+        type_keys(&mut console, b":10\r");
+        assert_eq!(console.input_buffer.len(), 4);
+
+        // Backspacing over the ':' hands the line back to the 8080
+        let mut console = Console::new();
+        console.set_host_commands(Rc::new(RefCell::new(HostCommands::new())));
+        type_keys(&mut console, b":\x08G");
+        assert_eq!(console.input_buffer.iter().copied().collect::<Vec<_>>(), b"G");
+    }
+
+    #[test]
+    fn test_ctrl_c_requests_quit() {
+        let console = Console::new();
+        let quit = console.quit_flag();
+        let plain = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE);
+        assert_eq!(key_to_byte(plain, &quit), Some(b'c'));
+        assert!(!quit.get());
+
+        let ctrl_c = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert_eq!(key_to_byte(ctrl_c, &quit), None);
+        assert!(console.quit_flag().get());
+        assert!(!Console::new().quit_flag().get());     // Not process-wide
+    }
+}
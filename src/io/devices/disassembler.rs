@@ -0,0 +1,173 @@
+// disassembler.rs - Disassembler coprocessor for the monitor U command
+//
+// The spec'd 0x20-0x27 range collides with the legacy DiskDevice (0x20-0x22),
+// so this device lives in the expansion range with the same port layout.
+//
+// Port 0x74: Opcode byte (write) - up to 3 bytes, extras ignored
+// Port 0x75: Command (write)
+// Port 0x76: Status (read)  - bits 0-6 = text length, bit 7 = error
+// Port 0x77: Text char (read) - auto-advance, 0x00 past the end
+// Port 0x78: Instruction length (read) - bytes consumed (1-3),
+//            or bytes needed when the error flag is set
+//
+// Commands:
+//   0x01: Disassemble (consumes the opcode bytes)
+//   0x02: Reset (clear opcode bytes and text)
+
+use crate::disasm;
+use crate::io::IoDevice;
+
+pub struct DisassemblerDevice {
+    bytes: Vec<u8>,
+    text: Vec<u8>,
+    text_pos: usize,
+    length: u8,
+    error: bool,
+}
+
+impl DisassemblerDevice {
+    pub fn new() -> Self {
+        DisassemblerDevice {
+            bytes: Vec::with_capacity(3),
+            text: Vec::new(),
+            text_pos: 0,
+            length: 0,
+            error: false,
+        }
+    }
+
+    fn do_disassemble(&mut self) {
+        self.text.clear();
+        self.text_pos = 0;
+        match disasm::disassemble(&self.bytes) {
+            Ok((text, len)) => {
+                self.text = text.into_bytes();
+                self.length = len;
+                self.error = false;
+            }
+            Err(needed) => {
+                self.length = needed;
+                self.error = true;
+            }
+        }
+        self.bytes.clear();
+    }
+
+    fn do_reset(&mut self) {
+        self.bytes.clear();
+        self.text.clear();
+        self.text_pos = 0;
+        self.length = 0;
+        self.error = false;
+    }
+
+    fn status(&self) -> u8 {
+        let mut status = (self.text.len() as u8) & 0x7F;
+        if self.error {
+            status |= 0x80;
+        }
+        status
+    }
+
+    fn next_char(&mut self) -> u8 {
+        match self.text.get(self.text_pos) {
+            Some(&c) => {
+                self.text_pos += 1;
+                c
+            }
+            None => 0x00,
+        }
+    }
+}
+
+impl Default for DisassemblerDevice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IoDevice for DisassemblerDevice {
+    fn read(&mut self, port: u8) -> u8 {
+        match port {
+            0x76 => self.status(),
+            0x77 => self.next_char(),
+            0x78 => self.length,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) {
+        match port {
+            0x74 if self.bytes.len() < 3 => self.bytes.push(value),
+            0x75 => match value {
+                0x01 => self.do_disassemble(),
+                0x02 => self.do_reset(),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn disassemble(dev: &mut DisassemblerDevice, bytes: &[u8]) -> String {
+        for &b in bytes {
+            dev.write(0x74, b);
+        }
+        dev.write(0x75, 0x01);
+        let len = dev.read(0x76) & 0x7F;
+        (0..len).map(|_| dev.read(0x77) as char).collect()
+    }
+
+    #[test]
+    fn test_disassemble_mvi() {
+        let mut dev = DisassemblerDevice::new();
+
+        assert_eq!(disassemble(&mut dev, &[0x3E, 0x42]), "MVI  A,42H");
+        assert_eq!(dev.read(0x78), 2);
+        assert_eq!(dev.read(0x77), 0x00);  // Past end
+    }
+
+    #[test]
+    fn test_reports_consumed_length_with_extra_bytes() {
+        let mut dev = DisassemblerDevice::new();
+
+        // ROM always sends three bytes; RET only consumes one
+        assert_eq!(disassemble(&mut dev, &[0xC9, 0x3E, 0x42]), "RET");
+        assert_eq!(dev.read(0x78), 1);
+    }
+
+    #[test]
+    fn test_fourth_byte_ignored() {
+        let mut dev = DisassemblerDevice::new();
+
+        assert_eq!(disassemble(&mut dev, &[0xC3, 0x00, 0xF0, 0xFF]), "JMP  0F000H");
+        assert_eq!(dev.read(0x78), 3);
+    }
+
+    #[test]
+    fn test_short_input_sets_error() {
+        let mut dev = DisassemblerDevice::new();
+
+        assert_eq!(disassemble(&mut dev, &[0xCD, 0x00]), "");
+        assert_eq!(dev.read(0x76), 0x80);
+        assert_eq!(dev.read(0x78), 3);  // Bytes needed
+    }
+
+    #[test]
+    fn test_reset_clears_state() {
+        let mut dev = DisassemblerDevice::new();
+
+        disassemble(&mut dev, &[0x00]);
+        dev.write(0x74, 0x3E);
+        dev.write(0x75, 0x02);
+        assert_eq!(dev.read(0x76), 0x00);
+        assert_eq!(dev.read(0x78), 0x00);
+
+        // Pending byte was discarded
+        assert_eq!(disassemble(&mut dev, &[0x76]), "HLT");
+    }
+}
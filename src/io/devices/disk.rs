@@ -13,6 +13,7 @@ impl DiskDevice {
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open(path)?;
         
         Ok(DiskDevice {
@@ -0,0 +1,410 @@
+// dma.rs - Intel 8257 DMA controller
+//
+// Port base+0: Channel 0 address    Port base+1: Channel 0 terminal count
+// Port base+2: Channel 1 address    Port base+3: Channel 1 terminal count
+// Port base+4: Channel 2 address    Port base+5: Channel 2 terminal count
+// Port base+6: Channel 3 address    Port base+7: Channel 3 terminal count
+// Port base+8: Mode set (write) / Status (read)
+//
+// Channel registers are 16 bits, low byte then high byte through one
+// first/last flip-flop shared by all channels. A mode set resets it.
+//
+// Terminal count register:
+//   Bits 0-13:  Bytes to move minus one
+//   Bits 14-15: 00 verify (no memory access), 01 write (device to memory),
+//               10 read (memory to device)
+//
+// Mode set:
+//   Bits 0-3: Enable channel 0-3
+//   Bit 4:    Rotating priority (else channel 0 highest)
+//   Bit 5:    Extended write (no effect here)
+//   Bit 6:    TC stop - disable a channel when its count runs out
+//   Bit 7:    Autoload - channel 2 reloads from channel 3 at TC;
+//             writes to channel 2 registers also load channel 3
+//
+// Status:
+//   Bits 0-3: Channel reached terminal count (cleared by reading)
+//   Bit 4:    Update flag - channel 2 was reloaded, cleared by its next cycle
+//
+// Peripherals attach to a channel as DmaDevice. After every instruction
+// the controller takes the bus: each enabled channel with DREQ up moves
+// bytes until DREQ drops or its count runs out, 4 cycles per byte stolen
+// from the CPU. Memory goes through the CPU, so ROM stays protected.
+
+use crate::io::{BusMaster, DmaDevice, IoDevice};
+use crate::memory::Memory;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub const CHANNELS: usize = 4;
+
+pub const MODE_ROTATE: u8 = 0x10;
+pub const MODE_TC_STOP: u8 = 0x40;
+pub const MODE_AUTOLOAD: u8 = 0x80;
+pub const STATUS_UPDATE: u8 = 0x10;
+
+pub const TRANSFER_VERIFY: u16 = 0x0000;
+pub const TRANSFER_WRITE: u16 = 0x4000;
+pub const TRANSFER_READ: u16 = 0x8000;
+
+/// Bus cycles per byte moved
+const CYCLES_PER_BYTE: u32 = 4;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Channel {
+    address: u16,
+    count: u16,     // Terminal count register, transfer type included
+}
+
+pub struct Dma8257 {
+    base: u8,
+    channels: [Channel; CHANNELS],
+    mode: u8,
+    status: u8,
+    high_byte: bool,
+    last_served: usize,
+    devices: [Option<Rc<RefCell<dyn DmaDevice>>>; CHANNELS],
+}
+
+impl Dma8257 {
+    /// Controller on `base` to `base + 8`, every channel disabled
+    pub fn new(base: u8) -> Self {
+        Dma8257 {
+            base,
+            channels: [Channel::default(); CHANNELS],
+            mode: 0,
+            status: 0,
+            high_byte: false,
+            last_served: CHANNELS - 1,
+            devices: Default::default(),
+        }
+    }
+
+    /// Attach a peripheral's DREQ/DACK to a channel (0-3)
+    pub fn connect(&mut self, channel: usize, device: Rc<RefCell<dyn DmaDevice>>) {
+        self.devices[channel] = Some(device);
+    }
+
+    pub fn mode(&self) -> u8 {
+        self.mode
+    }
+
+    /// Current address of a channel
+    pub fn address(&self, channel: usize) -> u16 {
+        self.channels[channel].address
+    }
+
+    /// Current terminal count register of a channel
+    pub fn count(&self, channel: usize) -> u16 {
+        self.channels[channel].count
+    }
+
+    /// Put one byte of a 16-bit register through the flip-flop
+    fn write_register(&mut self, channel: usize, is_count: bool, value: u8) {
+        let high = self.high_byte;
+        self.high_byte = !high;
+        let targets = if channel == 2 && self.mode & MODE_AUTOLOAD != 0 { 2..4 } else { channel..channel + 1 };
+        for ch in targets {
+            let reg = &mut self.channels[ch];
+            let reg = if is_count { &mut reg.count } else { &mut reg.address };
+            *reg = if high {
+                (*reg & 0x00FF) | (value as u16) << 8
+            } else {
+                (*reg & 0xFF00) | value as u16
+            };
+        }
+    }
+
+    fn read_register(&mut self, channel: usize, is_count: bool) -> u8 {
+        let high = self.high_byte;
+        self.high_byte = !high;
+        let reg = &self.channels[channel];
+        let value = if is_count { reg.count } else { reg.address };
+        if high { (value >> 8) as u8 } else { value as u8 }
+    }
+
+    /// Channels in priority order
+    fn priority_order(&self) -> [usize; CHANNELS] {
+        let first = if self.mode & MODE_ROTATE != 0 { (self.last_served + 1) % CHANNELS } else { 0 };
+        std::array::from_fn(|i| (first + i) % CHANNELS)
+    }
+
+    /// One DMA cycle on a channel. Returns true at terminal count.
+    fn transfer(&mut self, ch: usize, device: &mut dyn DmaDevice, memory: &mut dyn Memory) -> bool {
+        let Channel { address, count } = self.channels[ch];
+        match count & 0xC000 {
+            TRANSFER_WRITE => memory.write(address, device.dma_read()),
+            TRANSFER_READ => device.dma_write(memory.read(address)),
+            _ => {}
+        }
+        if ch == 2 {
+            self.status &= !STATUS_UPDATE;
+        }
+
+        let remaining = count & 0x3FFF;
+        self.channels[ch] = Channel {
+            address: address.wrapping_add(1),
+            count: (count & 0xC000) | (remaining.wrapping_sub(1) & 0x3FFF),
+        };
+        if remaining != 0 {
+            return false;
+        }
+
+        self.status |= 1 << ch;
+        device.terminal_count();
+        if ch == 2 && self.mode & MODE_AUTOLOAD != 0 {
+            self.channels[2] = self.channels[3];
+            self.status |= STATUS_UPDATE;
+        } else if self.mode & MODE_TC_STOP != 0 {
+            self.mode &= !(1 << ch);
+        }
+        true
+    }
+}
+
+impl IoDevice for Dma8257 {
+    fn read(&mut self, port: u8) -> u8 {
+        match port.wrapping_sub(self.base) {
+            offset @ 0..=7 => self.read_register(offset as usize / 2, offset & 1 != 0),
+            8 => {
+                let status = self.status;
+                self.status &= STATUS_UPDATE;
+                status
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) {
+        match port.wrapping_sub(self.base) {
+            offset @ 0..=7 => self.write_register(offset as usize / 2, offset & 1 != 0, value),
+            8 => {
+                self.mode = value;
+                self.high_byte = false;
+                self.status &= !STATUS_UPDATE;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl BusMaster for Dma8257 {
+    fn service(&mut self, memory: &mut dyn Memory) -> u32 {
+        let mut cycles = 0;
+        for ch in self.priority_order() {
+            let Some(device) = self.devices[ch].clone() else { continue };
+            let mut device = device.borrow_mut();
+            let mut served = false;
+            // Demand transfer: hold the bus while DREQ stays up, at most
+            // one block per instruction
+            while self.mode & (1 << ch) != 0 && device.dma_request() {
+                served = true;
+                cycles += CYCLES_PER_BYTE;
+                if self.transfer(ch, &mut *device, memory) {
+                    break;
+                }
+            }
+            if served {
+                self.last_served = ch;
+            }
+        }
+        cycles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Intel8080;
+    use crate::io::devices::storage::Storage;
+    use crate::memory::FlatMemory;
+    use std::collections::VecDeque;
+    use std::io::Write;
+
+    const BASE: u8 = 0x90;
+
+    /// Peripheral with bytes to give and a record of what it got
+    #[derive(Default)]
+    struct Peripheral {
+        source: VecDeque<u8>,
+        sink: Vec<u8>,
+        wants: usize,
+        tc_seen: bool,
+    }
+
+    impl DmaDevice for Peripheral {
+        fn dma_request(&mut self) -> bool {
+            !self.source.is_empty() || self.sink.len() < self.wants
+        }
+
+        fn dma_read(&mut self) -> u8 {
+            self.source.pop_front().unwrap_or(0xFF)
+        }
+
+        fn dma_write(&mut self, value: u8) {
+            self.sink.push(value);
+        }
+
+        fn terminal_count(&mut self) {
+            self.tc_seen = true;
+        }
+    }
+
+    fn program(dma: &mut Dma8257, channel: u8, address: u16, count: u16) {
+        let port = BASE + channel * 2;
+        dma.write(port, address as u8);
+        dma.write(port, (address >> 8) as u8);
+        dma.write(port + 1, count as u8);
+        dma.write(port + 1, (count >> 8) as u8);
+    }
+
+    fn peripheral(dma: &mut Dma8257, channel: usize, source: &[u8], wants: usize) -> Rc<RefCell<Peripheral>> {
+        let dev = Rc::new(RefCell::new(Peripheral {
+            source: source.iter().copied().collect(),
+            wants,
+            ..Default::default()
+        }));
+        dma.connect(channel, dev.clone());
+        dev
+    }
+
+    #[test]
+    fn test_register_flip_flop() {
+        let mut dma = Dma8257::new(BASE);
+        program(&mut dma, 1, 0x1234, TRANSFER_READ | 0x00FF);
+        assert_eq!(dma.address(1), 0x1234);
+        assert_eq!(dma.count(1), 0x80FF);
+
+        dma.write(BASE + 8, 0x00);          // Mode set resets the flip-flop
+        assert_eq!(dma.read(BASE + 2), 0x34);
+        assert_eq!(dma.read(BASE + 2), 0x12);
+        assert_eq!(dma.read(BASE + 3), 0xFF);
+        assert_eq!(dma.read(BASE + 3), 0x80);
+    }
+
+    #[test]
+    fn test_write_transfer_to_memory() {
+        let mut dma = Dma8257::new(BASE);
+        let dev = peripheral(&mut dma, 0, b"DMA!", 0);
+        program(&mut dma, 0, 0x2000, TRANSFER_WRITE | 3);
+        dma.write(BASE + 8, 0x01);
+
+        let mut mem = FlatMemory::new();
+        assert_eq!(dma.service(&mut mem), 16);
+        assert_eq!((0..4).map(|i| mem.read(0x2000 + i)).collect::<Vec<_>>(), b"DMA!");
+        assert!(dev.borrow().tc_seen);
+        assert_eq!(dma.address(0), 0x2004);
+
+        assert_eq!(dma.read(BASE + 8), 0x01);   // TC on channel 0
+        assert_eq!(dma.read(BASE + 8), 0x00);   // Cleared by reading
+    }
+
+    #[test]
+    fn test_read_transfer_and_verify() {
+        let mut mem = FlatMemory::new();
+        for (i, &b) in b"hello".iter().enumerate() {
+            mem.write(0x3000 + i as u16, b);
+        }
+
+        let mut dma = Dma8257::new(BASE);
+        let dev = peripheral(&mut dma, 1, &[], 5);
+        program(&mut dma, 1, 0x3000, TRANSFER_READ | 4);
+        dma.write(BASE + 8, 0x42);          // Channel 1, TC stop
+        dma.service(&mut mem);
+        assert_eq!(dev.borrow().sink, b"hello");
+        assert_eq!(dma.mode() & 0x02, 0);   // Disabled at TC
+
+        // Verify cycles count down without touching memory or the device
+        let dev = peripheral(&mut dma, 2, &[], 2);
+        program(&mut dma, 2, 0x3000, TRANSFER_VERIFY | 9);
+        dma.write(BASE + 8, 0x04);
+        assert_eq!(dma.service(&mut mem), 40);  // Whole block, DREQ never drops
+        assert!(dev.borrow().sink.is_empty());
+        assert_eq!(dma.read(BASE + 8) & 0x04, 0x04);
+    }
+
+    #[test]
+    fn test_stops_when_dreq_drops() {
+        let mut dma = Dma8257::new(BASE);
+        let dev = peripheral(&mut dma, 0, b"ab", 0);
+        program(&mut dma, 0, 0x4000, TRANSFER_WRITE | 99);
+        dma.write(BASE + 8, 0x01);
+
+        let mut mem = FlatMemory::new();
+        assert_eq!(dma.service(&mut mem), 8);
+        assert_eq!(dma.count(0) & 0x3FFF, 97);
+        assert!(!dev.borrow().tc_seen);
+
+        dev.borrow_mut().source.extend(b"c");
+        dma.service(&mut mem);
+        assert_eq!(mem.read(0x4002), b'c');
+    }
+
+    #[test]
+    fn test_autoload_reloads_channel_2() {
+        let mut dma = Dma8257::new(BASE);
+        dma.write(BASE + 8, MODE_AUTOLOAD);
+        program(&mut dma, 2, 0x5000, TRANSFER_WRITE | 1);
+        assert_eq!(dma.address(3), 0x5000);     // Channel 3 loaded too
+
+        peripheral(&mut dma, 2, b"xyz", 0);
+        dma.write(BASE + 8, MODE_AUTOLOAD | 0x04);
+        let mut mem = FlatMemory::new();
+        dma.service(&mut mem);
+        assert_eq!(dma.read(BASE + 8), 0x04 | STATUS_UPDATE);
+        assert_eq!(dma.address(2), 0x5000);
+
+        dma.service(&mut mem);                  // Next block starts over
+        assert_eq!(mem.read(0x5000), b'z');
+        assert_eq!(dma.read(BASE + 8) & STATUS_UPDATE, 0);
+    }
+
+    #[test]
+    fn test_rom_is_write_protected() {
+        let mut cpu = Intel8080::new();
+        cpu.load_rom(&[0x00; 0x1000]);
+
+        let mut dma = Dma8257::new(BASE);
+        peripheral(&mut dma, 0, &[0xAA, 0xBB], 0);
+        program(&mut dma, 0, 0xEFFF, TRANSFER_WRITE | 1);
+        dma.write(BASE + 8, 0x01);
+        dma.service(&mut cpu);
+        assert_eq!(cpu.read_byte(0xEFFF), 0xAA);
+        assert_eq!(cpu.read_byte(0xF000), 0x00);
+    }
+
+    #[test]
+    fn test_storage_block_load_steals_cycles() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("block.bin");
+        std::fs::File::create(&path).unwrap().write_all(b"ABCD").unwrap();
+
+        // Channel 0 at 2000H, 4 bytes, device to memory, TC stop
+        let code = [
+            0x3E, 0x00, 0xD3, 0x90, 0x3E, 0x20, 0xD3, 0x90,
+            0x3E, 0x03, 0xD3, 0x91, 0x3E, 0x40, 0xD3, 0x91,
+            0x3E, 0x41, 0xD3, 0x98, 0x76,
+        ];
+        let run = |mounted: bool| {
+            let mut cpu = Intel8080::new();
+            cpu.load_program(&code, 0x0000);
+            let storage = Rc::new(RefCell::new(Storage::new()));
+            if mounted {
+                storage.borrow_mut().mount(&path).unwrap();
+            }
+            let dma = Rc::new(RefCell::new(Dma8257::new(BASE)));
+            dma.borrow_mut().connect(0, storage);
+            for port in BASE..=BASE + 8 {
+                cpu.io_bus_mut().map_port(port, dma.clone());
+            }
+            cpu.attach_bus_master(dma);
+            cpu.run();
+            cpu
+        };
+
+        let mut loaded = run(true);
+        let idle = run(false);
+        assert_eq!((0..4).map(|i| loaded.read_byte(0x2000 + i)).collect::<Vec<_>>(), b"ABCD");
+        assert_eq!(loaded.cycles - idle.cycles, 16);
+    }
+}
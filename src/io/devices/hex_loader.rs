@@ -0,0 +1,288 @@
+// hex_loader.rs - Intel HEX loader coprocessor for the monitor H command
+//
+// Port 0x7C: Record char (write) - ':' starts a new record, CR/LF/null ignored
+// Port 0x7D: Command (write)
+// Port 0x7E: Status (read)
+// Port 0x7F: Record address low (read)  - load address, or start address for 03
+// Port 0x80: Record address high (read)
+// Port 0x81: Record data length (read)
+// Port 0x82: Total bytes loaded low (read)
+// Port 0x83: Total bytes loaded high (read)
+// Port 0x84: First load address low (read)
+// Port 0x85: First load address high (read)
+//
+// Commands:
+//   0x01: Process record (validate, write data to memory)
+//   0x02: Reset (clear record and totals - start of an H session)
+//
+// Status:
+//   0x00: Idle (nothing processed since reset)
+//   0x01: Data record loaded
+//   0x02: End of file record
+//   0x03: Start address record
+//   0x80: Missing ':'
+//   0x81: Bad hex digit
+//   0x82: Length mismatch
+//   0x83: Checksum error
+//   0x84: Unsupported record type
+//   0x85: Data runs past 0xFFFF
+//
+// Data bytes reach memory as a bus master, right after the OUT that
+// processed the record, through the CPU's ROM protection. Bad records
+// write nothing.
+
+use crate::formats::{self, FormatErrorKind};
+use crate::io::{BusMaster, IoDevice};
+use crate::memory::Memory;
+
+pub const STATUS_IDLE: u8 = 0x00;
+pub const STATUS_DATA: u8 = 0x01;
+pub const STATUS_EOF: u8 = 0x02;
+pub const STATUS_START: u8 = 0x03;
+pub const ERR_START_CODE: u8 = 0x80;
+pub const ERR_HEX_DIGIT: u8 = 0x81;
+pub const ERR_LENGTH: u8 = 0x82;
+pub const ERR_CHECKSUM: u8 = 0x83;
+pub const ERR_TYPE: u8 = 0x84;
+pub const ERR_OVERFLOW: u8 = 0x85;
+
+/// ':' + 255 data bytes + length, address, type and checksum, as hex
+const MAX_RECORD: usize = 1 + (255 + 5) * 2;
+
+pub struct HexLoaderDevice {
+    text: Vec<u8>,
+    status: u8,
+    address: u16,
+    length: u8,
+    total: u16,
+    first_address: Option<u16>,
+    pending: Option<(u16, Vec<u8>)>,
+}
+
+impl HexLoaderDevice {
+    pub fn new() -> Self {
+        HexLoaderDevice {
+            text: Vec::with_capacity(MAX_RECORD),
+            status: STATUS_IDLE,
+            address: 0,
+            length: 0,
+            total: 0,
+            first_address: None,
+            pending: None,
+        }
+    }
+
+    fn process(&mut self) {
+        let line = String::from_utf8_lossy(&self.text).to_string();
+        self.text.clear();
+        self.address = 0;
+        self.length = 0;
+
+        let record = match formats::parse_hex_record(&line) {
+            Ok(record) => record,
+            Err(kind) => {
+                self.status = error_code(kind);
+                return;
+            }
+        };
+        self.length = record.data.len() as u8;
+        match record.record_type {
+            0x00 => {
+                self.address = record.address;
+                self.total = self.total.wrapping_add(record.data.len() as u16);
+                if !record.data.is_empty() {
+                    self.first_address.get_or_insert(record.address);
+                }
+                self.pending = Some((record.address, record.data));
+                self.status = STATUS_DATA;
+            }
+            0x01 => self.status = STATUS_EOF,
+            _ => {
+                self.address = formats::hex_start_address(&record);
+                self.status = STATUS_START;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl Default for HexLoaderDevice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn error_code(kind: FormatErrorKind) -> u8 {
+    match kind {
+        FormatErrorKind::MissingStartCode => ERR_START_CODE,
+        FormatErrorKind::BadHexDigit => ERR_HEX_DIGIT,
+        FormatErrorKind::BadLength | FormatErrorKind::MissingEnd => ERR_LENGTH,
+        FormatErrorKind::BadChecksum { .. } => ERR_CHECKSUM,
+        FormatErrorKind::UnsupportedType(_) => ERR_TYPE,
+        FormatErrorKind::AddressOverflow => ERR_OVERFLOW,
+    }
+}
+
+impl IoDevice for HexLoaderDevice {
+    fn read(&mut self, port: u8) -> u8 {
+        let first = self.first_address.unwrap_or(0);
+        match port {
+            0x7E => self.status,
+            0x7F => self.address as u8,
+            0x80 => (self.address >> 8) as u8,
+            0x81 => self.length,
+            0x82 => self.total as u8,
+            0x83 => (self.total >> 8) as u8,
+            0x84 => first as u8,
+            0x85 => (first >> 8) as u8,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) {
+        match port {
+            // A ':' always starts over, so a garbled line can't poison the next
+            0x7C if value == b':' => {
+                self.text.clear();
+                self.text.push(value);
+            }
+            0x7C if !matches!(value, 0x00 | 0x0A | 0x0D) && self.text.len() < MAX_RECORD => {
+                self.text.push(value);
+            }
+            0x7D => match value {
+                0x01 => self.process(),
+                0x02 => self.reset(),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+impl BusMaster for HexLoaderDevice {
+    fn service(&mut self, memory: &mut dyn Memory) -> u32 {
+        // A coprocessor write, not a timed bus cycle
+        if let Some((address, data)) = self.pending.take() {
+            for (i, &byte) in data.iter().enumerate() {
+                memory.write(address.wrapping_add(i as u16), byte);
+            }
+        }
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Intel8080;
+    use crate::memory::FlatMemory;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn send(dev: &mut HexLoaderDevice, line: &str) -> u8 {
+        for b in line.bytes() {
+            dev.write(0x7C, b);
+        }
+        dev.write(0x7C, 0x0D);
+        dev.write(0x7D, 0x01);
+        dev.read(0x7E)
+    }
+
+    #[test]
+    fn test_data_record_reaches_memory() {
+        let mut dev = HexLoaderDevice::new();
+        let mut mem = FlatMemory::new();
+        assert_eq!(dev.read(0x7E), STATUS_IDLE);
+
+        assert_eq!(send(&mut dev, ":0A0100003E48D3013E49D3017600CA"), STATUS_DATA);
+        assert_eq!((dev.read(0x80), dev.read(0x7F)), (0x01, 0x00));
+        assert_eq!(dev.read(0x81), 10);
+
+        dev.service(&mut mem);
+        assert_eq!(mem.read(0x0100), 0x3E);
+        assert_eq!(mem.read(0x0109), 0x00);
+        assert_eq!(mem.read(0x0108), 0x76);
+    }
+
+    #[test]
+    fn test_session_totals() {
+        let mut dev = HexLoaderDevice::new();
+        let data: Vec<u8> = (0..=255).collect();
+        let text = formats::write_intel_hex(&data, 0x0100, None);
+
+        let mut last = STATUS_IDLE;
+        for line in text.lines() {
+            last = send(&mut dev, line);
+        }
+        assert_eq!(last, STATUS_EOF);
+        assert_eq!((dev.read(0x83), dev.read(0x82)), (0x01, 0x00));  // 256 bytes
+        assert_eq!((dev.read(0x85), dev.read(0x84)), (0x01, 0x00));  // at 0100
+
+        dev.write(0x7D, 0x02);
+        assert_eq!(dev.read(0x7E), STATUS_IDLE);
+        assert_eq!(dev.read(0x82), 0);
+    }
+
+    #[test]
+    fn test_bad_records_write_nothing() {
+        let mut dev = HexLoaderDevice::new();
+        let mut mem = FlatMemory::new();
+
+        assert_eq!(send(&mut dev, ":0A0100003E48D3013E49D3017600CB"), ERR_CHECKSUM);
+        dev.service(&mut mem);
+        assert_eq!(mem.read(0x0100), 0x00);
+        assert_eq!(dev.read(0x82), 0);
+
+        assert_eq!(send(&mut dev, "0100000000FF"), ERR_START_CODE);
+        assert_eq!(send(&mut dev, ":01000000ZZFF"), ERR_HEX_DIGIT);
+        assert_eq!(send(&mut dev, ":0200000000FF"), ERR_LENGTH);
+        assert_eq!(send(&mut dev, ":020000040000FA"), ERR_TYPE);
+        assert_eq!(send(&mut dev, ":02FFFF00000000"), ERR_OVERFLOW);
+    }
+
+    #[test]
+    fn test_start_address_record() {
+        let mut dev = HexLoaderDevice::new();
+
+        assert_eq!(send(&mut dev, ":0400000300000200F7"), STATUS_START);
+        assert_eq!((dev.read(0x80), dev.read(0x7F)), (0x02, 0x00));
+    }
+
+    #[test]
+    fn test_colon_restarts_record() {
+        let mut dev = HexLoaderDevice::new();
+
+        for b in b":0A01GARBAGE" {
+            dev.write(0x7C, *b);
+        }
+        assert_eq!(send(&mut dev, ":00000001FF"), STATUS_EOF);
+    }
+
+    #[test]
+    fn test_rom_loop_writes_through_cpu() {
+        // LXI H,0200 / MOV A,M / ORA A / JZ DONE / OUT 7C / INX H / JMP LOOP
+        // DONE: MVI A,01 / OUT 7D / HLT
+        let program = [
+            0x21, 0x00, 0x02,
+            0x7E, 0xB7, 0xCA, 0x0F, 0x01, 0xD3, 0x7C, 0x23, 0xC3, 0x03, 0x01,
+            0x00,
+            0x3E, 0x01, 0xD3, 0x7D, 0x76,
+        ];
+        let mut cpu = Intel8080::new();
+        let loader = Rc::new(RefCell::new(HexLoaderDevice::new()));
+        for port in 0x7C..=0x85 {
+            cpu.io_bus_mut().map_port(port, loader.clone());
+        }
+        cpu.attach_bus_master(loader.clone());
+        cpu.load_program(b":03300000C3000109\0", 0x0200);
+        cpu.load_program(&program, 0x0100);
+        cpu.run();
+
+        assert_eq!(loader.borrow().status, STATUS_DATA);
+        assert_eq!(cpu.read_byte(0x3000), 0xC3);
+        assert_eq!(cpu.read_word(0x3001), 0x0100);
+    }
+}
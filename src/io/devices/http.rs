@@ -0,0 +1,508 @@
+// http.rs - HTTP client device
+//
+// Port 0x40: URL char (write)
+// Port 0x41: Command (write)
+// Port 0x42: Status code low byte (read)  - e.g. 200 = 0xC8
+// Port 0x43: Header char (read) - auto-advance, 0x00 at end
+// Port 0x44: Body char (read) - auto-advance, 0x00 at end of chunk
+// Port 0x45: POST body char (write)
+// Port 0x46: Device state (read)
+// Port 0x47: Status code high byte (read) - e.g. 404 = 0x01/0x94
+//
+// Commands:
+//   0x01: GET
+//   0x02: POST (sends the accumulated POST body)
+//   0x03: Next chunk
+//   0x04: Clear (URL, POST body and response)
+//
+// Device state:
+//   0x00: Idle
+//   0x01: Waiting (request in flight)
+//   0x02: Ready (body chars available in this chunk)
+//   0x03: Done (no more body chars)
+//   0x04: Chunk end (more body remains - send Next chunk)
+//   0x81: Bad URL
+//   0x82: Connect failed
+//   0x83: I/O or protocol error
+//   0x84: Unsupported scheme (only http:// is supported)
+//
+// Requests run on a background thread; the 8080 polls the state port.
+// The body is delivered in chunks (256 bytes by default) so the ROM can
+// process it in a small buffer.
+
+use crate::io::IoDevice;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+pub const STATE_IDLE: u8 = 0x00;
+pub const STATE_WAITING: u8 = 0x01;
+pub const STATE_READY: u8 = 0x02;
+pub const STATE_DONE: u8 = 0x03;
+pub const STATE_CHUNK_END: u8 = 0x04;
+pub const ERR_BAD_URL: u8 = 0x81;
+pub const ERR_CONNECT: u8 = 0x82;
+pub const ERR_IO: u8 = 0x83;
+pub const ERR_UNSUPPORTED: u8 = 0x84;
+
+pub const DEFAULT_CHUNK_SIZE: usize = 256;
+const MAX_URL: usize = 255;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<u8>,   // Header lines, CR LF separated, no status line
+    pub body: Vec<u8>,
+}
+
+pub struct HttpDevice {
+    url: Vec<u8>,
+    post_body: Vec<u8>,
+    state: u8,
+    pending: Option<Receiver<Result<HttpResponse, u8>>>,
+    response: Option<HttpResponse>,
+    header_pos: usize,
+    body_pos: usize,
+    chunk_end: usize,
+    chunk_size: usize,
+}
+
+impl HttpDevice {
+    pub fn new() -> Self {
+        HttpDevice {
+            url: Vec::new(),
+            post_body: Vec::new(),
+            state: STATE_IDLE,
+            pending: None,
+            response: None,
+            header_pos: 0,
+            body_pos: 0,
+            chunk_end: 0,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    pub fn set_chunk_size(&mut self, size: usize) {
+        self.chunk_size = size.max(1);
+    }
+
+    /// Response of the last completed request
+    pub fn response(&mut self) -> Option<&HttpResponse> {
+        self.poll();
+        self.response.as_ref()
+    }
+
+    fn start(&mut self, method: &'static str) {
+        let url = String::from_utf8_lossy(&self.url).to_string();
+        let body = if method == "POST" { self.post_body.clone() } else { Vec::new() };
+        self.url.clear();
+        self.post_body.clear();
+        self.response = None;
+
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = tx.send(request(method, &url, &[], &body));
+        });
+        self.pending = Some(rx);
+        self.state = STATE_WAITING;
+    }
+
+    /// Pick up a finished request from the background thread
+    fn poll(&mut self) {
+        let result = match &self.pending {
+            Some(rx) => match rx.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => Err(ERR_IO),
+            },
+            None => return,
+        };
+        self.pending = None;
+        match result {
+            Ok(response) => {
+                self.response = Some(response);
+                self.header_pos = 0;
+                self.body_pos = 0;
+                self.chunk_end = 0;
+                self.next_chunk();
+            }
+            Err(code) => self.state = code,
+        }
+    }
+
+    fn next_chunk(&mut self) {
+        if let Some(ref r) = self.response {
+            if self.body_pos < self.chunk_end {
+                return;  // Current chunk not finished
+            }
+            self.chunk_end = (self.body_pos + self.chunk_size).min(r.body.len());
+            self.update_body_state();
+        }
+    }
+
+    fn update_body_state(&mut self) {
+        if let Some(ref r) = self.response {
+            self.state = if self.body_pos < self.chunk_end {
+                STATE_READY
+            } else if self.body_pos < r.body.len() {
+                STATE_CHUNK_END
+            } else {
+                STATE_DONE
+            };
+        }
+    }
+
+    fn clear(&mut self) {
+        self.url.clear();
+        self.post_body.clear();
+        self.pending = None;
+        self.response = None;
+        self.state = STATE_IDLE;
+    }
+
+    fn status_code(&mut self) -> u16 {
+        self.poll();
+        self.response.as_ref().map(|r| r.status).unwrap_or(0)
+    }
+
+    fn next_header_char(&mut self) -> u8 {
+        self.poll();
+        if let Some(ref r) = self.response {
+            if let Some(&c) = r.headers.get(self.header_pos) {
+                self.header_pos += 1;
+                return c;
+            }
+        }
+        0x00
+    }
+
+    fn next_body_char(&mut self) -> u8 {
+        self.poll();
+        let c = match self.response {
+            Some(ref r) if self.body_pos < self.chunk_end => r.body[self.body_pos],
+            _ => return 0x00,
+        };
+        self.body_pos += 1;
+        self.update_body_state();
+        c
+    }
+}
+
+impl Default for HttpDevice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IoDevice for HttpDevice {
+    fn read(&mut self, port: u8) -> u8 {
+        match port {
+            0x42 => self.status_code() as u8,
+            0x43 => self.next_header_char(),
+            0x44 => self.next_body_char(),
+            0x46 => {
+                self.poll();
+                self.state
+            }
+            0x47 => (self.status_code() >> 8) as u8,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) {
+        match port {
+            0x40 if value != 0x00 && self.url.len() < MAX_URL => self.url.push(value),
+            0x41 => {
+                if self.state == STATE_WAITING && value != 0x04 {
+                    return;  // One request at a time
+                }
+                match value {
+                    0x01 => self.start("GET"),
+                    0x02 => self.start("POST"),
+                    0x03 => self.next_chunk(),
+                    0x04 => self.clear(),
+                    _ => {}
+                }
+            }
+            0x45 => self.post_body.push(value),
+            _ => {}
+        }
+    }
+}
+
+/// Split http://host[:port]/path into (host, port, path)
+pub(crate) fn parse_url(url: &str) -> Result<(String, u16, String), u8> {
+    let rest = if let Some(r) = url.strip_prefix("http://") {
+        r
+    } else if url.contains("://") {
+        return Err(ERR_UNSUPPORTED);
+    } else {
+        url
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((h, p)) => (h, p.parse().map_err(|_| ERR_BAD_URL)?),
+        None => (authority, 80),
+    };
+    if host.is_empty() || path.bytes().any(|b| b <= b' ') {
+        return Err(ERR_BAD_URL);
+    }
+    Ok((host.to_string(), port, path.to_string()))
+}
+
+/// Blocking HTTP/1.1 request with optional extra headers.
+/// Runs on a background thread; errors are device state codes.
+pub(crate) fn request(
+    method: &str,
+    url: &str,
+    headers: &[(String, String)],
+    body: &[u8],
+) -> Result<HttpResponse, u8> {
+    let (host, port, path) = parse_url(url)?;
+    let addr = (host.as_str(), port)
+        .to_socket_addrs()
+        .map_err(|_| ERR_CONNECT)?
+        .next()
+        .ok_or(ERR_CONNECT)?;
+    let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT).map_err(|_| ERR_CONNECT)?;
+    stream.set_read_timeout(Some(TIMEOUT)).map_err(|_| ERR_IO)?;
+
+    let mut req = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: intel8080_emu\r\nConnection: close\r\n",
+        method, path, host
+    );
+    for (name, value) in headers {
+        req.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if method == "POST" {
+        req.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    req.push_str("\r\n");
+    stream.write_all(req.as_bytes()).map_err(|_| ERR_IO)?;
+    stream.write_all(body).map_err(|_| ERR_IO)?;
+
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|_| ERR_IO)?;
+    let status = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .ok_or(ERR_IO)?;
+
+    let mut response_headers = Vec::new();
+    let mut content_length = None;
+    let mut chunked = false;
+    loop {
+        line.clear();
+        reader.read_line(&mut line).map_err(|_| ERR_IO)?;
+        let trimmed = line.trim_end();
+        if trimmed.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = trimmed.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = value.parse::<usize>().ok();
+            } else if name.eq_ignore_ascii_case("Transfer-Encoding") {
+                chunked = value.eq_ignore_ascii_case("chunked");
+            }
+        }
+        response_headers.extend_from_slice(trimmed.as_bytes());
+        response_headers.extend_from_slice(b"\r\n");
+    }
+
+    let mut body = Vec::new();
+    if chunked {
+        loop {
+            line.clear();
+            reader.read_line(&mut line).map_err(|_| ERR_IO)?;
+            let size_text = line.trim().split(';').next().unwrap_or("");
+            let size = usize::from_str_radix(size_text, 16).map_err(|_| ERR_IO)?;
+            if size == 0 {
+                break;
+            }
+            let start = body.len();
+            body.resize(start + size, 0);
+            reader.read_exact(&mut body[start..]).map_err(|_| ERR_IO)?;
+            line.clear();
+            reader.read_line(&mut line).map_err(|_| ERR_IO)?;  // Trailing CR LF
+        }
+    } else if let Some(len) = content_length {
+        body.resize(len, 0);
+        reader.read_exact(&mut body).map_err(|_| ERR_IO)?;
+    } else {
+        reader.read_to_end(&mut body).map_err(|_| ERR_IO)?;
+    }
+
+    Ok(HttpResponse { status, headers: response_headers, body })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    /// Serve one canned response per connection on a local port.
+    /// Returns the base URL and a receiver for the raw requests.
+    fn serve(responses: Vec<Vec<u8>>) -> (String, Receiver<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                // Read headers, then any Content-Length body
+                loop {
+                    let n = stream.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let len = text
+                            .lines()
+                            .find_map(|l| l.strip_prefix("Content-Length: "))
+                            .map(|v| v.trim().parse::<usize>().unwrap())
+                            .unwrap_or(0);
+                        if request.len() >= end + 4 + len || n == 0 {
+                            break;
+                        }
+                    }
+                }
+                stream.write_all(&response).unwrap();
+                tx.send(request).unwrap();
+            }
+        });
+        (url, rx)
+    }
+
+    fn send_url(dev: &mut HttpDevice, url: &str) {
+        for b in url.bytes() {
+            dev.write(0x40, b);
+        }
+    }
+
+    fn wait(dev: &mut HttpDevice) -> u8 {
+        let start = Instant::now();
+        while dev.read(0x46) == STATE_WAITING {
+            assert!(start.elapsed() < Duration::from_secs(5), "request timed out");
+            thread::sleep(Duration::from_millis(1));
+        }
+        dev.read(0x46)
+    }
+
+    fn read_stream(dev: &mut HttpDevice, port: u8) -> String {
+        let mut out = String::new();
+        loop {
+            match dev.read(port) {
+                0x00 => return out,
+                c => out.push(c as char),
+            }
+        }
+    }
+
+    #[test]
+    fn test_get() {
+        let (url, requests) = serve(vec![
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nX-Test: yes\r\n\r\nhello".to_vec(),
+        ]);
+        let mut dev = HttpDevice::new();
+
+        send_url(&mut dev, &format!("{}/index.txt", url));
+        dev.write(0x41, 0x01);
+        assert_eq!(wait(&mut dev), STATE_READY);
+
+        assert_eq!((dev.read(0x47), dev.read(0x42)), (0x00, 0xC8));
+        assert!(read_stream(&mut dev, 0x43).contains("X-Test: yes\r\n"));
+        assert_eq!(read_stream(&mut dev, 0x44), "hello");
+        assert_eq!(dev.read(0x46), STATE_DONE);
+
+        let request = String::from_utf8(requests.recv().unwrap()).unwrap();
+        assert!(request.starts_with("GET /index.txt HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn test_post_body_and_404() {
+        let (url, requests) = serve(vec![
+            b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_vec(),
+        ]);
+        let mut dev = HttpDevice::new();
+
+        send_url(&mut dev, &url);
+        for b in b"key=value" {
+            dev.write(0x45, *b);
+        }
+        dev.write(0x41, 0x02);
+        assert_eq!(wait(&mut dev), STATE_DONE);
+        assert_eq!((dev.read(0x47), dev.read(0x42)), (0x01, 0x94));  // 404
+
+        let request = String::from_utf8(requests.recv().unwrap()).unwrap();
+        assert!(request.starts_with("POST / HTTP/1.1\r\n"));
+        assert!(request.contains("Content-Length: 9\r\n"));
+        assert!(request.ends_with("\r\n\r\nkey=value"));
+    }
+
+    #[test]
+    fn test_body_delivered_in_chunks() {
+        let (url, _requests) = serve(vec![
+            b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n0123456789".to_vec(),
+        ]);
+        let mut dev = HttpDevice::new();
+        dev.set_chunk_size(4);
+
+        send_url(&mut dev, &url);
+        dev.write(0x41, 0x01);
+        wait(&mut dev);
+
+        assert_eq!(read_stream(&mut dev, 0x44), "0123");
+        assert_eq!(dev.read(0x46), STATE_CHUNK_END);
+        dev.write(0x41, 0x03);
+        assert_eq!(read_stream(&mut dev, 0x44), "4567");
+        dev.write(0x41, 0x03);
+        assert_eq!(read_stream(&mut dev, 0x44), "89");
+        assert_eq!(dev.read(0x46), STATE_DONE);
+    }
+
+    #[test]
+    fn test_chunked_transfer_encoding() {
+        let (url, _requests) = serve(vec![
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nWiki\r\n5\r\npedia\r\n0\r\n\r\n".to_vec(),
+        ]);
+        let mut dev = HttpDevice::new();
+
+        send_url(&mut dev, &url);
+        dev.write(0x41, 0x01);
+        wait(&mut dev);
+        assert_eq!(read_stream(&mut dev, 0x44), "Wikipedia");
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut dev = HttpDevice::new();
+
+        send_url(&mut dev, "https://example.com/");
+        dev.write(0x41, 0x01);
+        assert_eq!(wait(&mut dev), ERR_UNSUPPORTED);
+
+        send_url(&mut dev, "http://127.0.0.1:notaport/");
+        dev.write(0x41, 0x01);
+        assert_eq!(wait(&mut dev), ERR_BAD_URL);
+
+        // Bind then drop to get a port nobody is listening on
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        send_url(&mut dev, &format!("http://127.0.0.1:{}/", port));
+        dev.write(0x41, 0x01);
+        assert_eq!(wait(&mut dev), ERR_CONNECT);
+
+        dev.write(0x41, 0x04);
+        assert_eq!(dev.read(0x46), STATE_IDLE);
+    }
+}
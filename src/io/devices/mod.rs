@@ -1,7 +1,27 @@
+pub mod acia;           // 6850 ACIA (88-2SIO)
+pub mod altair_disk;    // MITS 88-DCDD floppy controller
+pub mod assembler;      // Assembler coprocessor
+pub mod assistant;      // LLM assistant (Claude API)
+pub mod cassette;       // Kansas City Standard cassette interface
+pub mod ay;             // AY-3-8910 sound generator
 pub mod console;
+pub mod disassembler;   // Disassembler coprocessor
+pub mod dma;            // Intel 8257 DMA controller
 pub mod disk;           // Legacy 16-bit - deprecated
+pub mod hex_loader;     // Intel HEX loader coprocessor
+pub mod http;           // HTTP client
 pub mod null;
+pub mod page_cache;     // Page cache for storage files
+pub mod paper_tape;     // Paper tape reader and punch
+pub mod paper_tape_mount; // Paper tape mount service
+pub mod pic;            // Intel 8259A interrupt controller
+pub mod ppi;            // Intel 8255 parallel port
+pub mod printer;        // Line printer spooled to a host file
 pub mod storage;        // New 24-bit storage
+pub mod storage_backend; // Storage unit backends: file, memory, overlay
 pub mod storage_mount;  // File mounting service
+pub mod time;           // System time and uptime
 pub mod timer;
+pub mod usart;          // Intel 8251 USART
+pub mod vdm;            // VDM-1 memory-mapped text display
 pub mod test_console;
@@ -0,0 +1,295 @@
+// page_cache.rs - Page cache over a storage file
+//
+// Storage moves one byte per port access, so going to the file for each
+// one costs a seek and a read or write syscall per byte. The cache keeps
+// up to `capacity` 4KB pages of the file in memory and tracks which are
+// dirty; least recently used pages are evicted (written back if dirty).
+//
+// Sync policies:
+//   WriteBack    - dirty pages reach the file on flush, eviction or drop
+//   WriteThrough - every write also goes straight to the file
+//   Durable      - write-back, and flush also fsyncs the file
+//
+// Only bytes below the logical length are ever written, so a partly used
+// last page doesn't pad the file.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+pub const PAGE_SIZE: usize = 4096;
+
+/// Pages kept by default (256KB)
+pub const DEFAULT_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncPolicy {
+    #[default]
+    WriteBack,
+    WriteThrough,
+    Durable,
+}
+
+struct Page {
+    data: Box<[u8; PAGE_SIZE]>,
+    dirty: bool,
+    used: u64,
+}
+
+pub struct PageCache {
+    file: File,
+    pages: HashMap<u64, Page>,
+    capacity: usize,
+    policy: SyncPolicy,
+    len: u64,           // Logical length
+    file_len: u64,      // What the file itself holds
+    clock: u64,
+}
+
+impl PageCache {
+    pub fn new(file: File, policy: SyncPolicy) -> io::Result<Self> {
+        let len = file.metadata()?.len();
+        Ok(PageCache {
+            file,
+            pages: HashMap::new(),
+            capacity: DEFAULT_CAPACITY,
+            policy,
+            len,
+            file_len: len,
+            clock: 0,
+        })
+    }
+
+    /// Swap in another handle on the same file, e.g. a writable one.
+    /// Cached pages are kept.
+    pub fn set_file(&mut self, file: File) {
+        self.file = file;
+    }
+
+    /// Most pages to keep in memory (at least 1)
+    pub fn set_capacity(&mut self, pages: usize) -> io::Result<()> {
+        self.capacity = pages.max(1);
+        self.evict()
+    }
+
+    pub fn set_policy(&mut self, policy: SyncPolicy) {
+        self.policy = policy;
+    }
+
+    /// Logical file length, including writes not yet flushed
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Pages in memory that the file doesn't have yet
+    pub fn dirty_pages(&self) -> usize {
+        self.pages.values().filter(|p| p.dirty).count()
+    }
+
+    pub fn read_byte(&mut self, offset: u64) -> io::Result<u8> {
+        let page = self.page(offset / PAGE_SIZE as u64)?;
+        Ok(page.data[(offset % PAGE_SIZE as u64) as usize])
+    }
+
+    pub fn write_byte(&mut self, offset: u64, value: u8) -> io::Result<()> {
+        let write_through = self.policy == SyncPolicy::WriteThrough;
+        let page = self.page(offset / PAGE_SIZE as u64)?;
+        page.data[(offset % PAGE_SIZE as u64) as usize] = value;
+        page.dirty |= !write_through;
+        self.len = self.len.max(offset + 1);
+        if write_through {
+            self.file.seek(SeekFrom::Start(offset))?;
+            self.file.write_all(&[value])?;
+            self.file_len = self.file_len.max(offset + 1);
+        }
+        Ok(())
+    }
+
+    /// Change the logical length; bytes past it are dropped from the cache
+    /// and the file, and growing reads back as zeros
+    pub fn set_len(&mut self, len: u64) -> io::Result<()> {
+        let page_size = PAGE_SIZE as u64;
+        self.pages.retain(|&number, _| number * page_size < len);
+        if let Some(page) = self.pages.get_mut(&(len / page_size)) {
+            page.data[(len % page_size) as usize..].fill(0);
+        }
+        self.len = len;
+        if self.file_len > len {
+            self.file.set_len(len)?;
+            self.file_len = len;
+        }
+        Ok(())
+    }
+
+    /// Write every dirty page back (and fsync under Durable)
+    pub fn flush(&mut self) -> io::Result<()> {
+        let mut dirty: Vec<u64> = self.pages.iter().filter(|(_, p)| p.dirty).map(|(&n, _)| n).collect();
+        dirty.sort_unstable();
+        for number in dirty {
+            self.write_back(number)?;
+        }
+        self.file.flush()?;
+        if self.policy == SyncPolicy::Durable {
+            self.file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// The page, loading it (and evicting another) if needed
+    fn page(&mut self, number: u64) -> io::Result<&mut Page> {
+        self.clock += 1;
+        if !self.pages.contains_key(&number) {
+            self.evict_to(self.capacity - 1)?;
+            let mut data = Box::new([0u8; PAGE_SIZE]);
+            let start = number * PAGE_SIZE as u64;
+            if start < self.file_len {
+                let count = (self.file_len - start).min(PAGE_SIZE as u64) as usize;
+                self.file.seek(SeekFrom::Start(start))?;
+                self.file.read_exact(&mut data[..count])?;
+            }
+            self.pages.insert(number, Page { data, dirty: false, used: 0 });
+        }
+        let page = self.pages.get_mut(&number).unwrap();
+        page.used = self.clock;
+        Ok(page)
+    }
+
+    fn evict(&mut self) -> io::Result<()> {
+        self.evict_to(self.capacity)
+    }
+
+    /// Drop least recently used pages until at most `keep` remain
+    fn evict_to(&mut self, keep: usize) -> io::Result<()> {
+        while self.pages.len() > keep {
+            let oldest = *self.pages.iter().min_by_key(|(_, p)| p.used).unwrap().0;
+            self.write_back(oldest)?;
+            self.pages.remove(&oldest);
+        }
+        Ok(())
+    }
+
+    fn write_back(&mut self, number: u64) -> io::Result<()> {
+        let start = number * PAGE_SIZE as u64;
+        let Some(page) = self.pages.get_mut(&number).filter(|p| p.dirty) else { return Ok(()) };
+        let count = self.len.saturating_sub(start).min(PAGE_SIZE as u64) as usize;
+        self.file.seek(SeekFrom::Start(start))?;
+        self.file.write_all(&page.data[..count])?;
+        page.dirty = false;
+        self.file_len = self.file_len.max(start + count as u64);
+        Ok(())
+    }
+}
+
+impl Drop for PageCache {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::path::Path;
+
+    fn open(path: &Path) -> File {
+        OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path).unwrap()
+    }
+
+    #[test]
+    fn test_write_back_until_flush() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wb.bin");
+        std::fs::write(&path, b"abc").unwrap();
+        let mut cache = PageCache::new(open(&path), SyncPolicy::WriteBack).unwrap();
+
+        assert_eq!(cache.read_byte(1).unwrap(), b'b');
+        cache.write_byte(1, b'B').unwrap();
+        cache.write_byte(4, b'E').unwrap();         // Past the end leaves a gap
+        assert_eq!(cache.len(), 5);
+        assert_eq!(cache.dirty_pages(), 1);
+        assert_eq!(std::fs::read(&path).unwrap(), b"abc");
+
+        cache.flush().unwrap();
+        assert_eq!(cache.dirty_pages(), 0);
+        assert_eq!(std::fs::read(&path).unwrap(), b"aBc\0E");
+    }
+
+    #[test]
+    fn test_write_through() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wt.bin");
+        let mut cache = PageCache::new(open(&path), SyncPolicy::WriteThrough).unwrap();
+        cache.write_byte(0, 7).unwrap();
+        assert_eq!(cache.dirty_pages(), 0);
+        assert_eq!(std::fs::read(&path).unwrap(), vec![7]);
+    }
+
+    #[test]
+    fn test_eviction_writes_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lru.bin");
+        let mut cache = PageCache::new(open(&path), SyncPolicy::WriteBack).unwrap();
+        cache.set_capacity(2).unwrap();
+
+        for page in 0..5u64 {
+            cache.write_byte(page * PAGE_SIZE as u64, page as u8 + 1).unwrap();
+        }
+        assert_eq!(cache.pages.len(), 2);
+        // Pages 0-2 were evicted to the file already
+        let on_disk = std::fs::read(&path).unwrap();
+        assert_eq!(on_disk.len(), 3 * PAGE_SIZE);
+        assert_eq!(on_disk[2 * PAGE_SIZE], 3);
+        // And read back through the cache
+        for page in 0..5u64 {
+            assert_eq!(cache.read_byte(page * PAGE_SIZE as u64).unwrap(), page as u8 + 1);
+        }
+    }
+
+    #[test]
+    fn test_pages_past_the_file_end_load_blank() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gap.bin");
+        std::fs::write(&path, b"xy").unwrap();
+        let mut cache = PageCache::new(open(&path), SyncPolicy::WriteBack).unwrap();
+        cache.set_capacity(1).unwrap();
+
+        cache.write_byte(3 * PAGE_SIZE as u64, 1).unwrap();     // Grows len, not the file
+        assert_eq!(cache.read_byte(0).unwrap(), b'x');          // Evicts and writes page 3
+        assert_eq!(cache.read_byte(PAGE_SIZE as u64).unwrap(), 0);
+        assert_eq!(cache.read_byte(3 * PAGE_SIZE as u64).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_set_len_cuts_cache_and_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cut.bin");
+        std::fs::write(&path, b"abcdef").unwrap();
+        let mut cache = PageCache::new(open(&path), SyncPolicy::WriteBack).unwrap();
+        cache.write_byte(PAGE_SIZE as u64, 1).unwrap();
+
+        cache.set_len(3).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"abc");
+        cache.write_byte(5, b'!').unwrap();                     // Regrows with a zero gap
+        assert_eq!(cache.read_byte(3).unwrap(), 0);
+        cache.flush().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"abc\0\0!");
+    }
+
+    #[test]
+    fn test_drop_flushes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("drop.bin");
+        {
+            let mut cache = PageCache::new(open(&path), SyncPolicy::Durable).unwrap();
+            cache.write_byte(PAGE_SIZE as u64 + 1, 0x42).unwrap();
+        }
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(data.len(), PAGE_SIZE + 2);
+        assert_eq!(data[PAGE_SIZE + 1], 0x42);
+    }
+}
@@ -0,0 +1,409 @@
+// paper_tape.rs - Paper tape reader and punch
+//
+// Reader (default ports 0xB0-0xB1):
+//   Port base+0: Status (read) / Command (write)
+//   Port base+1: Data (read) - the frame under the read head
+//
+//   Status: Bit 0 READY   - a frame has been read and not yet taken
+//           Bit 1 MOUNTED - a tape is loaded
+//           Bit 7 END     - no more tape to read
+//   Commands: 0x01 Advance - step the tape one frame into the data register
+//             0x02 Rewind
+//
+// Punch (default ports 0xB2-0xB3):
+//   Port base+0: Status (read) / Command (write)
+//   Port base+1: Data (write) - punch one frame
+//
+//   Status: Bit 0 READY   - a tape is loaded and will take a frame
+//           Bit 1 MOUNTED
+//   Commands: 0x01 Leader - punch LEADER_FRAMES blank frames
+//
+// Leader and trailer are runs of blank (0x00) frames. By default the reader
+// steps over the leader to the first punched frame, and reports END when
+// only trailer is left, so loaders don't see the tape's blank ends.
+//
+// The reader loads the whole tape file at mount; the punch appends to its
+// file (creating it) one frame at a time. Both can also be driven from the
+// host with :reader and :punch (see host_command).
+
+use crate::io::IoDevice;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const STATUS_READY: u8 = 0x01;
+pub const STATUS_MOUNTED: u8 = 0x02;
+pub const STATUS_END: u8 = 0x80;
+
+pub const CMD_ADVANCE: u8 = 0x01;
+pub const CMD_REWIND: u8 = 0x02;
+pub const CMD_LEADER: u8 = 0x01;
+
+pub const READER_BASE: u8 = 0xB0;
+pub const PUNCH_BASE: u8 = 0xB2;
+
+/// Blank frames in a punched leader or trailer (about 10 inches)
+pub const LEADER_FRAMES: usize = 100;
+
+pub struct TapeReader {
+    base: u8,
+    path: Option<PathBuf>,
+    tape: Vec<u8>,
+    position: usize,
+    end: usize,
+    skip_leader: bool,
+    data: u8,
+    ready: bool,
+}
+
+impl TapeReader {
+    pub fn new(base: u8) -> Self {
+        TapeReader {
+            base,
+            path: None,
+            tape: Vec::new(),
+            position: 0,
+            end: 0,
+            skip_leader: true,
+            data: 0x00,
+            ready: false,
+        }
+    }
+
+    pub fn mount(&mut self, path: &Path) -> Result<(), String> {
+        self.tape = std::fs::read(path).map_err(|e| e.to_string())?;
+        self.path = Some(path.to_path_buf());
+        self.rewind();
+        Ok(())
+    }
+
+    pub fn unmount(&mut self) {
+        self.path = None;
+        self.tape.clear();
+        self.rewind();
+    }
+
+    pub fn is_mounted(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Whether leader and trailer are hidden (on by default)
+    pub fn set_skip_leader(&mut self, skip: bool) {
+        self.skip_leader = skip;
+        self.rewind();
+    }
+
+    /// Back to the start of the tape (past the leader if skipping)
+    pub fn rewind(&mut self) {
+        self.ready = false;
+        self.data = 0x00;
+        if self.skip_leader {
+            self.position = self.tape.iter().position(|&b| b != 0).unwrap_or(self.tape.len());
+            self.end = self.tape.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+        } else {
+            self.position = 0;
+            self.end = self.tape.len();
+        }
+    }
+
+    /// Frames left to read
+    pub fn remaining(&self) -> usize {
+        self.end.saturating_sub(self.position)
+    }
+
+    fn advance(&mut self) {
+        if self.position < self.end {
+            self.data = self.tape[self.position];
+            self.position += 1;
+            self.ready = true;
+        }
+    }
+
+    fn status(&self) -> u8 {
+        let mut status = 0;
+        if self.ready {
+            status |= STATUS_READY;
+        }
+        if self.is_mounted() {
+            status |= STATUS_MOUNTED;
+        }
+        if self.remaining() == 0 {
+            status |= STATUS_END;
+        }
+        status
+    }
+
+    /// :reader mount FILE | unmount | rewind | leader on|off | (status)
+    pub fn host_command(&mut self, args: &[&str]) -> Result<String, String> {
+        match args {
+            ["mount", path] => {
+                self.mount(Path::new(path))?;
+                Ok(format!("Reader: {} ({} frames)", path, self.remaining()))
+            }
+            ["unmount"] => {
+                self.unmount();
+                Ok("Reader empty".to_string())
+            }
+            ["rewind"] => {
+                self.rewind();
+                Ok("Reader rewound".to_string())
+            }
+            ["leader", setting @ ("on" | "off")] => {
+                self.set_skip_leader(*setting == "on");
+                Ok(format!("Reader leader skipping {}", setting))
+            }
+            [] => Ok(match &self.path {
+                Some(path) => format!("Reader: {}, {} frames left", path.display(), self.remaining()),
+                None => "Reader empty".to_string(),
+            }),
+            _ => Err("usage: :reader [mount FILE | unmount | rewind | leader on|off]".to_string()),
+        }
+    }
+}
+
+impl IoDevice for TapeReader {
+    fn read(&mut self, port: u8) -> u8 {
+        match port.wrapping_sub(self.base) {
+            0 => self.status(),
+            1 => {
+                self.ready = false;
+                self.data
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) {
+        if port.wrapping_sub(self.base) == 0 {
+            match value {
+                CMD_ADVANCE => self.advance(),
+                CMD_REWIND => self.rewind(),
+                _ => {}
+            }
+        }
+    }
+}
+
+pub struct TapePunch {
+    base: u8,
+    path: Option<PathBuf>,
+    file: Option<File>,
+    punched: usize,
+}
+
+impl TapePunch {
+    pub fn new(base: u8) -> Self {
+        TapePunch { base, path: None, file: None, punched: 0 }
+    }
+
+    /// Load tape: frames are appended to `path`, which is created if needed
+    pub fn mount(&mut self, path: &Path) -> Result<(), String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| e.to_string())?;
+        self.file = Some(file);
+        self.path = Some(path.to_path_buf());
+        self.punched = 0;
+        Ok(())
+    }
+
+    pub fn unmount(&mut self) {
+        if let Some(ref mut f) = self.file {
+            let _ = f.flush();
+        }
+        self.file = None;
+        self.path = None;
+    }
+
+    pub fn is_mounted(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// Frames punched since mounting
+    pub fn punched(&self) -> usize {
+        self.punched
+    }
+
+    fn punch(&mut self, frames: &[u8]) {
+        if let Some(ref mut f) = self.file {
+            if f.write_all(frames).is_ok() {
+                self.punched += frames.len();
+            }
+        }
+    }
+
+    /// Punch a blank leader (or trailer)
+    pub fn leader(&mut self) {
+        self.punch(&[0x00; LEADER_FRAMES]);
+    }
+
+    /// :punch mount FILE | unmount | leader | (status)
+    pub fn host_command(&mut self, args: &[&str]) -> Result<String, String> {
+        match args {
+            ["mount", path] => {
+                self.mount(Path::new(path))?;
+                Ok(format!("Punch: {}", path))
+            }
+            ["unmount"] => {
+                let punched = self.punched;
+                self.unmount();
+                Ok(format!("Punch empty ({} frames punched)", punched))
+            }
+            ["leader"] => {
+                if !self.is_mounted() {
+                    return Err("no tape in punch".to_string());
+                }
+                self.leader();
+                Ok(format!("Punched {} frames of leader", LEADER_FRAMES))
+            }
+            [] => Ok(match &self.path {
+                Some(path) => format!("Punch: {}, {} frames punched", path.display(), self.punched),
+                None => "Punch empty".to_string(),
+            }),
+            _ => Err("usage: :punch [mount FILE | unmount | leader]".to_string()),
+        }
+    }
+}
+
+impl IoDevice for TapePunch {
+    fn read(&mut self, port: u8) -> u8 {
+        match port.wrapping_sub(self.base) {
+            0 if self.is_mounted() => STATUS_READY | STATUS_MOUNTED,
+            0 => 0x00,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) {
+        match port.wrapping_sub(self.base) {
+            0 if value == CMD_LEADER => self.leader(),
+            1 => self.punch(&[value]),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tape(dir: &tempfile::TempDir, name: &str, frames: &[u8]) -> PathBuf {
+        let path = dir.path().join(name);
+        std::fs::write(&path, frames).unwrap();
+        path
+    }
+
+    /// Advance and read until END, like a loader would
+    fn read_all(reader: &mut TapeReader) -> Vec<u8> {
+        let mut out = Vec::new();
+        while reader.read(READER_BASE) & STATUS_END == 0 {
+            reader.write(READER_BASE, CMD_ADVANCE);
+            assert_eq!(reader.read(READER_BASE) & STATUS_READY, STATUS_READY);
+            out.push(reader.read(READER_BASE + 1));
+        }
+        out
+    }
+
+    #[test]
+    fn test_reader_skips_leader_and_trailer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = tape(&dir, "BASIC.TAP", &[0, 0, 0, 0xAE, 0x00, 0x3C, 0, 0]);
+        let mut reader = TapeReader::new(READER_BASE);
+        assert_eq!(reader.read(READER_BASE), STATUS_END);
+
+        reader.mount(&path).unwrap();
+        assert_eq!(reader.read(READER_BASE), STATUS_MOUNTED);
+        assert_eq!(reader.remaining(), 3);
+        assert_eq!(read_all(&mut reader), vec![0xAE, 0x00, 0x3C]);
+        assert_eq!(reader.read(READER_BASE), STATUS_MOUNTED | STATUS_END);
+
+        reader.set_skip_leader(false);
+        assert_eq!(read_all(&mut reader).len(), 8);
+    }
+
+    #[test]
+    fn test_reader_ready_and_rewind() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = tape(&dir, "T.TAP", b"AB");
+        let mut reader = TapeReader::new(READER_BASE);
+        reader.mount(&path).unwrap();
+
+        reader.write(READER_BASE, CMD_ADVANCE);
+        assert_eq!(reader.read(READER_BASE + 1), b'A');
+        assert_eq!(reader.read(READER_BASE) & STATUS_READY, 0);
+        reader.write(READER_BASE, CMD_REWIND);
+        reader.write(READER_BASE, CMD_ADVANCE);
+        assert_eq!(reader.read(READER_BASE + 1), b'A');
+
+        reader.write(READER_BASE, CMD_ADVANCE);
+        reader.write(READER_BASE, CMD_ADVANCE);     // Past the end: nothing new
+        assert_eq!(reader.read(READER_BASE + 1), b'B');
+        reader.unmount();
+        assert_eq!(reader.read(READER_BASE), STATUS_END);
+    }
+
+    #[test]
+    fn test_punch_appends() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = tape(&dir, "OUT.TAP", b"X");
+        let mut punch = TapePunch::new(PUNCH_BASE);
+        assert_eq!(punch.read(PUNCH_BASE), 0x00);
+        punch.write(PUNCH_BASE + 1, b'!');          // No tape: dropped
+
+        punch.mount(&path).unwrap();
+        assert_eq!(punch.read(PUNCH_BASE), STATUS_READY | STATUS_MOUNTED);
+        punch.write(PUNCH_BASE, CMD_LEADER);
+        punch.write(PUNCH_BASE + 1, b'Y');
+        punch.unmount();
+
+        let frames = std::fs::read(&path).unwrap();
+        assert_eq!(frames.len(), 2 + LEADER_FRAMES);
+        assert_eq!(frames[0], b'X');
+        assert_eq!(frames[frames.len() - 1], b'Y');
+    }
+
+    #[test]
+    fn test_punch_then_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("LOOP.TAP");
+        let mut punch = TapePunch::new(PUNCH_BASE);
+        punch.mount(&path).unwrap();
+        punch.leader();
+        for &b in b"HELLO" {
+            punch.write(PUNCH_BASE + 1, b);
+        }
+        punch.leader();
+        punch.unmount();
+
+        let mut reader = TapeReader::new(READER_BASE);
+        reader.mount(&path).unwrap();
+        assert_eq!(read_all(&mut reader), b"HELLO");
+    }
+
+    #[test]
+    fn test_host_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = tape(&dir, "H.TAP", &[0, 1, 2, 0]);
+        let path = path.to_str().unwrap();
+        let mut reader = TapeReader::new(READER_BASE);
+        let mut punch = TapePunch::new(PUNCH_BASE);
+
+        assert!(reader.host_command(&["mount", path]).unwrap().ends_with("(2 frames)"));
+        reader.write(READER_BASE, CMD_ADVANCE);
+        assert_eq!(reader.host_command(&[]).unwrap(), format!("Reader: {}, 1 frames left", path));
+        reader.host_command(&["rewind"]).unwrap();
+        assert_eq!(reader.remaining(), 2);
+        reader.host_command(&["leader", "off"]).unwrap();
+        assert_eq!(reader.remaining(), 4);
+        assert!(reader.host_command(&["eject"]).is_err());
+        assert!(reader.host_command(&["mount", "/no/such/tape"]).is_err());
+
+        assert!(punch.host_command(&["leader"]).is_err());
+        let out = dir.path().join("P.TAP");
+        punch.host_command(&["mount", out.to_str().unwrap()]).unwrap();
+        punch.host_command(&["leader"]).unwrap();
+        assert_eq!(punch.host_command(&["unmount"]).unwrap(), "Punch empty (100 frames punched)");
+    }
+}
@@ -0,0 +1,164 @@
+// paper_tape_mount.rs - Mount service for the paper tape reader and punch
+//
+// Port base+0: Filename char (write only)
+// Port base+1: Control (write only)
+// Port base+2: Status (read only)
+//
+// Same protocol as the storage mount service (0x0D-0x0F): send the name,
+// then a control command. Files live in the emulator's storage directory.
+//
+// Control commands:
+//   0x01: Mount reader (load tape from file)
+//   0x02: Unmount reader
+//   0x03: Rewind reader
+//   0x04: Mount punch (append to file, creating it)
+//   0x05: Unmount punch
+//
+// Status codes:
+//   0x00: OK
+//   0x01: File not found (or error opening)
+//   0x02: Invalid filename
+
+use crate::io::IoDevice;
+use super::paper_tape::{TapePunch, TapeReader};
+use super::storage_mount::take_path;
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+pub const DEFAULT_BASE: u8 = 0xB4;
+
+pub struct PaperTapeMount {
+    base: u8,
+    reader: Rc<RefCell<TapeReader>>,
+    punch: Rc<RefCell<TapePunch>>,
+    base_path: PathBuf,
+    filename_buffer: Vec<u8>,
+    status: u8,
+}
+
+impl PaperTapeMount {
+    pub fn new(
+        base: u8,
+        reader: Rc<RefCell<TapeReader>>,
+        punch: Rc<RefCell<TapePunch>>,
+        base_path: PathBuf,
+    ) -> Self {
+        PaperTapeMount {
+            base,
+            reader,
+            punch,
+            base_path,
+            filename_buffer: Vec::with_capacity(13),
+            status: 0x00,
+        }
+    }
+
+    /// Full path of the accumulated filename, None (status set) if invalid
+    fn take_path(&mut self) -> Option<PathBuf> {
+        take_path(&mut self.filename_buffer, &self.base_path, &mut self.status)
+    }
+
+    fn command(&mut self, value: u8) {
+        let result = match value {
+            0x01 | 0x04 => {
+                let Some(path) = self.take_path() else { return };
+                if value == 0x01 {
+                    self.reader.borrow_mut().mount(&path)
+                } else {
+                    self.punch.borrow_mut().mount(&path)
+                }
+            }
+            0x02 => {
+                self.reader.borrow_mut().unmount();
+                Ok(())
+            }
+            0x03 => {
+                self.reader.borrow_mut().rewind();
+                Ok(())
+            }
+            0x05 => {
+                self.punch.borrow_mut().unmount();
+                Ok(())
+            }
+            _ => return,
+        };
+        self.status = if result.is_ok() { 0x00 } else { 0x01 };
+    }
+}
+
+impl IoDevice for PaperTapeMount {
+    fn read(&mut self, port: u8) -> u8 {
+        match port.wrapping_sub(self.base) {
+            2 => self.status,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) {
+        match port.wrapping_sub(self.base) {
+            0 if value != 0 && self.filename_buffer.len() < 12 => self.filename_buffer.push(value),
+            1 => self.command(value),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::devices::paper_tape::{PUNCH_BASE, READER_BASE};
+
+    fn setup() -> (tempfile::TempDir, Rc<RefCell<TapeReader>>, Rc<RefCell<TapePunch>>, PaperTapeMount) {
+        let dir = tempfile::tempdir().unwrap();
+        let reader = Rc::new(RefCell::new(TapeReader::new(READER_BASE)));
+        let punch = Rc::new(RefCell::new(TapePunch::new(PUNCH_BASE)));
+        let mount = PaperTapeMount::new(DEFAULT_BASE, reader.clone(), punch.clone(), dir.path().to_path_buf());
+        (dir, reader, punch, mount)
+    }
+
+    fn send_name(mount: &mut PaperTapeMount, name: &[u8]) {
+        for &c in name {
+            mount.write(DEFAULT_BASE, c);
+        }
+    }
+
+    #[test]
+    fn test_mount_reader_and_punch() {
+        let (dir, reader, punch, mut mount) = setup();
+        std::fs::write(dir.path().join("BASIC.TAP"), b"\0\0RUN").unwrap();
+
+        send_name(&mut mount, b"BASIC.TAP");
+        mount.write(DEFAULT_BASE + 1, 0x01);
+        assert_eq!(mount.read(DEFAULT_BASE + 2), 0x00);
+        assert_eq!(reader.borrow().remaining(), 3);
+
+        send_name(&mut mount, b"OUT.TAP");
+        mount.write(DEFAULT_BASE + 1, 0x04);
+        assert_eq!(mount.read(DEFAULT_BASE + 2), 0x00);
+        assert!(punch.borrow().is_mounted());
+        assert!(dir.path().join("OUT.TAP").exists());
+
+        mount.write(DEFAULT_BASE + 1, 0x02);
+        mount.write(DEFAULT_BASE + 1, 0x05);
+        assert!(!reader.borrow().is_mounted());
+        assert!(!punch.borrow().is_mounted());
+    }
+
+    #[test]
+    fn test_missing_and_invalid_names() {
+        let (_dir, reader, _punch, mut mount) = setup();
+
+        send_name(&mut mount, b"NONE.TAP");
+        mount.write(DEFAULT_BASE + 1, 0x01);
+        assert_eq!(mount.read(DEFAULT_BASE + 2), 0x01);     // Reader won't create
+        assert!(!reader.borrow().is_mounted());
+
+        send_name(&mut mount, b"../x");
+        mount.write(DEFAULT_BASE + 1, 0x04);
+        assert_eq!(mount.read(DEFAULT_BASE + 2), 0x02);
+
+        mount.write(DEFAULT_BASE + 1, 0x04);                // Buffer was cleared
+        assert_eq!(mount.read(DEFAULT_BASE + 2), 0x02);
+    }
+}
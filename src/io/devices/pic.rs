@@ -0,0 +1,526 @@
+// pic.rs - Intel 8259A Programmable Interrupt Controller (8080 mode)
+//
+// Port base+0: ICW1, OCW2, OCW3 (write) / IRR, ISR or poll word (read)
+// Port base+1: ICW2-ICW4, OCW1 (write) / IMR (read)
+//
+// Initialization (ICW1 starts it, then base+1 takes the rest):
+//   ICW1: Bit 0 IC4 (ICW4 follows), bit 1 SNGL (no ICW3), bit 2 ADI
+//         (call interval 4, else 8), bit 3 LTIM (level triggered),
+//         bit 4 = 1, bits 5-7 A5-A7 of the vector address
+//   ICW2: A8-A15 of the vector address
+//   ICW3: Cascade wiring (stored, cascading not modeled)
+//   ICW4: Bit 1 AEOI (automatic end of interrupt); 8086 mode not modeled
+// ICW1 clears IMR, ISR and IRR, selects IRR for reading and makes IR7
+// the lowest priority.
+//
+// OCW1 (base+1): Interrupt mask, bit n masks IRn
+// OCW2 (base+0, bits 3-4 = 00), bits 5-7 R/SL/EOI, bits 0-2 level:
+//   001 non-specific EOI      011 specific EOI
+//   101 rotate on EOI         111 rotate on specific EOI
+//   100 rotate in AEOI on     000 rotate in AEOI off
+//   110 set priority (level becomes lowest)
+// OCW3 (base+0, bits 3-4 = 01):
+//   Bit 0: RIS - read ISR (else IRR), when bit 1 RR is set
+//   Bit 2: P - poll; the next base+0 read acknowledges the highest request
+//          and returns bit 7 = interrupt, bits 0-2 = level
+//   Special mask mode is not modeled.
+//
+// On the 8080 the INTA cycle takes a CALL from the 8259. Here the CPU
+// calls acknowledge(), which returns the CALL target:
+//   interval 4: A15-A5 from ICW2/ICW1, plus level * 4
+//   interval 8: A15-A6 from ICW2/ICW1, plus level * 8
+// With no request left at acknowledge time, IR7's address is returned
+// (spurious interrupt) and ISR is untouched.
+//
+// IR inputs: connect() wires a device's interrupt line (any
+// InterruptSource) to IRn; set_irq() drives a line from the host. Edge
+// triggered inputs latch IRR on a rising edge; level triggered inputs
+// follow the line. When a level goes into service (INTA or poll) the
+// device on it is acknowledged too, which clears requests it latches,
+// like the timer's; the address it returns is not used.
+//
+// init_rst() programs the PIC as a boot ROM might: IRn calls n * 8, the
+// address of RST n, with automatic EOI.
+
+use crate::io::{InterruptSource, IoDevice};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const ICW1: u8 = 0x10;
+const ICW1_IC4: u8 = 0x01;
+const ICW1_SNGL: u8 = 0x02;
+const ICW1_ADI: u8 = 0x04;
+const ICW1_LTIM: u8 = 0x08;
+const OCW3: u8 = 0x08;
+const ICW4_AEOI: u8 = 0x02;
+
+/// What base+1 expects next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InitState {
+    Uninitialized,
+    Icw2,
+    Icw3,
+    Icw4,
+    Ready,
+}
+
+pub struct Pic8259 {
+    base: u8,
+    state: InitState,
+    icw1: u8,
+    icw2: u8,
+    icw3: u8,
+    icw4: u8,
+    imr: u8,
+    irr: u8,
+    isr: u8,
+    lowest: u8,
+    read_isr: bool,
+    poll: bool,
+    rotate_in_aeoi: bool,
+    host_lines: u8,
+    last_lines: u8,
+    inputs: [Option<Rc<RefCell<dyn InterruptSource>>>; 8],
+}
+
+impl Pic8259 {
+    /// PIC on `base`/`base + 1`. Requests nothing until initialized.
+    pub fn new(base: u8) -> Self {
+        Pic8259 {
+            base,
+            state: InitState::Uninitialized,
+            icw1: 0,
+            icw2: 0,
+            icw3: 0,
+            icw4: 0,
+            imr: 0,
+            irr: 0,
+            isr: 0,
+            lowest: 7,
+            read_isr: false,
+            poll: false,
+            rotate_in_aeoi: false,
+            host_lines: 0,
+            last_lines: 0,
+            inputs: Default::default(),
+        }
+    }
+
+    /// Wire a device's interrupt line to IRn (0-7)
+    pub fn connect(&mut self, irq: u8, device: Rc<RefCell<dyn InterruptSource>>) {
+        self.inputs[irq as usize & 0x07] = Some(device);
+    }
+
+    /// Program for RST-compatible vectors: single, edge triggered,
+    /// interval 8 from 0000, auto-EOI. Software can initialize it again.
+    pub fn init_rst(&mut self) {
+        self.write_command(ICW1 | ICW1_SNGL | ICW1_IC4);
+        self.write_data(0x00);
+        self.write_data(ICW4_AEOI);
+    }
+
+    /// Drive IRn from the host
+    pub fn set_irq(&mut self, irq: u8, level: bool) {
+        let bit = 1 << (irq & 0x07);
+        if level {
+            self.host_lines |= bit;
+        } else {
+            self.host_lines &= !bit;
+        }
+    }
+
+    pub fn irr(&mut self) -> u8 {
+        self.sample();
+        self.irr
+    }
+
+    pub fn isr(&self) -> u8 {
+        self.isr
+    }
+
+    pub fn imr(&self) -> u8 {
+        self.imr
+    }
+
+    /// Vector address for an IR level
+    pub fn vector(&self, level: u8) -> u16 {
+        let low = if self.icw1 & ICW1_ADI != 0 {
+            (self.icw1 & 0xE0) as u16 | (level as u16 * 4)
+        } else {
+            (self.icw1 & 0xC0) as u16 | (level as u16 * 8)
+        };
+        (self.icw2 as u16) << 8 | low
+    }
+
+    /// Read the IR lines into IRR
+    fn sample(&mut self) {
+        let mut lines = self.host_lines;
+        for (irq, input) in self.inputs.iter().enumerate() {
+            if let Some(device) = input {
+                if device.borrow_mut().interrupt_requested() {
+                    lines |= 1 << irq;
+                }
+            }
+        }
+        if self.icw1 & ICW1_LTIM != 0 {
+            self.irr = lines;
+        } else {
+            self.irr |= lines & !self.last_lines;
+        }
+        self.last_lines = lines;
+    }
+
+    /// Levels from highest to lowest priority
+    fn priority_order(&self) -> impl Iterator<Item = u8> {
+        let first = (self.lowest + 1) & 0x07;
+        (0..8).map(move |i| (first + i) & 0x07)
+    }
+
+    /// Highest unmasked request not blocked by a level in service
+    fn highest_request(&self) -> Option<u8> {
+        for level in self.priority_order() {
+            let bit = 1 << level;
+            if self.isr & bit != 0 {
+                return None;
+            }
+            if self.irr & !self.imr & bit != 0 {
+                return Some(level);
+            }
+        }
+        None
+    }
+
+    /// Highest level in service
+    fn highest_in_service(&self) -> Option<u8> {
+        self.priority_order().find(|level| self.isr & (1 << level) != 0)
+    }
+
+    /// INTA: move the highest request into service, returning its level
+    fn take_request(&mut self) -> Option<u8> {
+        self.sample();
+        let level = self.highest_request()?;
+        self.irr &= !(1 << level);
+        if let Some(device) = &self.inputs[level as usize] {
+            device.borrow_mut().acknowledge();
+        }
+        if self.icw4 & ICW4_AEOI != 0 {
+            if self.rotate_in_aeoi {
+                self.lowest = level;
+            }
+        } else {
+            self.isr |= 1 << level;
+        }
+        Some(level)
+    }
+
+    fn write_ocw2(&mut self, value: u8) {
+        let level = value & 0x07;
+        match value >> 5 {
+            0b001 | 0b101 => {
+                if let Some(served) = self.highest_in_service() {
+                    self.isr &= !(1 << served);
+                    if value & 0x80 != 0 {
+                        self.lowest = served;
+                    }
+                }
+            }
+            0b011 => self.isr &= !(1 << level),
+            0b111 => {
+                self.isr &= !(1 << level);
+                self.lowest = level;
+            }
+            0b100 => self.rotate_in_aeoi = true,
+            0b000 => self.rotate_in_aeoi = false,
+            0b110 => self.lowest = level,
+            _ => {}
+        }
+    }
+
+    fn write_ocw3(&mut self, value: u8) {
+        if value & 0x02 != 0 {
+            self.read_isr = value & 0x01 != 0;
+        }
+        self.poll = value & 0x04 != 0;
+    }
+
+    fn write_command(&mut self, value: u8) {
+        if value & ICW1 != 0 {
+            self.icw1 = value;
+            self.icw4 = 0;
+            self.imr = 0;
+            self.irr = 0;
+            self.isr = 0;
+            self.lowest = 7;
+            self.read_isr = false;
+            self.poll = false;
+            self.rotate_in_aeoi = false;
+            self.last_lines = 0;
+            self.state = InitState::Icw2;
+        } else if value & OCW3 != 0 {
+            self.write_ocw3(value);
+        } else {
+            self.write_ocw2(value);
+        }
+    }
+
+    fn write_data(&mut self, value: u8) {
+        self.state = match self.state {
+            InitState::Icw2 => {
+                self.icw2 = value;
+                if self.icw1 & ICW1_SNGL == 0 {
+                    InitState::Icw3
+                } else if self.icw1 & ICW1_IC4 != 0 {
+                    InitState::Icw4
+                } else {
+                    InitState::Ready
+                }
+            }
+            InitState::Icw3 => {
+                self.icw3 = value;
+                if self.icw1 & ICW1_IC4 != 0 { InitState::Icw4 } else { InitState::Ready }
+            }
+            InitState::Icw4 => {
+                self.icw4 = value;
+                InitState::Ready
+            }
+            InitState::Uninitialized | InitState::Ready => {
+                self.imr = value;
+                self.state
+            }
+        };
+    }
+
+    /// Cascade wiring from ICW3
+    pub fn icw3(&self) -> u8 {
+        self.icw3
+    }
+}
+
+impl IoDevice for Pic8259 {
+    fn read(&mut self, port: u8) -> u8 {
+        match port.wrapping_sub(self.base) {
+            0 if self.poll => {
+                self.poll = false;
+                match self.take_request() {
+                    Some(level) => 0x80 | level,
+                    None => 0x00,
+                }
+            }
+            0 if self.read_isr => self.isr,
+            0 => self.irr(),
+            1 => self.imr,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) {
+        match port.wrapping_sub(self.base) {
+            0 => self.write_command(value),
+            1 => self.write_data(value),
+            _ => {}
+        }
+    }
+}
+
+impl InterruptSource for Pic8259 {
+    /// INT output
+    fn interrupt_requested(&mut self) -> bool {
+        if self.state != InitState::Ready {
+            return false;
+        }
+        self.sample();
+        self.highest_request().is_some()
+    }
+
+    fn acknowledge(&mut self) -> u16 {
+        let level = self.take_request().unwrap_or(7);
+        self.vector(level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Intel8080;
+
+    const BASE: u8 = 0x20;
+
+    /// Single, edge triggered, interval 4, vectors from 0x1000, AEOI as given
+    fn pic(icw4: u8) -> Pic8259 {
+        let mut pic = Pic8259::new(BASE);
+        pic.write(BASE, 0x17);              // ICW1: IC4, SNGL, ADI
+        pic.write(BASE + 1, 0x10);          // ICW2
+        pic.write(BASE + 1, icw4);          // ICW4
+        pic
+    }
+
+    /// Line that stays up until cleared
+    struct Line(bool);
+
+    impl InterruptSource for Line {
+        fn interrupt_requested(&mut self) -> bool {
+            self.0
+        }
+
+        fn acknowledge(&mut self) -> u16 {
+            0
+        }
+    }
+
+    #[test]
+    fn test_uninitialized_requests_nothing() {
+        let mut pic = Pic8259::new(BASE);
+        pic.set_irq(0, true);
+        assert!(!pic.interrupt_requested());
+    }
+
+    #[test]
+    fn test_vectors_interval_4_and_8() {
+        let mut pic = pic(0x00);
+        pic.set_irq(3, true);
+        assert!(pic.interrupt_requested());
+        assert_eq!(pic.acknowledge(), 0x100C);
+        assert_eq!(pic.isr(), 0x08);
+
+        let mut pic = Pic8259::new(BASE);
+        pic.write(BASE, 0xD2);              // ICW1: SNGL, interval 8, A7-A6 = 11
+        pic.write(BASE + 1, 0x20);
+        assert_eq!(pic.vector(0), 0x20C0);
+        assert_eq!(pic.vector(5), 0x20E8);
+    }
+
+    #[test]
+    fn test_priority_mask_and_eoi() {
+        let mut pic = pic(0x00);
+        pic.set_irq(5, true);
+        pic.set_irq(2, true);
+        assert_eq!(pic.irr(), 0x24);
+
+        assert_eq!(pic.acknowledge(), pic.vector(2));
+        assert!(!pic.interrupt_requested());    // IR2 in service blocks IR5
+        pic.write(BASE, 0x20);                  // Non-specific EOI
+        assert_eq!(pic.isr(), 0x00);
+
+        pic.write(BASE + 1, 0x20);              // Mask IR5
+        assert_eq!(pic.read(BASE + 1), 0x20);
+        assert!(!pic.interrupt_requested());
+        pic.write(BASE + 1, 0x00);
+        assert_eq!(pic.acknowledge(), pic.vector(5));
+        pic.write(BASE, 0x65);                  // Specific EOI IR5
+        assert_eq!(pic.isr(), 0x00);
+    }
+
+    #[test]
+    fn test_edge_and_level_trigger() {
+        // Edge: a line held high requests once
+        let mut pic = pic(0x02);                // AEOI
+        pic.set_irq(1, true);
+        assert!(pic.interrupt_requested());
+        pic.acknowledge();
+        assert!(!pic.interrupt_requested());
+
+        // Level: requests while the line is high
+        let mut pic = Pic8259::new(BASE);
+        pic.write(BASE, 0x1F);                  // LTIM
+        pic.write(BASE + 1, 0x10);
+        pic.write(BASE + 1, 0x02);
+        let line = Rc::new(RefCell::new(Line(true)));
+        pic.connect(1, line.clone());
+        pic.acknowledge();
+        assert!(pic.interrupt_requested());
+        line.borrow_mut().0 = false;
+        assert!(!pic.interrupt_requested());
+    }
+
+    #[test]
+    fn test_rotation_and_set_priority() {
+        let mut pic = pic(0x00);
+        pic.write(BASE, 0xC4);                  // IR4 lowest, IR5 highest
+        pic.set_irq(0, true);
+        pic.set_irq(6, true);
+        assert_eq!(pic.acknowledge(), pic.vector(6));
+        pic.write(BASE, 0xA0);                  // Rotate on EOI: IR6 lowest
+        assert_eq!(pic.acknowledge(), pic.vector(0));
+        pic.write(BASE, 0xA0);
+
+        pic.set_irq(6, false);
+        pic.irr();
+        pic.set_irq(6, true);                   // New edge on IR6
+        pic.set_irq(7, true);
+        assert_eq!(pic.acknowledge(), pic.vector(6));
+    }
+
+    #[test]
+    fn test_read_isr_and_poll() {
+        let mut pic = pic(0x00);
+        pic.set_irq(4, true);
+        assert_eq!(pic.read(BASE), 0x10);       // IRR
+        pic.write(BASE, 0x0C);                  // OCW3: poll
+        assert_eq!(pic.read(BASE), 0x84);
+        pic.write(BASE, 0x0B);                  // OCW3: read ISR
+        assert_eq!(pic.read(BASE), 0x10);
+        assert_eq!(pic.acknowledge(), pic.vector(7));   // Spurious
+    }
+
+    #[test]
+    fn test_cpu_calls_pic_vector() {
+        // 0000: EI / JMP 0001   100C: MVI A,42H / HLT
+        let mut cpu = Intel8080::new();
+        cpu.load_program(&[0x3E, 0x42, 0x76], 0x100C);
+        cpu.load_program(&[0xFB, 0xC3, 0x01, 0x00], 0x0000);
+        cpu.sp = 0x8000;
+
+        let pic = Rc::new(RefCell::new(pic(0x00)));
+        let line = Rc::new(RefCell::new(Line(false)));
+        pic.borrow_mut().connect(3, line.clone());
+        cpu.io_bus_mut().map_port(BASE, pic.clone());
+        cpu.io_bus_mut().map_port(BASE + 1, pic.clone());
+        cpu.attach_interrupt_source(pic.clone());
+
+        for _ in 0..10 {
+            cpu.execute_one();
+        }
+        assert_eq!(cpu.pc, 0x0001);
+
+        line.borrow_mut().0 = true;
+        cpu.run();
+        assert_eq!(cpu.a, 0x42);
+        assert_eq!(pic.borrow().isr(), 0x08);
+    }
+
+    #[test]
+    fn test_timer_through_pic() {
+        // 0000: Timer reload 0010, enable / EI / JMP 000C   0038: MVI A,42H / HLT
+        let mut cpu = Intel8080::new();
+        cpu.load_program(&[0x3E, 0x42, 0x76], 0x0038);
+        cpu.load_program(&[
+            0x3E, 0x10, 0xD3, 0x30, 0xAF, 0xD3, 0x31, 0x3E, 0x01, 0xD3, 0x32,
+            0xFB, 0xC3, 0x0C, 0x00,
+        ], 0x0000);
+        cpu.sp = 0x8000;
+
+        // Wired as main.rs does it
+        let mut pic = Pic8259::new(BASE);
+        pic.init_rst();
+        let timer: Rc<RefCell<dyn InterruptSource>> = cpu.timer.clone();
+        cpu.detach_interrupt_source(&timer);
+        pic.connect(7, timer);
+        let pic = Rc::new(RefCell::new(pic));
+        cpu.io_bus_mut().map_port(BASE, pic.clone());
+        cpu.io_bus_mut().map_port(BASE + 1, pic.clone());
+        cpu.attach_interrupt_source(pic.clone());
+
+        // Masked at the PIC, the timer cannot interrupt
+        pic.borrow_mut().write(BASE + 1, 0x80);
+        for _ in 0..50 {
+            cpu.execute_one();
+        }
+        assert!(cpu.pc >= 0x000C && cpu.pc <= 0x000E);
+
+        pic.borrow_mut().write(BASE + 1, 0x00);
+        cpu.run();
+        assert_eq!(cpu.a, 0x42);
+        assert!(!cpu.timer.borrow().interrupt_pending);     // Acknowledged through the PIC
+        assert_eq!(pic.borrow().isr(), 0x00);               // Auto-EOI
+    }
+}
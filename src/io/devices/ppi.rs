@@ -0,0 +1,394 @@
+// ppi.rs - Intel 8255 Programmable Peripheral Interface
+//
+// Port base+0: Port A
+// Port base+1: Port B
+// Port base+2: Port C
+// Port base+3: Control word (write only, reads 0xFF)
+//
+// Control word, bit 7 = 1 (mode set):
+//   Bits 5-6: Group A mode - 00 mode 0, 01 mode 1 (1x mode 2, run as mode 0)
+//   Bit 4:    Port A input
+//   Bit 3:    Port C upper (PC4-7) input
+//   Bit 2:    Group B mode - 0 mode 0, 1 mode 1
+//   Bit 1:    Port B input
+//   Bit 0:    Port C lower (PC0-3) input
+// A mode set clears every output latch and handshake flag.
+//
+// Control word, bit 7 = 0 (port C bit set/reset):
+//   Bits 1-3: Bit number
+//   Bit 0:    1 = set, 0 = reset
+// On a mode 1 handshake bit this sets INTE instead (PC4 A in, PC6 A out,
+// PC2 B).
+//
+// Mode 1 port C bits (read back by the CPU):
+//   Group A input:  PC3 INTR_A, PC4 INTE_A, PC5 IBF_A
+//   Group A output: PC3 INTR_A, PC6 INTE_A, PC7 OBF_A (active low)
+//   Group B input:  PC0 INTR_B, PC1 IBF_B, PC2 INTE_B
+//   Group B output: PC0 INTR_B, PC1 OBF_B (active low), PC2 INTE_B
+// The other port C bits stay plain I/O in the direction set by bits 0/3.
+//
+// The peripheral side is the host API: set_port_x() drives input pins,
+// port_x() reads what the chip drives, strobe_x() latches a byte (STB
+// pulse) and ack_x() takes an output byte (ACK pulse). INTR_A or INTR_B
+// interrupts the CPU through InterruptSource.
+
+use crate::io::{InterruptSource, IoDevice};
+
+const MODE_SET: u8 = 0x80;
+const A_INPUT: u8 = 0x10;
+const C_UPPER_INPUT: u8 = 0x08;
+const B_INPUT: u8 = 0x02;
+const C_LOWER_INPUT: u8 = 0x01;
+
+pub struct Ppi8255 {
+    base: u8,
+    control: u8,
+    latch_a: u8,
+    latch_b: u8,
+    latch_c: u8,
+    pins_a: u8,
+    pins_b: u8,
+    pins_c: u8,
+    strobed_a: u8,
+    strobed_b: u8,
+    ibf_a: bool,
+    ibf_b: bool,
+    obf_a: bool,
+    obf_b: bool,
+    inte_a: bool,
+    inte_b: bool,
+    vector: u16,
+}
+
+impl Ppi8255 {
+    /// PPI on `base` to `base + 3`, reset to mode 0 with every port input
+    pub fn new(base: u8) -> Self {
+        Ppi8255 {
+            base,
+            control: 0x9B,
+            latch_a: 0,
+            latch_b: 0,
+            latch_c: 0,
+            pins_a: 0xFF,
+            pins_b: 0xFF,
+            pins_c: 0xFF,
+            strobed_a: 0,
+            strobed_b: 0,
+            ibf_a: false,
+            ibf_b: false,
+            obf_a: false,
+            obf_b: false,
+            inte_a: false,
+            inte_b: false,
+            vector: 0x0038,
+        }
+    }
+
+    /// RST vector (0-7) used when INTR_A or INTR_B interrupts the CPU
+    pub fn set_interrupt_vector(&mut self, rst: u8) {
+        self.vector = (rst as u16 & 0x07) * 8;
+    }
+
+    pub fn control(&self) -> u8 {
+        self.control
+    }
+
+    fn a_mode1(&self) -> bool {
+        self.control & 0x60 == 0x20
+    }
+
+    fn b_mode1(&self) -> bool {
+        self.control & 0x04 != 0
+    }
+
+    fn a_input(&self) -> bool {
+        self.control & A_INPUT != 0
+    }
+
+    fn b_input(&self) -> bool {
+        self.control & B_INPUT != 0
+    }
+
+    /// Port C bits taken over by mode 1 handshaking
+    fn c_handshake_mask(&self) -> u8 {
+        let mut mask = 0;
+        if self.a_mode1() {
+            mask |= if self.a_input() { 0x38 } else { 0xC8 };
+        }
+        if self.b_mode1() {
+            mask |= 0x07;
+        }
+        mask
+    }
+
+    /// Port C bits read from the pins rather than the output latch
+    fn c_input_mask(&self) -> u8 {
+        let mut mask = 0;
+        if self.control & C_UPPER_INPUT != 0 {
+            mask |= 0xF0;
+        }
+        if self.control & C_LOWER_INPUT != 0 {
+            mask |= 0x0F;
+        }
+        mask & !self.c_handshake_mask()
+    }
+
+    /// INTR_A output pin
+    pub fn intr_a(&self) -> bool {
+        self.a_mode1() && self.inte_a && if self.a_input() { self.ibf_a } else { !self.obf_a }
+    }
+
+    /// INTR_B output pin
+    pub fn intr_b(&self) -> bool {
+        self.b_mode1() && self.inte_b && if self.b_input() { self.ibf_b } else { !self.obf_b }
+    }
+
+    /// Port C as the CPU reads it: I/O bits plus mode 1 status
+    fn read_c(&self) -> u8 {
+        let input = self.c_input_mask();
+        let mut value = (self.pins_c & input) | (self.latch_c & !input & !self.c_handshake_mask());
+        if self.a_mode1() {
+            value |= (self.intr_a() as u8) << 3;
+            if self.a_input() {
+                value |= (self.inte_a as u8) << 4 | (self.ibf_a as u8) << 5;
+            } else {
+                value |= (self.inte_a as u8) << 6 | (!self.obf_a as u8) << 7;
+            }
+        }
+        if self.b_mode1() {
+            let buffer = if self.b_input() { self.ibf_b } else { !self.obf_b };
+            value |= self.intr_b() as u8 | (buffer as u8) << 1 | (self.inte_b as u8) << 2;
+        }
+        value
+    }
+
+    fn set_mode(&mut self, value: u8) {
+        self.control = value;
+        self.latch_a = 0;
+        self.latch_b = 0;
+        self.latch_c = 0;
+        self.ibf_a = false;
+        self.ibf_b = false;
+        self.obf_a = false;
+        self.obf_b = false;
+        self.inte_a = false;
+        self.inte_b = false;
+    }
+
+    fn bit_set_reset(&mut self, value: u8) {
+        let bit = (value >> 1) & 0x07;
+        let set = value & 0x01 != 0;
+        let inte_a_bit = if self.a_input() { 4 } else { 6 };
+        if self.a_mode1() && bit == inte_a_bit {
+            self.inte_a = set;
+        } else if self.b_mode1() && bit == 2 {
+            self.inte_b = set;
+        } else if self.c_handshake_mask() & (1 << bit) == 0 {
+            if set {
+                self.latch_c |= 1 << bit;
+            } else {
+                self.latch_c &= !(1 << bit);
+            }
+        }
+    }
+
+    /// Drive the port A pins (seen by the CPU when port A is a mode 0 input)
+    pub fn set_port_a(&mut self, value: u8) {
+        self.pins_a = value;
+    }
+
+    /// Drive the port B pins
+    pub fn set_port_b(&mut self, value: u8) {
+        self.pins_b = value;
+    }
+
+    /// Drive the port C pins (only input bits are seen)
+    pub fn set_port_c(&mut self, value: u8) {
+        self.pins_c = value;
+    }
+
+    /// Port A pins: the output latch, or the driven pins when an input
+    pub fn port_a(&self) -> u8 {
+        if self.a_input() { self.pins_a } else { self.latch_a }
+    }
+
+    pub fn port_b(&self) -> u8 {
+        if self.b_input() { self.pins_b } else { self.latch_b }
+    }
+
+    /// Port C pins, handshake outputs included
+    pub fn port_c(&self) -> u8 {
+        self.read_c()
+    }
+
+    /// Mode 1 input: peripheral strobes a byte into port A
+    pub fn strobe_a(&mut self, value: u8) {
+        self.pins_a = value;
+        self.strobed_a = value;
+        self.ibf_a = true;
+    }
+
+    pub fn strobe_b(&mut self, value: u8) {
+        self.pins_b = value;
+        self.strobed_b = value;
+        self.ibf_b = true;
+    }
+
+    /// Mode 1 output: peripheral acknowledges the byte on port A
+    pub fn ack_a(&mut self) {
+        self.obf_a = false;
+    }
+
+    pub fn ack_b(&mut self) {
+        self.obf_b = false;
+    }
+}
+
+impl IoDevice for Ppi8255 {
+    fn read(&mut self, port: u8) -> u8 {
+        match port.wrapping_sub(self.base) {
+            0 if self.a_mode1() && self.a_input() => {
+                self.ibf_a = false;
+                self.strobed_a
+            }
+            0 => self.port_a(),
+            1 if self.b_mode1() && self.b_input() => {
+                self.ibf_b = false;
+                self.strobed_b
+            }
+            1 => self.port_b(),
+            2 => self.read_c(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) {
+        match port.wrapping_sub(self.base) {
+            0 => {
+                self.latch_a = value;
+                if self.a_mode1() && !self.a_input() {
+                    self.obf_a = true;
+                }
+            }
+            1 => {
+                self.latch_b = value;
+                if self.b_mode1() && !self.b_input() {
+                    self.obf_b = true;
+                }
+            }
+            2 => {
+                let keep = self.c_handshake_mask();
+                self.latch_c = (self.latch_c & keep) | (value & !keep);
+            }
+            3 if value & MODE_SET != 0 => self.set_mode(value),
+            3 => self.bit_set_reset(value),
+            _ => {}
+        }
+    }
+}
+
+impl InterruptSource for Ppi8255 {
+    fn interrupt_requested(&mut self) -> bool {
+        self.intr_a() || self.intr_b()
+    }
+
+    fn acknowledge(&mut self) -> u16 {
+        self.vector
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: u8 = 0x80;
+
+    #[test]
+    fn test_reset_is_all_inputs() {
+        let mut ppi = Ppi8255::new(BASE);
+        ppi.set_port_a(0x12);
+        ppi.set_port_b(0x34);
+        ppi.set_port_c(0x56);
+        assert_eq!(ppi.read(BASE), 0x12);
+        assert_eq!(ppi.read(BASE + 1), 0x34);
+        assert_eq!(ppi.read(BASE + 2), 0x56);
+        assert_eq!(ppi.read(BASE + 3), 0xFF);
+    }
+
+    #[test]
+    fn test_mode0_outputs_drive_pins() {
+        let mut ppi = Ppi8255::new(BASE);
+        ppi.write(BASE + 3, 0x90);          // A in, B and C out
+        ppi.set_port_a(0xA5);               // Switch bank
+        let switches = ppi.read(BASE);
+        ppi.write(BASE + 1, switches);      // LEDs
+        assert_eq!(ppi.port_b(), 0xA5);
+
+        ppi.write(BASE + 2, 0x3C);
+        assert_eq!(ppi.port_c(), 0x3C);
+        assert_eq!(ppi.read(BASE + 2), 0x3C);
+    }
+
+    #[test]
+    fn test_mode_set_clears_outputs() {
+        let mut ppi = Ppi8255::new(BASE);
+        ppi.write(BASE + 3, 0x80);
+        ppi.write(BASE, 0xFF);
+        ppi.write(BASE + 3, 0x80);
+        assert_eq!(ppi.port_a(), 0x00);
+    }
+
+    #[test]
+    fn test_port_c_bit_set_reset() {
+        let mut ppi = Ppi8255::new(BASE);
+        ppi.write(BASE + 3, 0x80);          // All outputs
+        ppi.write(BASE + 3, 0x0F);          // Set PC7
+        ppi.write(BASE + 3, 0x03);          // Set PC1
+        assert_eq!(ppi.port_c(), 0x82);
+        ppi.write(BASE + 3, 0x0E);          // Reset PC7
+        assert_eq!(ppi.port_c(), 0x02);
+    }
+
+    #[test]
+    fn test_mixed_port_c_directions() {
+        let mut ppi = Ppi8255::new(BASE);
+        ppi.write(BASE + 3, 0x88);          // PC upper in, PC lower out
+        ppi.write(BASE + 2, 0xFF);
+        ppi.set_port_c(0x50);
+        assert_eq!(ppi.read(BASE + 2), 0x5F);
+    }
+
+    #[test]
+    fn test_mode1_strobed_input() {
+        let mut ppi = Ppi8255::new(BASE);
+        ppi.write(BASE + 3, 0xB0);          // A mode 1 input
+        ppi.write(BASE + 3, 0x09);          // INTE_A (PC4)
+        assert_eq!(ppi.read(BASE + 2) & 0x38, 0x10);
+        assert!(!ppi.interrupt_requested());
+
+        ppi.strobe_a(0x42);
+        assert_eq!(ppi.read(BASE + 2) & 0x38, 0x38);  // IBF, INTE, INTR
+        assert!(ppi.interrupt_requested());
+        assert_eq!(ppi.read(BASE), 0x42);
+        assert_eq!(ppi.read(BASE + 2) & 0x38, 0x10);
+        assert!(!ppi.interrupt_requested());
+    }
+
+    #[test]
+    fn test_mode1_printer_handshake() {
+        // Centronics printer on port B, mode 1 output
+        let mut ppi = Ppi8255::new(BASE);
+        ppi.write(BASE + 3, 0x84);
+        ppi.write(BASE + 3, 0x05);          // INTE_B (PC2)
+        assert_eq!(ppi.read(BASE + 2) & 0x07, 0x07);  // OBF high, INTR: ready
+
+        ppi.write(BASE + 1, b'P');
+        assert_eq!(ppi.read(BASE + 2) & 0x07, 0x04);  // OBF low, no INTR
+        assert_eq!(ppi.port_b(), b'P');
+        assert!(!ppi.intr_b());
+
+        ppi.ack_b();
+        assert!(ppi.intr_b());
+        assert_eq!(ppi.acknowledge(), 0x0038);
+    }
+}
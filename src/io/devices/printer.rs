@@ -0,0 +1,424 @@
+// printer.rs - Line printer spooled to a host file
+//
+// Port base+0: Status (read) / Control (write)
+// Port base+1: Data (write) - print a character
+//
+// Status register:
+//   Bit 0: READY     - will take a character now
+//   Bit 1: BUSY      - still printing the last character
+//   Bit 2: PAPER_OUT - out of paper
+//   Bit 7: SELECT    - online (a spool file is attached)
+//
+// Control:
+//   0x01: Flush the current page to the spool file
+//
+// Characters collect in a page buffer that is written out on form feed
+// (0x0C), flush, unmount and emulator exit. With page splitting on, each
+// page goes to its own file (LISTING-001.TXT, LISTING-002.TXT, ...) and
+// the form feeds are dropped; otherwise pages follow each other in the
+// one file, form feeds included. CR/LF normalisation turns CR LF and bare
+// CR into a single LF and drops NUL/DEL padding.
+//
+// Fault simulation for testing print routines: a per-character busy time
+// (in CPU cycles), a stuck BUSY line and paper out. Characters written
+// when the printer isn't READY are lost, like on the real thing; dropped()
+// counts them.
+
+use crate::io::{Clocked, IoDevice};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const STATUS_READY: u8 = 0x01;
+pub const STATUS_BUSY: u8 = 0x02;
+pub const STATUS_PAPER_OUT: u8 = 0x04;
+pub const STATUS_SELECT: u8 = 0x80;
+
+pub const CMD_FLUSH: u8 = 0x01;
+
+pub const DEFAULT_BASE: u8 = 0xB8;
+
+const FORM_FEED: u8 = 0x0C;
+
+pub struct Printer {
+    base: u8,
+    path: Option<PathBuf>,
+    page: Vec<u8>,
+    pages: usize,
+    split_pages: bool,
+    normalize: bool,
+    pending_cr: bool,
+    busy_cycles: u64,
+    busy: u64,
+    stuck_busy: bool,
+    paper_out: bool,
+    dropped: usize,
+}
+
+impl Printer {
+    pub fn new(base: u8) -> Self {
+        Printer {
+            base,
+            path: None,
+            page: Vec::new(),
+            pages: 0,
+            split_pages: false,
+            normalize: false,
+            pending_cr: false,
+            busy_cycles: 0,
+            busy: 0,
+            stuck_busy: false,
+            paper_out: false,
+            dropped: 0,
+        }
+    }
+
+    /// Spool to `path`, replacing what was there
+    pub fn mount(&mut self, path: &Path) -> Result<(), String> {
+        self.unmount()?;
+        if !self.split_pages {
+            std::fs::write(path, b"").map_err(|e| e.to_string())?;
+        }
+        self.path = Some(path.to_path_buf());
+        self.pages = 0;
+        Ok(())
+    }
+
+    /// Flush and detach the spool file
+    pub fn unmount(&mut self) -> Result<(), String> {
+        let result = self.flush();
+        self.path = None;
+        result
+    }
+
+    pub fn is_mounted(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// One file per page instead of one spool file
+    pub fn set_split_pages(&mut self, split: bool) {
+        self.split_pages = split;
+    }
+
+    /// CR LF and bare CR become LF, padding is dropped
+    pub fn set_normalize(&mut self, normalize: bool) {
+        self.normalize = normalize;
+    }
+
+    /// CPU cycles BUSY stays up after each character (0 = instant)
+    pub fn set_busy_cycles(&mut self, cycles: u64) {
+        self.busy_cycles = cycles;
+    }
+
+    /// Hold BUSY up regardless of printing
+    pub fn set_stuck_busy(&mut self, stuck: bool) {
+        self.stuck_busy = stuck;
+    }
+
+    pub fn set_paper_out(&mut self, out: bool) {
+        self.paper_out = out;
+    }
+
+    /// Characters lost because the printer wasn't ready
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Pages written to the spool so far
+    pub fn pages(&self) -> usize {
+        self.pages
+    }
+
+    /// The page being printed (not yet flushed)
+    pub fn page(&self) -> &[u8] {
+        &self.page
+    }
+
+    fn ready(&self) -> bool {
+        self.is_mounted() && !self.paper_out && !self.stuck_busy && self.busy == 0
+    }
+
+    fn status(&self) -> u8 {
+        let mut status = 0;
+        if self.ready() {
+            status |= STATUS_READY;
+        }
+        if self.stuck_busy || self.busy > 0 {
+            status |= STATUS_BUSY;
+        }
+        if self.paper_out {
+            status |= STATUS_PAPER_OUT;
+        }
+        if self.is_mounted() {
+            status |= STATUS_SELECT;
+        }
+        status
+    }
+
+    fn print(&mut self, c: u8) {
+        if !self.ready() {
+            self.dropped += 1;
+            return;
+        }
+        self.busy = self.busy_cycles;
+        if c == FORM_FEED {
+            self.end_line();
+            if !self.split_pages {
+                self.page.push(FORM_FEED);
+            }
+            let _ = self.flush();
+            return;
+        }
+        if !self.normalize {
+            self.page.push(c);
+            return;
+        }
+        match c {
+            0x0D => {
+                self.end_line();
+                self.pending_cr = true;
+            }
+            0x0A => {
+                self.pending_cr = false;
+                self.page.push(b'\n');
+            }
+            0x00 | 0x7F => {}
+            _ => {
+                self.end_line();
+                self.page.push(c);
+            }
+        }
+    }
+
+    /// A CR not followed by LF still ends the line
+    fn end_line(&mut self) {
+        if std::mem::take(&mut self.pending_cr) {
+            self.page.push(b'\n');
+        }
+    }
+
+    /// Write out the current page
+    pub fn flush(&mut self) -> Result<(), String> {
+        self.end_line();
+        let Some(path) = &self.path else { return Ok(()) };
+        if self.page.is_empty() {
+            return Ok(());
+        }
+        let result = if self.split_pages {
+            std::fs::write(page_path(path, self.pages + 1), &self.page)
+        } else {
+            OpenOptions::new().append(true).open(path).and_then(|mut f| f.write_all(&self.page))
+        };
+        result.map_err(|e| e.to_string())?;
+        self.page.clear();
+        self.pages += 1;
+        Ok(())
+    }
+
+    /// :printer mount FILE | unmount | flush | split|crlf|paperout|busy on|off | delay CYCLES
+    pub fn host_command(&mut self, args: &[&str]) -> Result<String, String> {
+        let on = |setting: &str| setting == "on";
+        match args {
+            ["mount", path] => {
+                self.mount(Path::new(path))?;
+                Ok(format!("Printer: {}", path))
+            }
+            ["unmount"] => {
+                self.unmount()?;
+                Ok(format!("Printer offline ({} pages)", self.pages))
+            }
+            ["flush"] => {
+                self.flush()?;
+                Ok(format!("{} pages printed", self.pages))
+            }
+            [option @ ("split" | "crlf" | "paperout" | "busy"), setting @ ("on" | "off")] => {
+                match *option {
+                    "split" => self.set_split_pages(on(setting)),
+                    "crlf" => self.set_normalize(on(setting)),
+                    "paperout" => self.set_paper_out(on(setting)),
+                    _ => self.set_stuck_busy(on(setting)),
+                }
+                Ok(format!("Printer {} {}", option, setting))
+            }
+            ["delay", cycles] => {
+                let cycles = cycles.parse().map_err(|_| format!("bad cycle count: {}", cycles))?;
+                self.set_busy_cycles(cycles);
+                Ok(format!("Printer busy {} cycles per character", cycles))
+            }
+            [] => Ok(match &self.path {
+                Some(path) => format!("Printer: {}, {} pages, {} dropped", path.display(), self.pages, self.dropped),
+                None => "Printer offline".to_string(),
+            }),
+            _ => Err("usage: :printer [mount FILE | unmount | flush | split|crlf|paperout|busy on|off | delay CYCLES]".to_string()),
+        }
+    }
+}
+
+/// LISTING.TXT page 3 -> LISTING-003.TXT
+fn page_path(path: &Path, page: usize) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{}-{:03}.{}", stem, page, ext.to_string_lossy()),
+        None => format!("{}-{:03}", stem, page),
+    };
+    path.with_file_name(name)
+}
+
+impl IoDevice for Printer {
+    fn read(&mut self, port: u8) -> u8 {
+        match port.wrapping_sub(self.base) {
+            0 => self.status(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) {
+        match port.wrapping_sub(self.base) {
+            0 if value == CMD_FLUSH => {
+                let _ = self.flush();
+            }
+            1 => self.print(value),
+            _ => {}
+        }
+    }
+}
+
+impl Clocked for Printer {
+    fn tick(&mut self, cycles: u64) {
+        self.busy = self.busy.saturating_sub(cycles);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Intel8080;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn printer(dir: &tempfile::TempDir) -> (Printer, PathBuf) {
+        let path = dir.path().join("LIST.TXT");
+        let mut printer = Printer::new(DEFAULT_BASE);
+        printer.mount(&path).unwrap();
+        (printer, path)
+    }
+
+    fn print_str(printer: &mut Printer, text: &[u8]) {
+        for &c in text {
+            printer.write(DEFAULT_BASE + 1, c);
+        }
+    }
+
+    #[test]
+    fn test_spool_flushes_on_form_feed_and_unmount() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut printer, path) = printer(&dir);
+        assert_eq!(printer.read(DEFAULT_BASE), STATUS_READY | STATUS_SELECT);
+
+        print_str(&mut printer, b"PAGE 1\r\n\x0cPAGE 2");
+        assert_eq!(std::fs::read(&path).unwrap(), b"PAGE 1\r\n\x0c");
+        assert_eq!(printer.page(), b"PAGE 2");
+
+        printer.unmount().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"PAGE 1\r\n\x0cPAGE 2");
+        assert_eq!(printer.pages(), 2);
+        assert_eq!(printer.read(DEFAULT_BASE), 0x00);
+    }
+
+    #[test]
+    fn test_split_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut printer = Printer::new(DEFAULT_BASE);
+        printer.set_split_pages(true);
+        printer.mount(&dir.path().join("LIST.TXT")).unwrap();
+
+        print_str(&mut printer, b"\x0cONE\x0cTWO\x0c");
+        assert_eq!(std::fs::read(dir.path().join("LIST-001.TXT")).unwrap(), b"ONE");
+        assert_eq!(std::fs::read(dir.path().join("LIST-002.TXT")).unwrap(), b"TWO");
+        assert!(!dir.path().join("LIST.TXT").exists());
+        assert_eq!(page_path(Path::new("out"), 7), PathBuf::from("out-007"));
+    }
+
+    #[test]
+    fn test_crlf_normalisation() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut printer, path) = printer(&dir);
+        printer.set_normalize(true);
+
+        print_str(&mut printer, b"A\r\n\0\0B\rC\n\n\x7fD\r");
+        printer.write(DEFAULT_BASE, CMD_FLUSH);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "A\nB\nC\n\nD\n");
+    }
+
+    #[test]
+    fn test_faults_drop_characters() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut printer, _path) = printer(&dir);
+
+        printer.set_paper_out(true);
+        assert_eq!(printer.read(DEFAULT_BASE), STATUS_PAPER_OUT | STATUS_SELECT);
+        print_str(&mut printer, b"X");
+        printer.set_paper_out(false);
+
+        printer.set_stuck_busy(true);
+        assert_eq!(printer.read(DEFAULT_BASE), STATUS_BUSY | STATUS_SELECT);
+        print_str(&mut printer, b"Y");
+        printer.set_stuck_busy(false);
+
+        printer.set_busy_cycles(1000);
+        print_str(&mut printer, b"OK");         // K arrives while busy
+        assert_eq!(printer.read(DEFAULT_BASE), STATUS_BUSY | STATUS_SELECT);
+        printer.tick(1000);
+        assert_eq!(printer.read(DEFAULT_BASE), STATUS_READY | STATUS_SELECT);
+        assert_eq!(printer.page(), b"O");
+        assert_eq!(printer.dropped(), 3);
+    }
+
+    #[test]
+    fn test_host_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("H.TXT");
+        let mut printer = Printer::new(DEFAULT_BASE);
+        assert_eq!(printer.host_command(&[]).unwrap(), "Printer offline");
+        printer.host_command(&["mount", path.to_str().unwrap()]).unwrap();
+        printer.host_command(&["paperout", "on"]).unwrap();
+        assert_eq!(printer.read(DEFAULT_BASE) & STATUS_PAPER_OUT, STATUS_PAPER_OUT);
+        printer.host_command(&["paperout", "off"]).unwrap();
+        printer.host_command(&["delay", "50"]).unwrap();
+        print_str(&mut printer, b"Z");
+        assert_eq!(printer.read(DEFAULT_BASE) & STATUS_BUSY, STATUS_BUSY);
+        assert!(printer.host_command(&["delay", "soon"]).is_err());
+        assert_eq!(printer.host_command(&["unmount"]).unwrap(), "Printer offline (1 pages)");
+        assert_eq!(std::fs::read(&path).unwrap(), b"Z");
+    }
+
+    #[test]
+    fn test_cpu_print_routine_waits_for_ready() {
+        // Print "HI" polling READY before each character, then halt
+        //       LXI H,MSG / MVI C,2
+        // WAIT: IN B8 / RRC / JNC WAIT / MOV A,M / OUT B9 / INX H
+        //       DCR C / JNZ WAIT / HLT
+        // MSG:  'HI'
+        let program = [
+            0x21, 0x14, 0x00, 0x0E, 0x02,
+            0xDB, 0xB8, 0x0F, 0xD2, 0x05, 0x00, 0x7E, 0xD3, 0xB9, 0x23,
+            0x0D, 0xC2, 0x05, 0x00, 0x76, b'H', b'I',
+        ];
+        let dir = tempfile::tempdir().unwrap();
+        let (mut dev, _path) = printer(&dir);
+        dev.set_busy_cycles(500);
+        let dev = Rc::new(RefCell::new(dev));
+        let mut cpu = Intel8080::new();
+        cpu.load_program(&program, 0x0000);
+        cpu.io_bus_mut().map_port(DEFAULT_BASE, dev.clone());
+        cpu.io_bus_mut().map_port(DEFAULT_BASE + 1, dev.clone());
+        cpu.attach_clocked(dev.clone());
+
+        while !cpu.halted && cpu.cycles < 100_000 {
+            cpu.execute_one();
+        }
+        assert_eq!(dev.borrow().page(), b"HI");
+        assert_eq!(dev.borrow().dropped(), 0);
+        assert!(cpu.cycles > 500);
+    }
+}
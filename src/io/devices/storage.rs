@@ -3,129 +3,409 @@
 // 24-bit addressing = 16MB address space
 // No sectors, no tracks, no banks. Just bytes.
 //
+// Port 0x04: Unit select (read/write)
+// Port 0x05: Error code (read only)
 // Port 0x08: Address low byte
 // Port 0x09: Address mid byte  
 // Port 0x0A: Address high byte
 // Port 0x0B: Data (read/write with auto-increment)
 // Port 0x0C: Read = Status, Write = Control
 //
+// There are UNITS independent units (drives), each with its own file,
+// address and size. Ports 0x08-0x0C work on the selected unit; after
+// reset that is unit 0, so single-drive software never notices. Unit
+// numbers outside 0..UNITS are ignored.
+//
 // Status bits:
 //   Bit 0: Mounted (1 = file mounted)
-//   Bit 1: Ready (always 1)
+//   Bit 1: Ready (no error pending)
+//   Bit 2: I/O error (host read/write failed, or no file mounted)
+//   Bit 3: Write-protected (write to a read-only mount)
+//   Bit 4: Beyond limit (read past EOF, or write past the size limit)
+//   Bit 5: Not supported (control the unit's backend can't do)
+//   Bit 6: Read-only mount
 //   Bit 7: EOF (address >= file size)
 //
+// Error codes (port 0x05):
+//   0x00: None
+//   0x01: I/O error
+//   0x02: Write-protected
+//   0x03: Address beyond limit
+//   0x04: Not mounted
+//   0x05: Not supported (e.g. discard on a plain file)
+//
+// Errors are sticky per unit: bits 2-5 and the code (the first error)
+// stay until cleared with control 0x03 or a new mount. A failed read
+// returns 0xFF and a failed write is dropped; neither moves the address.
+//
 // Control commands:
 //   0x00: Reset address to 0
 //   0x01: Decrement address
 //   0x02: Flush write buffer
+//   0x03: Clear error
+//   0x04: Commit (overlay: write the changes into the base image)
+//   0x05: Discard (overlay: drop changes since the last commit)
+//
+// Each unit holds a StorageBackend (storage_backend.rs). Host files go
+// through a page cache (page_cache.rs): writes are held in memory until
+// control 0x02, unmount, eviction or shutdown, unless the sync policy says
+// otherwise. Programs see the same bytes either way. An overlay mount
+// keeps writes out of the file entirely until a commit.
+//
+// As a DMA peripheral, DREQ is up while the selected unit has a file
+// mounted and each DACK moves one byte at its current address, with the
+// same auto-increment.
 
-use crate::io::IoDevice;
-use std::fs::{File, OpenOptions};
-use std::io::{Read, Write, Seek, SeekFrom};
-use std::path::PathBuf;
+use crate::io::{DmaDevice, IoDevice};
+use super::page_cache::{PageCache, SyncPolicy};
+use super::storage_backend::{Overlay, StorageBackend};
+use std::fs::OpenOptions;
+use std::io;
+use std::path::{Path, PathBuf};
 
-pub struct Storage {
-    file: Option<File>,
+/// Number of storage units
+pub const UNITS: usize = 4;
+
+pub const STATUS_MOUNTED: u8 = 0x01;
+pub const STATUS_READY: u8 = 0x02;
+pub const STATUS_IO_ERROR: u8 = 0x04;
+pub const STATUS_WRITE_PROTECT: u8 = 0x08;
+pub const STATUS_LIMIT: u8 = 0x10;
+pub const STATUS_UNSUPPORTED: u8 = 0x20;
+pub const STATUS_READ_ONLY: u8 = 0x40;
+pub const STATUS_EOF: u8 = 0x80;
+
+pub const ERR_NONE: u8 = 0x00;
+pub const ERR_IO: u8 = 0x01;
+pub const ERR_WRITE_PROTECT: u8 = 0x02;
+pub const ERR_LIMIT: u8 = 0x03;
+pub const ERR_NOT_MOUNTED: u8 = 0x04;
+pub const ERR_UNSUPPORTED: u8 = 0x05;
+
+/// Largest file a unit can grow to (the 24-bit address space)
+pub const MAX_SIZE: u32 = 0x0100_0000;
+
+/// How mount treats the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MountMode {
+    Create,     // Read/write, create if missing
+    Existing,   // Read/write, must exist
+    ReadOnly,   // Must exist, writes refused
+    Overlay,    // Must exist, writes kept in memory until commit
+}
+
+/// One drive: a mounted file and its address register
+struct Unit {
+    file: Option<Box<dyn StorageBackend>>,
+    path: Option<PathBuf>,
     address: u32,       // 24-bit, stored in 32 for convenience
     file_size: u32,
+    read_only: bool,
+    limit: u32,
+    error: u8,          // First error code since clear
+    error_bits: u8,     // Status bits 2-5
 }
 
-impl Storage {
-    pub fn new() -> Self {
-        Storage {
+impl Default for Unit {
+    fn default() -> Self {
+        Unit {
             file: None,
+            path: None,
             address: 0,
             file_size: 0,
+            read_only: false,
+            limit: MAX_SIZE,
+            error: ERR_NONE,
+            error_bits: 0,
+        }
+    }
+}
+
+impl Unit {
+    fn fail(&mut self, code: u8) {
+        if self.error == ERR_NONE {
+            self.error = code;
         }
+        self.error_bits |= match code {
+            ERR_WRITE_PROTECT => STATUS_WRITE_PROTECT,
+            ERR_LIMIT => STATUS_LIMIT,
+            ERR_UNSUPPORTED => STATUS_UNSUPPORTED,
+            _ => STATUS_IO_ERROR,
+        };
     }
 
-    /// Mount a file for storage operations
+    fn clear_error(&mut self) {
+        self.error = ERR_NONE;
+        self.error_bits = 0;
+    }
+}
+
+pub struct Storage {
+    units: Vec<Unit>,
+    selected: usize,
+    policy: SyncPolicy,
+}
+
+impl Storage {
+    pub fn new() -> Self {
+        Storage {
+            units: (0..UNITS).map(|_| Unit::default()).collect(),
+            selected: 0,
+            policy: SyncPolicy::default(),
+        }
+    }
+
+    /// Make `unit` the one ports 0x08-0x0C talk to
+    pub fn select(&mut self, unit: usize) {
+        if unit < self.units.len() {
+            self.selected = unit;
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    fn unit(&self) -> &Unit {
+        &self.units[self.selected]
+    }
+
+    fn unit_mut(&mut self) -> &mut Unit {
+        &mut self.units[self.selected]
+    }
+
+    /// When cached writes reach the files (all units, now and later)
+    pub fn set_sync_policy(&mut self, policy: SyncPolicy) {
+        self.policy = policy;
+        for backend in self.units.iter_mut().filter_map(|u| u.file.as_mut()) {
+            backend.set_policy(policy);
+        }
+    }
+
+    /// Mount a file on the selected unit
     pub fn mount(&mut self, path: &PathBuf) -> Result<(), String> {
+        self.mount_unit(self.selected, path)
+    }
+
+    /// Mount a file on a given unit, creating it if missing
+    pub fn mount_unit(&mut self, unit: usize, path: &PathBuf) -> Result<(), String> {
+        self.mount_unit_mode(unit, path, MountMode::Create)
+    }
+
+    pub fn mount_unit_mode(&mut self, unit: usize, path: &PathBuf, mode: MountMode) -> Result<(), String> {
+        if unit >= self.units.len() {
+            return Err(format!("no storage unit {}", unit));
+        }
+        if let Some(other) = self.mounted_unit(path).filter(|&other| other != unit) {
+            return Err(format!("{} is already mounted on unit {}", path.display(), other));
+        }
+        // Write back what the unit holds first, so the file is opened as the
+        // program last left it (remounting the same file included)
+        self.unmount_unit(unit);
         let file = OpenOptions::new()
             .read(true)
-            .write(true)
-            .create(true)
+            .write(mode != MountMode::ReadOnly && mode != MountMode::Overlay)
+            .create(mode == MountMode::Create)
+            .truncate(false)
             .open(path)
             .map_err(|e| e.to_string())?;
+        let path = path.canonicalize().map_err(|e| e.to_string())?;
+
+        let cache = Box::new(PageCache::new(file, self.policy).map_err(|e| e.to_string())?);
+        let backend: Box<dyn StorageBackend> = match mode {
+            MountMode::Overlay => {
+                let mut overlay = Overlay::new(cache);
+                overlay.set_base_file(path.clone());
+                Box::new(overlay)
+            }
+            _ => cache,
+        };
+        self.mount_backend(unit, backend, mode == MountMode::ReadOnly)?;
+        self.units[unit].path = Some(path);
+        Ok(())
+    }
 
-        let metadata = file.metadata().map_err(|e| e.to_string())?;
-        self.file_size = metadata.len() as u32;
-        self.file = Some(file);
-        self.address = 0;
+    /// Mount any backend (in-memory image, overlay...) on a unit
+    pub fn mount_backend(&mut self, unit: usize, backend: Box<dyn StorageBackend>, read_only: bool) -> Result<(), String> {
+        if unit >= self.units.len() {
+            return Err(format!("no storage unit {}", unit));
+        }
+        let limit = self.units[unit].limit;
+        self.unmount_unit(unit);
+        let unit = &mut self.units[unit];
+        unit.file_size = backend.len().min(MAX_SIZE as u64) as u32;
+        unit.file = Some(backend);
+        unit.read_only = read_only;
+        unit.limit = limit;
         Ok(())
     }
 
-    /// Unmount current file
+    /// Largest size writes may grow a unit's file to (default MAX_SIZE)
+    pub fn set_size_limit(&mut self, unit: usize, limit: u32) {
+        if let Some(unit) = self.units.get_mut(unit) {
+            unit.limit = limit.min(MAX_SIZE);
+        }
+    }
+
+    /// Sticky error code of the selected unit
+    pub fn error(&self) -> u8 {
+        self.unit().error
+    }
+
+    /// Unmount the selected unit's file
     pub fn unmount(&mut self) {
-        if let Some(ref mut f) = self.file {
+        self.unmount_unit(self.selected);
+    }
+
+    pub fn unmount_unit(&mut self, unit: usize) {
+        let Some(unit) = self.units.get_mut(unit) else { return };
+        if let Some(ref mut f) = unit.file {
             let _ = f.flush();
         }
-        self.file = None;
-        self.file_size = 0;
-        self.address = 0;
+        let limit = unit.limit;
+        *unit = Unit { limit, ..Unit::default() };
+    }
+
+    /// Unmount every unit (emulator shutdown)
+    pub fn unmount_all(&mut self) {
+        for unit in 0..self.units.len() {
+            self.unmount_unit(unit);
+        }
     }
 
     pub fn is_mounted(&self) -> bool {
-        self.file.is_some()
+        self.is_unit_mounted(self.selected)
+    }
+
+    pub fn is_unit_mounted(&self, unit: usize) -> bool {
+        self.units.get(unit).is_some_and(|u| u.file.is_some())
+    }
+
+    /// Size of a file if some unit has it mounted (including unflushed writes)
+    pub fn mounted_size(&self, path: &Path) -> Option<u32> {
+        self.mounted_unit(path).map(|unit| self.units[unit].file_size)
+    }
+
+    /// Unit a host file is mounted on, however its path is spelled
+    pub fn mounted_unit(&self, path: &Path) -> Option<usize> {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.units.iter().position(|u| u.path.as_deref() == Some(path.as_path()))
     }
 
     fn read_data(&mut self) -> u8 {
-        if let Some(ref mut file) = self.file {
-            if file.seek(SeekFrom::Start(self.address as u64)).is_ok() {
-                let mut buf = [0u8; 1];
-                if file.read_exact(&mut buf).is_ok() {
-                    self.increment_address();
-                    return buf[0];
-                }
+        let unit = self.unit_mut();
+        let Some(ref mut file) = unit.file else {
+            unit.fail(ERR_NOT_MOUNTED);
+            return 0xFF;
+        };
+        if unit.address >= unit.file_size {
+            unit.fail(ERR_LIMIT);
+            return 0xFF;
+        }
+        match file.read_byte(unit.address as u64) {
+            Ok(value) => {
+                self.increment_address();
+                value
+            }
+            Err(_) => {
+                unit.fail(ERR_IO);
+                0xFF
             }
         }
-        0xFF
     }
 
     fn write_data(&mut self, value: u8) {
-        if let Some(ref mut file) = self.file {
-            if file.seek(SeekFrom::Start(self.address as u64)).is_ok() {
-                let _ = file.write_all(&[value]);
-                // Expand file size tracking if we wrote past end
-                if self.address >= self.file_size {
-                    self.file_size = self.address + 1;
-                }
-                self.increment_address();
-            }
+        let unit = self.unit_mut();
+        let Some(ref mut file) = unit.file else {
+            unit.fail(ERR_NOT_MOUNTED);
+            return;
+        };
+        if unit.read_only {
+            unit.fail(ERR_WRITE_PROTECT);
+            return;
+        }
+        if unit.address >= unit.limit {
+            unit.fail(ERR_LIMIT);
+            return;
+        }
+        if file.write_byte(unit.address as u64, value).is_err() {
+            unit.fail(ERR_IO);
+            return;
         }
+        // Expand file size tracking if we wrote past end
+        if unit.address >= unit.file_size {
+            unit.file_size = unit.address + 1;
+        }
+        self.increment_address();
     }
 
     fn flush(&mut self) {
-        if let Some(ref mut file) = self.file {
-            let _ = file.flush();
+        let unit = self.unit_mut();
+        if let Some(ref mut file) = unit.file {
+            if file.flush().is_err() {
+                unit.fail(ERR_IO);
+            }
+        }
+    }
+
+    /// Commit or discard the selected unit's pending changes
+    fn settle(&mut self, commit: bool) {
+        let unit = self.unit_mut();
+        let Some(ref mut backend) = unit.file else {
+            unit.fail(ERR_NOT_MOUNTED);
+            return;
+        };
+        let result = if commit { backend.commit() } else { backend.discard() };
+        unit.file_size = backend.len().min(MAX_SIZE as u64) as u32;
+        match result {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => unit.fail(ERR_UNSUPPORTED),
+            Err(_) => unit.fail(ERR_IO),
         }
     }
 
     fn increment_address(&mut self) {
         // 24-bit wrap
-        self.address = (self.address.wrapping_add(1)) & 0x00FF_FFFF;
+        let unit = self.unit_mut();
+        unit.address = (unit.address.wrapping_add(1)) & 0x00FF_FFFF;
     }
 
     fn decrement_address(&mut self) {
         // 24-bit wrap
-        self.address = (self.address.wrapping_sub(1)) & 0x00FF_FFFF;
+        let unit = self.unit_mut();
+        unit.address = (unit.address.wrapping_sub(1)) & 0x00FF_FFFF;
+    }
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl IoDevice for Storage {
     fn read(&mut self, port: u8) -> u8 {
+        let unit = self.unit();
         match port {
-            0x08 => self.address as u8,                         // ADDR_LO
-            0x09 => (self.address >> 8) as u8,                  // ADDR_MID
-            0x0A => (self.address >> 16) as u8,                 // ADDR_HI
+            0x04 => self.selected as u8,                        // UNIT
+            0x05 => unit.error,                                 // ERROR
+            0x08 => unit.address as u8,                         // ADDR_LO
+            0x09 => (unit.address >> 8) as u8,                  // ADDR_MID
+            0x0A => (unit.address >> 16) as u8,                 // ADDR_HI
             0x0B => self.read_data(),                           // DATA
             0x0C => {                                           // STATUS
-                let mut status = 0x02;  // Bit 1: always ready
-                if self.is_mounted() {
-                    status |= 0x01;     // Bit 0: mounted
+                let mut status = unit.error_bits;
+                if unit.error == ERR_NONE {
+                    status |= STATUS_READY;
+                }
+                if unit.file.is_some() {
+                    status |= STATUS_MOUNTED;
+                }
+                if unit.read_only {
+                    status |= STATUS_READ_ONLY;
                 }
-                if self.address >= self.file_size {
-                    status |= 0x80;     // Bit 7: EOF
+                if unit.address >= unit.file_size {
+                    status |= STATUS_EOF;
                 }
                 status
             }
@@ -134,24 +414,31 @@ impl IoDevice for Storage {
     }
 
     fn write(&mut self, port: u8, value: u8) {
+        let unit = self.unit_mut();
         match port {
+            0x04 => {  // UNIT
+                self.select(value as usize);
+            }
             0x08 => {  // ADDR_LO
-                self.address = (self.address & 0x00FFFF00) | (value as u32);
+                unit.address = (unit.address & 0x00FFFF00) | (value as u32);
             }
             0x09 => {  // ADDR_MID
-                self.address = (self.address & 0x00FF00FF) | ((value as u32) << 8);
+                unit.address = (unit.address & 0x00FF00FF) | ((value as u32) << 8);
             }
             0x0A => {  // ADDR_HI
-                self.address = (self.address & 0x0000FFFF) | ((value as u32) << 16);
+                unit.address = (unit.address & 0x0000FFFF) | ((value as u32) << 16);
             }
             0x0B => {  // DATA
                 self.write_data(value);
             }
             0x0C => {  // CONTROL
                 match value {
-                    0x00 => self.address = 0,           // Reset address
+                    0x00 => unit.address = 0,           // Reset address
                     0x01 => self.decrement_address(),   // Decrement
                     0x02 => self.flush(),               // Flush
+                    0x03 => unit.clear_error(),         // Clear error
+                    0x04 => self.settle(true),          // Commit
+                    0x05 => self.settle(false),         // Discard
                     _ => {}
                 }
             }
@@ -160,9 +447,25 @@ impl IoDevice for Storage {
     }
 }
 
+impl DmaDevice for Storage {
+    fn dma_request(&mut self) -> bool {
+        self.is_mounted()
+    }
+
+    fn dma_read(&mut self) -> u8 {
+        self.read_data()
+    }
+
+    fn dma_write(&mut self, value: u8) {
+        self.write_data(value);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::io::devices::storage_backend::MemoryImage;
+    use std::fs::File;
     use std::io::Write;
 
     fn temp_file_with_data(data: &[u8]) -> (tempfile::TempDir, PathBuf) {
@@ -198,7 +501,7 @@ mod tests {
         assert_eq!(storage.read(0x08), 0x56);
         assert_eq!(storage.read(0x09), 0x34);
         assert_eq!(storage.read(0x0A), 0x12);
-        assert_eq!(storage.address, 0x123456);
+        assert_eq!(storage.unit().address, 0x123456);
     }
 
     #[test]
@@ -217,7 +520,7 @@ mod tests {
         assert_eq!(storage.read(0x0B), 0x44);
         
         // Address should be 4 now
-        assert_eq!(storage.address, 4);
+        assert_eq!(storage.unit().address, 4);
     }
 
     #[test]
@@ -277,7 +580,7 @@ mod tests {
         
         // Increment should wrap to 0
         storage.increment_address();
-        assert_eq!(storage.address, 0);
+        assert_eq!(storage.unit().address, 0);
     }
 
     #[test]
@@ -289,4 +592,184 @@ mod tests {
         
         assert_eq!(storage.read(0x08), 0x04);
     }
+
+    #[test]
+    fn test_units_are_independent() {
+        let (_dir, path_a) = temp_file_with_data(b"AAAA");
+        let (_dir2, path_b) = temp_file_with_data(b"BB");
+        let mut storage = Storage::new();
+        storage.mount(&path_a).unwrap();                // Unit 0, as before
+        storage.mount_unit(1, &path_b).unwrap();
+        assert!(storage.mount_unit(UNITS, &path_b).is_err());
+
+        storage.write(0x08, 0x03);
+        assert_eq!(storage.read(0x0B), b'A');
+        storage.write(0x04, 0x01);
+        assert_eq!(storage.read(0x04), 0x01);
+        assert_eq!(storage.read(0x08), 0x00);           // Unit 1's own address
+        assert_eq!(storage.read(0x0B), b'B');
+        storage.write(0x0B, b'b');                      // Copy target
+        storage.write(0x04, 0x00);
+        assert_eq!(storage.read(0x08), 0x04);
+        assert_eq!(storage.read(0x0C) & 0x80, 0x80);    // Unit 0 at its EOF
+
+        storage.write(0x04, 0x07);                      // No such unit: ignored
+        assert_eq!(storage.selected(), 0);
+        storage.unmount_all();
+        assert!(!storage.is_unit_mounted(0) && !storage.is_unit_mounted(1));
+        assert_eq!(std::fs::read(&path_b).unwrap(), b"Bb");
+    }
+
+    #[test]
+    fn test_errors_are_sticky_until_cleared() {
+        let (_dir, path) = temp_file_with_data(&[0x41]);
+        let mut storage = Storage::new();
+
+        assert_eq!(storage.read(0x0B), 0xFF);           // Nothing mounted
+        assert_eq!(storage.read(0x05), ERR_NOT_MOUNTED);
+        assert_eq!(storage.read(0x0C) & (STATUS_READY | STATUS_IO_ERROR), STATUS_IO_ERROR);
+        storage.write(0x0C, 0x03);
+        assert_eq!(storage.read(0x05), ERR_NONE);
+
+        storage.mount(&path).unwrap();
+        assert_eq!(storage.read(0x0B), 0x41);
+        assert_eq!(storage.read(0x0B), 0xFF);           // Past EOF
+        assert_eq!(storage.read(0x08), 0x01);           // Address didn't move
+        assert_eq!(storage.read(0x05), ERR_LIMIT);
+        assert_eq!(storage.read(0x0C), STATUS_MOUNTED | STATUS_LIMIT | STATUS_EOF);
+
+        storage.write(0x0C, 0x03);
+        assert_eq!(storage.read(0x0C), STATUS_MOUNTED | STATUS_READY | STATUS_EOF);
+    }
+
+    #[test]
+    fn test_read_only_mount() {
+        let (dir, path) = temp_file_with_data(&[0x41, 0x42]);
+        let mut storage = Storage::new();
+        assert!(storage.mount_unit_mode(0, &dir.path().join("none.bin"), MountMode::ReadOnly).is_err());
+        assert!(storage.mount_unit_mode(0, &dir.path().join("none.bin"), MountMode::Existing).is_err());
+        assert!(!dir.path().join("none.bin").exists());
+
+        storage.mount_unit_mode(0, &path, MountMode::ReadOnly).unwrap();
+        assert_eq!(storage.read(0x0C) & STATUS_READ_ONLY, STATUS_READ_ONLY);
+        storage.write(0x0B, 0x00);
+        assert_eq!(storage.read(0x05), ERR_WRITE_PROTECT);
+        assert_eq!(storage.read(0x0C) & STATUS_WRITE_PROTECT, STATUS_WRITE_PROTECT);
+        assert_eq!(storage.read(0x0B), 0x41);           // Reads still work
+        storage.unmount();
+        assert_eq!(std::fs::read(&path).unwrap(), vec![0x41, 0x42]);
+    }
+
+    #[test]
+    fn test_file_mounts_on_one_unit() {
+        let (dir, path) = temp_file_with_data(&[0x41]);
+        let mut storage = Storage::new();
+        storage.mount_unit(0, &path).unwrap();
+        let other_name = dir.path().join(".").join("test.bin");
+        assert!(storage.mount_unit_mode(1, &other_name, MountMode::ReadOnly).is_err());
+        assert!(!storage.is_unit_mounted(1));
+        assert_eq!(storage.mounted_unit(&other_name), Some(0));
+
+        storage.mount_unit_mode(0, &other_name, MountMode::Existing).unwrap();  // Same unit again
+        storage.unmount_unit(0);
+        storage.mount_unit(1, &other_name).unwrap();
+    }
+
+    #[test]
+    fn test_remount_sees_unflushed_writes() {
+        let (_dir, path) = temp_file_with_data(b"ab");
+        let mut storage = Storage::new();
+        storage.mount(&path).unwrap();
+        storage.write(0x08, 0x02);
+        storage.write(0x0B, b'c');                      // Past EOF, still in the cache
+        storage.write(0x0B, b'd');
+
+        storage.mount_unit_mode(0, &path, MountMode::Existing).unwrap();
+        storage.write(0x08, 0x02);
+        assert_eq!(storage.read(0x0B), b'c');
+        assert_eq!(storage.read(0x0B), b'd');
+        assert_eq!(storage.error(), ERR_NONE);
+        assert_eq!(std::fs::read(&path).unwrap(), b"abcd");
+    }
+
+    #[test]
+    fn test_size_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut storage = Storage::new();
+        storage.set_size_limit(0, 2);
+        storage.mount(&dir.path().join("small.bin")).unwrap();
+        for b in [1, 2, 3] {
+            storage.write(0x0B, b);
+        }
+        assert_eq!(storage.error(), ERR_LIMIT);
+        storage.unmount();
+        assert_eq!(std::fs::read(dir.path().join("small.bin")).unwrap(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_writes_reach_file_on_flush() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.bin");
+        let mut storage = Storage::new();
+        storage.mount(&path).unwrap();
+        storage.write(0x0B, 0x11);
+        storage.write(0x0B, 0x22);
+        assert_eq!(std::fs::read(&path).unwrap(), Vec::<u8>::new());
+        storage.write(0x08, 0x00);                  // Reads see the cached bytes
+        assert_eq!(storage.read(0x0B), 0x11);
+        assert_eq!(storage.read(0x0B), 0x22);
+
+        storage.write(0x0C, 0x02);
+        assert_eq!(std::fs::read(&path).unwrap(), vec![0x11, 0x22]);
+
+        storage.set_sync_policy(SyncPolicy::WriteThrough);
+        storage.write(0x0B, 0x33);
+        assert_eq!(std::fs::read(&path).unwrap(), vec![0x11, 0x22, 0x33]);
+    }
+
+    #[test]
+    fn test_memory_backend() {
+        let mut storage = Storage::new();
+        storage.mount_backend(1, Box::new(MemoryImage::new(vec![0x10, 0x20])), false).unwrap();
+        storage.select(1);
+        assert_eq!(storage.read(0x0C), STATUS_MOUNTED | STATUS_READY);
+        assert_eq!(storage.read(0x0B), 0x10);
+        storage.write(0x0B, 0x99);
+        storage.write(0x0B, 0x77);              // Grows the image
+        storage.write(0x08, 0x01);
+        assert_eq!(storage.read(0x0B), 0x99);
+        assert_eq!(storage.read(0x0B), 0x77);
+        assert_eq!(storage.read(0x0C) & STATUS_EOF, STATUS_EOF);
+
+        storage.write(0x0C, 0x05);              // Nothing to discard
+        assert_eq!(storage.error(), ERR_UNSUPPORTED);
+        assert_eq!(storage.read(0x0C) & (STATUS_READY | STATUS_IO_ERROR | STATUS_UNSUPPORTED), STATUS_UNSUPPORTED);
+    }
+
+    #[test]
+    fn test_overlay_commit_and_discard() {
+        let (_dir, path) = temp_file_with_data(b"GOLD");
+        let mut storage = Storage::new();
+        storage.mount_unit_mode(0, &path, MountMode::Overlay).unwrap();
+        storage.write(0x0B, b'X');
+        storage.write(0x08, 0x04);
+        storage.write(0x0B, b'!');
+        storage.write(0x0C, 0x02);              // Flush leaves the golden image alone
+        assert_eq!(std::fs::read(&path).unwrap(), b"GOLD");
+
+        storage.write(0x0C, 0x05);              // Discard
+        storage.write(0x0C, 0x00);
+        assert_eq!(storage.read(0x0B), b'G');
+        assert_eq!(storage.read(0x0C) & STATUS_EOF, 0);
+        storage.write(0x08, 0x04);
+        assert_eq!(storage.read(0x0C) & STATUS_EOF, STATUS_EOF);
+
+        storage.write(0x0C, 0x00);
+        storage.write(0x0B, b'C');
+        storage.write(0x0C, 0x04);              // Commit
+        assert_eq!(storage.error(), ERR_NONE);
+        assert_eq!(std::fs::read(&path).unwrap(), b"COLD");
+        storage.unmount();
+        assert_eq!(std::fs::read(&path).unwrap(), b"COLD");
+    }
 }
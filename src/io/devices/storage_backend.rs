@@ -0,0 +1,479 @@
+// storage_backend.rs - What a storage unit keeps its bytes in
+//
+// Backends:
+//   PageCache   - a host file through the page cache (page_cache.rs)
+//   MemoryImage - a byte buffer, nothing on disk (tests, scratch units)
+//   Overlay     - copy-on-write over another backend: reads fall through
+//                 to the base image until a page is written, writes land
+//                 in a delta kept in memory or in a delta file. commit()
+//                 folds the delta into the base, discard() throws it away.
+//                 A file base is opened read-only and only reopened for
+//                 writing when a commit has pages to write. A commit that
+//                 fails puts the base bytes back and keeps the delta.
+//
+// Delta file format (all little-endian):
+//   "OVL1", logical length (u64), then per page: page number (u64)
+//   followed by PAGE_SIZE bytes.
+
+use super::page_cache::{PageCache, SyncPolicy, PAGE_SIZE};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const DELTA_MAGIC: &[u8; 4] = b"OVL1";
+
+/// Byte-addressed image behind a storage unit
+pub trait StorageBackend {
+    /// Logical length, including writes not yet flushed
+    fn len(&self) -> u64;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Byte at `offset`; 0 in a gap below `len()`
+    fn read_byte(&mut self, offset: u64) -> io::Result<u8>;
+
+    /// Store a byte, growing the image if `offset` is past the end
+    fn write_byte(&mut self, offset: u64, value: u8) -> io::Result<()>;
+
+    /// Make written bytes durable in whatever the backend keeps them in
+    fn flush(&mut self) -> io::Result<()>;
+
+    fn set_policy(&mut self, _policy: SyncPolicy) {}
+
+    /// Get ready for writes after being opened read-only from `path`
+    fn reopen_writable(&mut self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Cut or extend the image to `len` bytes
+    fn set_len(&mut self, _len: u64) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "backend can't change length"))
+    }
+
+    /// Make pending changes permanent (for an overlay, write them to the base)
+    fn commit(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+
+    /// Drop changes since the last commit, if the backend can
+    fn discard(&mut self) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "backend keeps no undo"))
+    }
+}
+
+impl StorageBackend for PageCache {
+    fn len(&self) -> u64 {
+        PageCache::len(self)
+    }
+
+    fn read_byte(&mut self, offset: u64) -> io::Result<u8> {
+        PageCache::read_byte(self, offset)
+    }
+
+    fn write_byte(&mut self, offset: u64, value: u8) -> io::Result<()> {
+        PageCache::write_byte(self, offset, value)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        PageCache::flush(self)
+    }
+
+    fn set_policy(&mut self, policy: SyncPolicy) {
+        PageCache::set_policy(self, policy)
+    }
+
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        PageCache::set_len(self, len)
+    }
+
+    fn reopen_writable(&mut self, path: &Path) -> io::Result<()> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        self.set_file(file);
+        Ok(())
+    }
+}
+
+/// Image held entirely in memory
+#[derive(Debug, Clone, Default)]
+pub struct MemoryImage {
+    data: Vec<u8>,
+}
+
+impl MemoryImage {
+    pub fn new(data: Vec<u8>) -> Self {
+        MemoryImage { data }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl StorageBackend for MemoryImage {
+    fn len(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    fn read_byte(&mut self, offset: u64) -> io::Result<u8> {
+        Ok(self.data.get(offset as usize).copied().unwrap_or(0))
+    }
+
+    fn write_byte(&mut self, offset: u64, value: u8) -> io::Result<()> {
+        let offset = offset as usize;
+        if offset >= self.data.len() {
+            self.data.resize(offset + 1, 0);
+        }
+        self.data[offset] = value;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        self.data.resize(len as usize, 0);
+        Ok(())
+    }
+}
+
+/// Copy-on-write layer over a base image that it never writes until commit
+pub struct Overlay {
+    base: Box<dyn StorageBackend>,
+    pages: HashMap<u64, Box<[u8; PAGE_SIZE]>>,
+    len: u64,
+    delta_file: Option<PathBuf>,
+    base_file: Option<PathBuf>,     // Read-only base, reopened to commit
+    dirty: bool,            // Delta changed since it was last saved
+}
+
+impl Overlay {
+    /// Overlay with the delta in memory
+    pub fn new(base: Box<dyn StorageBackend>) -> Self {
+        let len = base.len();
+        Overlay { base, pages: HashMap::new(), len, delta_file: None, base_file: None, dirty: false }
+    }
+
+    /// The base was opened read-only from `path`; commit reopens it for writing
+    pub fn set_base_file(&mut self, path: PathBuf) {
+        self.base_file = Some(path);
+    }
+
+    /// Overlay whose delta lives in `path`, picking up an earlier delta there
+    pub fn with_delta_file(base: Box<dyn StorageBackend>, path: PathBuf) -> io::Result<Self> {
+        let mut overlay = Overlay::new(base);
+        match std::fs::read(&path) {
+            Ok(bytes) => overlay.load_delta(&bytes)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        overlay.delta_file = Some(path);
+        Ok(overlay)
+    }
+
+    /// Pages that differ from the base
+    pub fn changed_pages(&self) -> usize {
+        self.pages.len()
+    }
+
+    fn load_delta(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not an overlay delta file");
+        if bytes.len() < 12 || &bytes[..4] != DELTA_MAGIC {
+            return Err(invalid());
+        }
+        let len = u64::from_le_bytes(bytes[4..12].try_into().unwrap());
+        let records = &bytes[12..];
+        if !records.len().is_multiple_of(8 + PAGE_SIZE) {
+            return Err(invalid());
+        }
+        for record in records.chunks(8 + PAGE_SIZE) {
+            let number = u64::from_le_bytes(record[..8].try_into().unwrap());
+            let mut page = Box::new([0u8; PAGE_SIZE]);
+            page.copy_from_slice(&record[8..]);
+            self.pages.insert(number, page);
+        }
+        self.len = len.max(self.base.len());
+        Ok(())
+    }
+
+    fn save_delta(&mut self) -> io::Result<()> {
+        let Some(ref path) = self.delta_file else { return Ok(()) };
+        if self.pages.is_empty() && self.len == self.base.len() {
+            return match std::fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+        let mut numbers: Vec<u64> = self.pages.keys().copied().collect();
+        numbers.sort_unstable();
+        let mut bytes = Vec::with_capacity(12 + numbers.len() * (8 + PAGE_SIZE));
+        bytes.extend_from_slice(DELTA_MAGIC);
+        bytes.extend_from_slice(&self.len.to_le_bytes());
+        for number in numbers {
+            bytes.extend_from_slice(&number.to_le_bytes());
+            bytes.extend_from_slice(&self.pages[&number][..]);
+        }
+        std::fs::write(path, bytes)
+    }
+
+    /// Write the delta into the base and flush it, keeping the base bytes
+    /// each page overwrites in `undo`
+    fn apply(&mut self, undo: &mut Vec<(u64, Vec<u8>)>) -> io::Result<()> {
+        let base_len = self.base.len();
+        let mut numbers: Vec<u64> = self.pages.keys().copied().collect();
+        numbers.sort_unstable();
+        for number in numbers {
+            let start = number * PAGE_SIZE as u64;
+            let count = self.len.saturating_sub(start).min(PAGE_SIZE as u64) as usize;
+            let original = base_len.saturating_sub(start).min(count as u64);
+            let saved = (0..original).map(|i| self.base.read_byte(start + i)).collect::<io::Result<_>>()?;
+            undo.push((start, saved));
+            for i in 0..count {
+                let value = self.pages[&number][i];
+                self.base.write_byte(start + i as u64, value)?;
+            }
+        }
+        self.base.flush()
+    }
+
+    /// Put the base back as it was before a failed commit, so the delta can
+    /// still be committed again or discarded. Best effort: the base is
+    /// already failing, but its cached bytes at least come back.
+    fn restore_base(&mut self, undo: &[(u64, Vec<u8>)], len: u64) {
+        for (start, saved) in undo {
+            for (i, &byte) in saved.iter().enumerate() {
+                let _ = self.base.write_byte(start + i as u64, byte);
+            }
+        }
+        let _ = self.base.set_len(len);
+        let _ = self.base.flush();
+    }
+
+    /// The delta copy of a page, copying it up from the base on first write
+    fn page_mut(&mut self, number: u64) -> io::Result<&mut [u8; PAGE_SIZE]> {
+        if !self.pages.contains_key(&number) {
+            let mut page = Box::new([0u8; PAGE_SIZE]);
+            let start = number * PAGE_SIZE as u64;
+            let count = self.base.len().saturating_sub(start).min(PAGE_SIZE as u64) as usize;
+            for (i, byte) in page.iter_mut().take(count).enumerate() {
+                *byte = self.base.read_byte(start + i as u64)?;
+            }
+            self.pages.insert(number, page);
+        }
+        Ok(self.pages.get_mut(&number).unwrap())
+    }
+}
+
+impl StorageBackend for Overlay {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn read_byte(&mut self, offset: u64) -> io::Result<u8> {
+        let number = offset / PAGE_SIZE as u64;
+        match self.pages.get(&number) {
+            Some(page) => Ok(page[(offset % PAGE_SIZE as u64) as usize]),
+            None if offset < self.base.len() => self.base.read_byte(offset),
+            None => Ok(0),
+        }
+    }
+
+    fn write_byte(&mut self, offset: u64, value: u8) -> io::Result<()> {
+        let page = self.page_mut(offset / PAGE_SIZE as u64)?;
+        page[(offset % PAGE_SIZE as u64) as usize] = value;
+        self.len = self.len.max(offset + 1);
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Saves the delta file; the base is left alone
+    fn flush(&mut self) -> io::Result<()> {
+        if self.dirty {
+            self.save_delta()?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+
+    fn set_policy(&mut self, policy: SyncPolicy) {
+        self.base.set_policy(policy);
+    }
+
+    fn commit(&mut self) -> io::Result<()> {
+        if let Some(path) = self.base_file.as_ref().filter(|_| !self.pages.is_empty()) {
+            self.base.reopen_writable(path)?;
+            self.base_file = None;
+        }
+        let base_len = self.base.len();
+        let mut undo = Vec::new();
+        if let Err(e) = self.apply(&mut undo) {
+            self.restore_base(&undo, base_len);
+            return Err(e);
+        }
+        self.pages.clear();
+        self.len = self.base.len();
+        self.dirty = false;
+        self.save_delta()
+    }
+
+    fn discard(&mut self) -> io::Result<()> {
+        self.pages.clear();
+        self.len = self.base.len();
+        self.dirty = false;
+        self.save_delta()
+    }
+}
+
+impl Drop for Overlay {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn golden() -> Box<MemoryImage> {
+        Box::new(MemoryImage::new(b"GOLDEN".to_vec()))
+    }
+
+    #[test]
+    fn test_memory_image_grows_with_gap() {
+        let mut memory = MemoryImage::new(vec![1, 2]);
+        memory.write_byte(4, 5).unwrap();
+        assert_eq!(memory.data(), &[1, 2, 0, 0, 5]);
+        assert_eq!(memory.read_byte(9).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_overlay_leaves_base_alone_until_commit() {
+        let mut overlay = Overlay::new(golden());
+        overlay.write_byte(0, b'g').unwrap();
+        overlay.write_byte(PAGE_SIZE as u64, b'!').unwrap();    // Grows into page 1
+        assert_eq!(overlay.read_byte(0).unwrap(), b'g');
+        assert_eq!(overlay.read_byte(1).unwrap(), b'O');        // Copied up from base
+        assert_eq!(overlay.read_byte(10).unwrap(), 0);
+        assert_eq!(overlay.len(), PAGE_SIZE as u64 + 1);
+        assert_eq!(overlay.base.read_byte(0).unwrap(), b'G');
+        assert_eq!(overlay.base.len(), 6);
+
+        overlay.commit().unwrap();
+        assert_eq!(overlay.changed_pages(), 0);
+        assert_eq!(overlay.base.read_byte(0).unwrap(), b'g');
+        assert_eq!(overlay.base.len(), PAGE_SIZE as u64 + 1);
+    }
+
+    /// Image whose flush fails while `failing` is set
+    struct FlakyBase {
+        image: MemoryImage,
+        failing: Rc<Cell<bool>>,
+    }
+
+    impl StorageBackend for FlakyBase {
+        fn len(&self) -> u64 {
+            self.image.len()
+        }
+
+        fn read_byte(&mut self, offset: u64) -> io::Result<u8> {
+            self.image.read_byte(offset)
+        }
+
+        fn write_byte(&mut self, offset: u64, value: u8) -> io::Result<()> {
+            self.image.write_byte(offset, value)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            if self.failing.get() {
+                return Err(io::Error::other("disk full"));
+            }
+            Ok(())
+        }
+
+        fn set_len(&mut self, len: u64) -> io::Result<()> {
+            self.image.set_len(len)
+        }
+    }
+
+    #[test]
+    fn test_failed_commit_leaves_base_alone() {
+        let failing = Rc::new(Cell::new(true));
+        let base = FlakyBase { image: MemoryImage::new(b"GOLDEN".to_vec()), failing: failing.clone() };
+        let mut overlay = Overlay::new(Box::new(base));
+        overlay.write_byte(0, b'g').unwrap();
+        overlay.write_byte(PAGE_SIZE as u64 + 2, b'!').unwrap();
+
+        assert!(overlay.commit().is_err());
+        assert_eq!(overlay.base.len(), 6);
+        assert_eq!(overlay.base.read_byte(0).unwrap(), b'G');
+        assert_eq!(overlay.read_byte(0).unwrap(), b'g');            // Delta kept
+        assert_eq!(overlay.changed_pages(), 2);
+
+        overlay.discard().unwrap();
+        assert_eq!(overlay.read_byte(0).unwrap(), b'G');
+        assert_eq!(overlay.len(), 6);
+
+        overlay.write_byte(1, b'o').unwrap();
+        failing.set(false);
+        overlay.commit().unwrap();
+        assert_eq!(overlay.base.read_byte(1).unwrap(), b'o');
+    }
+
+    #[test]
+    fn test_overlay_discard() {
+        let mut overlay = Overlay::new(golden());
+        overlay.write_byte(2, b'X').unwrap();
+        overlay.write_byte(20, b'Y').unwrap();
+        overlay.discard().unwrap();
+        assert_eq!(overlay.len(), 6);
+        assert_eq!(overlay.read_byte(2).unwrap(), b'L');
+        assert_eq!(overlay.changed_pages(), 0);
+    }
+
+    #[test]
+    fn test_overlay_reopens_read_only_base_to_commit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("golden.img");
+        std::fs::write(&path, b"GOLDEN").unwrap();
+        let read_only = || {
+            let file = std::fs::File::open(&path).unwrap();
+            Box::new(PageCache::new(file, SyncPolicy::WriteBack).unwrap())
+        };
+
+        let mut overlay = Overlay::new(read_only());
+        overlay.write_byte(0, b'g').unwrap();
+        assert!(overlay.commit().is_err());         // Nothing says where the base lives
+
+        let mut overlay = Overlay::new(read_only());
+        overlay.set_base_file(path.clone());
+        overlay.write_byte(0, b'g').unwrap();
+        overlay.flush().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"GOLDEN");
+        overlay.commit().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"gOLDEN");
+    }
+
+    #[test]
+    fn test_delta_file_survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let delta = dir.path().join("work.ovl");
+        {
+            let mut overlay = Overlay::with_delta_file(golden(), delta.clone()).unwrap();
+            overlay.write_byte(5, b'?').unwrap();
+        }
+        assert!(delta.exists());
+
+        let mut overlay = Overlay::with_delta_file(golden(), delta.clone()).unwrap();
+        assert_eq!(overlay.read_byte(5).unwrap(), b'?');
+        overlay.discard().unwrap();
+        assert!(!delta.exists());
+
+        std::fs::write(&delta, b"junk").unwrap();
+        assert!(Overlay::with_delta_file(golden(), delta).is_err());
+    }
+}
@@ -1,24 +1,42 @@
 // storage_mount.rs - File mounting service for Storage device
 //
-// Port 0x0D: Filename char (write only)
+// Port 0x0D: Filename char (write) / Result byte (read)
 // Port 0x0E: Control (write only)
 // Port 0x0F: Status (read only)
 //
 // Control commands:
-//   0x01: Mount (open file with accumulated filename)
+//   0x01: Mount (open file with accumulated filename, creating it)
 //   0x02: Unmount
 //   0x03: Query mount status
+//   0x04: Mount an existing file (not found if missing)
+//   0x05: Mount an existing file read-only
+//   0x06: Test whether the file exists (never creates it)
+//   0x07: Query size (result: 4 bytes, little-endian)
+//   0x08: Delete
+//   0x09: Rename from (remembers the name; file must exist)
+//   0x0A: Rename to (renames the 0x09 file to the accumulated name)
+//   0x0B: Directory first (rewind the listing, load the first entry)
+//   0x0C: Directory next (result: name, 0x00, 4 size bytes little-endian)
+//   0x0D: Mount an existing file as a copy-on-write overlay (writes stay in
+//         memory until storage control 0x04 commits them)
+//   0x10-0x13: Target unit 0-3 for the commands above (default 0)
+//
+// Reads of port 0x0D return the last result a byte at a time, then 0x00.
+// The directory lists files with valid names only, sorted.
 //
 // Status codes:
-//   0x00: OK / Mounted
+//   0x00: OK / Mounted / Exists
 //   0x01: File not found (or error opening)
 //   0x02: Invalid filename
+//   0x03: File already exists (rename target)
+//   0x04: File in use (mounted on a unit, or on another unit when mounting)
+//   0x05: No more directory entries
 //   0xFF: Busy (not used, but reserved)
 
 use crate::io::IoDevice;
-use super::storage::Storage;
+use super::storage::{MountMode, Storage, UNITS};
 use std::cell::RefCell;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 pub struct StorageMount {
@@ -26,6 +44,12 @@ pub struct StorageMount {
     base_path: PathBuf,
     filename_buffer: Vec<u8>,
     status: u8,
+    unit: usize,
+    result: Vec<u8>,
+    result_pos: usize,
+    rename_from: Option<PathBuf>,
+    directory: Vec<(String, u32)>,
+    directory_pos: usize,
 }
 
 impl StorageMount {
@@ -35,43 +59,148 @@ impl StorageMount {
             base_path,
             filename_buffer: Vec::with_capacity(13),  // 8.3 + null
             status: 0x00,
+            unit: 0,
+            result: Vec::new(),
+            result_pos: 0,
+            rename_from: None,
+            directory: Vec::new(),
+            directory_pos: 0,
+        }
+    }
+
+    /// Full path of the accumulated filename, None (status set) if invalid
+    fn take_path(&mut self) -> Option<PathBuf> {
+        take_path(&mut self.filename_buffer, &self.base_path, &mut self.status)
+    }
+
+    fn set_result(&mut self, bytes: Vec<u8>) {
+        self.result = bytes;
+        self.result_pos = 0;
+    }
+
+    /// File size, preferring a mounted unit's view (it may not be flushed)
+    fn size_of(&self, path: &Path) -> Option<u32> {
+        if let Some(size) = self.storage.borrow().mounted_size(path) {
+            return Some(size);
+        }
+        let metadata = std::fs::metadata(path).ok().filter(|m| m.is_file())?;
+        Some(metadata.len().min(u32::MAX as u64) as u32)
+    }
+
+    fn do_exists(&mut self) {
+        let Some(path) = self.take_path() else { return };
+        self.status = if self.size_of(&path).is_some() { 0x00 } else { 0x01 };
+    }
+
+    fn do_size(&mut self) {
+        let Some(path) = self.take_path() else { return };
+        match self.size_of(&path) {
+            Some(size) => {
+                self.set_result(size.to_le_bytes().to_vec());
+                self.status = 0x00;
+            }
+            None => {
+                self.set_result(Vec::new());
+                self.status = 0x01;
+            }
         }
     }
 
-    fn do_mount(&mut self) {
-        // Build filename from buffer
-        let filename: String = self.filename_buffer.iter()
-            .take_while(|&&c| c != 0)
-            .map(|&c| c as char)
+    fn do_delete(&mut self) {
+        let Some(path) = self.take_path() else { return };
+        self.status = if self.storage.borrow().mounted_size(&path).is_some() {
+            0x04  // In use
+        } else if std::fs::metadata(&path).is_ok_and(|m| m.is_file()) && std::fs::remove_file(&path).is_ok() {
+            0x00
+        } else {
+            0x01
+        };
+    }
+
+    fn do_rename_from(&mut self) {
+        self.rename_from = None;
+        let Some(path) = self.take_path() else { return };
+        if self.size_of(&path).is_some() {
+            self.rename_from = Some(path);
+            self.status = 0x00;
+        } else {
+            self.status = 0x01;
+        }
+    }
+
+    fn do_rename_to(&mut self) {
+        let Some(to) = self.take_path() else { return };
+        let Some(from) = self.rename_from.take() else {
+            self.status = 0x01;  // Nothing to rename
+            return;
+        };
+        let storage = self.storage.borrow();
+        self.status = if storage.mounted_size(&from).is_some() || storage.mounted_size(&to).is_some() {
+            0x04  // In use
+        } else if to.exists() {
+            0x03  // Already exists
+        } else if std::fs::rename(&from, &to).is_ok() {
+            0x00
+        } else {
+            0x01
+        };
+    }
+
+    fn do_directory_first(&mut self) {
+        let mut entries: Vec<(String, u32)> = std::fs::read_dir(&self.base_path)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                let valid = filename(name.as_bytes()).is_some_and(|n| n == name);
+                if !valid {
+                    return None;
+                }
+                let size = self.size_of(&entry.path())?;
+                Some((name, size))
+            })
             .collect();
+        entries.sort();
+        self.directory = entries;
+        self.directory_pos = 0;
+        self.do_directory_next();
+    }
 
-        // Validate
-        if filename.is_empty() || filename.len() > 12 {
-            self.status = 0x02;  // Invalid
+    fn do_directory_next(&mut self) {
+        let Some((name, size)) = self.directory.get(self.directory_pos) else {
+            self.set_result(Vec::new());
+            self.status = 0x05;  // No more entries
             return;
-        }
+        };
+        let mut entry = name.as_bytes().to_vec();
+        entry.push(0x00);
+        entry.extend_from_slice(&size.to_le_bytes());
+        self.directory_pos += 1;
+        self.set_result(entry);
+        self.status = 0x00;
+    }
 
-        // Allow only safe characters
-        if !filename.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_') {
-            self.status = 0x02;  // Invalid
+    fn do_mount(&mut self, mode: MountMode) {
+        let Some(path) = self.take_path() else { return };
+        if self.storage.borrow().mounted_unit(&path).is_some_and(|unit| unit != self.unit) {
+            self.status = 0x04;  // Mounted on another unit
             return;
         }
 
-        let path = self.base_path.join(&filename);
-        
-        match self.storage.borrow_mut().mount(&path) {
+        match self.storage.borrow_mut().mount_unit_mode(self.unit, &path, mode) {
             Ok(()) => self.status = 0x00,
             Err(_) => self.status = 0x01,  // Not found / error
         }
     }
 
     fn do_unmount(&mut self) {
-        self.storage.borrow_mut().unmount();
+        self.storage.borrow_mut().unmount_unit(self.unit);
         self.status = 0x00;
     }
 
     fn do_query(&mut self) {
-        self.status = if self.storage.borrow().is_mounted() {
+        self.status = if self.storage.borrow().is_unit_mounted(self.unit) {
             0x00  // Mounted
         } else {
             0x01  // Not mounted
@@ -79,9 +208,44 @@ impl StorageMount {
     }
 }
 
+/// Filename from a mount service buffer, if it is a plain 8.3-ish name
+/// (1-12 of A-Z, 0-9, '.', '-', '_'; no paths, not "." or "..")
+pub(crate) fn filename(buffer: &[u8]) -> Option<String> {
+    let filename: String = buffer.iter()
+        .take_while(|&&c| c != 0)
+        .map(|&c| c as char)
+        .collect();
+
+    if filename.is_empty() || filename.len() > 12 || filename == "." || filename == ".." {
+        return None;
+    }
+
+    // Allow only safe characters
+    if !filename.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_') {
+        return None;
+    }
+    Some(filename)
+}
+
+/// Take the name accumulated in a mount service's `buffer` (leaving it
+/// empty) as a path in `base_path`. An invalid name sets `status` to 0x02.
+pub(crate) fn take_path(buffer: &mut Vec<u8>, base_path: &Path, status: &mut u8) -> Option<PathBuf> {
+    let name = filename(buffer);
+    buffer.clear();
+    if name.is_none() {
+        *status = 0x02;  // Invalid
+    }
+    name.map(|name| base_path.join(name))
+}
+
 impl IoDevice for StorageMount {
     fn read(&mut self, port: u8) -> u8 {
         match port {
+            0x0D => {
+                let value = self.result.get(self.result_pos).copied().unwrap_or(0x00);
+                self.result_pos = (self.result_pos + 1).min(self.result.len());
+                value
+            }
             0x0F => self.status,
             _ => 0xFF,
         }
@@ -98,12 +262,27 @@ impl IoDevice for StorageMount {
             }
             0x0E => {  // Control
                 match value {
-                    0x01 => {
-                        self.do_mount();
-                        self.filename_buffer.clear();
+                    0x01 | 0x04 | 0x05 | 0x0D => {
+                        let mode = match value {
+                            0x01 => MountMode::Create,
+                            0x04 => MountMode::Existing,
+                            0x05 => MountMode::ReadOnly,
+                            _ => MountMode::Overlay,
+                        };
+                        self.do_mount(mode);
                     }
                     0x02 => self.do_unmount(),
                     0x03 => self.do_query(),
+                    0x06 => self.do_exists(),
+                    0x07 => self.do_size(),
+                    0x08 => self.do_delete(),
+                    0x09 => self.do_rename_from(),
+                    0x0A => self.do_rename_to(),
+                    0x0B => self.do_directory_first(),
+                    0x0C => self.do_directory_next(),
+                    0x10..=0x1F if ((value & 0x0F) as usize) < UNITS => {
+                        self.unit = (value & 0x0F) as usize;
+                    }
                     _ => {}
                 }
             }
@@ -115,6 +294,7 @@ impl IoDevice for StorageMount {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::io::devices::storage::ERR_WRITE_PROTECT;
     use std::fs::File;
     use std::io::Write;
 
@@ -170,6 +350,11 @@ mod tests {
         // Empty filename
         mount.write(0x0E, 0x01);
         assert_eq!(mount.read(0x0F), 0x02);  // Invalid
+
+        // The directory itself and its parent
+        assert_eq!(command(&mut mount, b".", 0x05), 0x02);
+        assert_eq!(command(&mut mount, b"..", 0x05), 0x02);
+        assert_eq!(command(&mut mount, b"..", 0x07), 0x02);
     }
 
     #[test]
@@ -212,4 +397,137 @@ mod tests {
         
         assert_eq!(mount.read(0x0F), 0x02);  // Invalid
     }
+
+    #[test]
+    fn test_target_unit() {
+        let (_dir, storage, mut mount) = setup();
+
+        mount.write(0x0E, 0x12);            // Unit 2
+        for c in b"TWO.BIN" {
+            mount.write(0x0D, *c);
+        }
+        mount.write(0x0E, 0x01);
+        assert_eq!(mount.read(0x0F), 0x00);
+        assert!(storage.borrow().is_unit_mounted(2));
+        assert!(!storage.borrow().is_unit_mounted(0));
+
+        mount.write(0x0E, 0x10);            // Back to unit 0: not mounted
+        mount.write(0x0E, 0x03);
+        assert_eq!(mount.read(0x0F), 0x01);
+        assert_eq!(command(&mut mount, b"TWO.BIN", 0x04), 0x04);    // Already on unit 2
+        assert!(!storage.borrow().is_unit_mounted(0));
+        mount.write(0x0E, 0x12);
+        mount.write(0x0E, 0x02);
+        assert!(!storage.borrow().is_unit_mounted(2));
+    }
+
+    #[test]
+    fn test_mount_existing_and_read_only() {
+        let (dir, storage, mut mount) = setup();
+
+        for c in b"NEW.BIN" {
+            mount.write(0x0D, *c);
+        }
+        mount.write(0x0E, 0x04);            // Existing only
+        assert_eq!(mount.read(0x0F), 0x01);
+        assert!(!dir.path().join("NEW.BIN").exists());
+
+        File::create(dir.path().join("ROM.BIN")).unwrap().write_all(&[0x55]).unwrap();
+        for c in b"ROM.BIN" {
+            mount.write(0x0D, *c);
+        }
+        mount.write(0x0E, 0x05);            // Read-only
+        assert_eq!(mount.read(0x0F), 0x00);
+        storage.borrow_mut().write(0x0B, 0xAA);
+        assert_eq!(storage.borrow_mut().read(0x05), ERR_WRITE_PROTECT);
+    }
+
+    fn command(mount: &mut StorageMount, name: &[u8], control: u8) -> u8 {
+        for c in name {
+            mount.write(0x0D, *c);
+        }
+        mount.write(0x0E, control);
+        mount.read(0x0F)
+    }
+
+    fn result(mount: &mut StorageMount, count: usize) -> Vec<u8> {
+        (0..count).map(|_| mount.read(0x0D)).collect()
+    }
+
+    #[test]
+    fn test_exists_size_delete() {
+        let (dir, storage, mut mount) = setup();
+        std::fs::write(dir.path().join("DATA.BIN"), [0u8; 300]).unwrap();
+
+        assert_eq!(command(&mut mount, b"NONE.BIN", 0x06), 0x01);
+        assert!(!dir.path().join("NONE.BIN").exists());
+        assert_eq!(command(&mut mount, b"DATA.BIN", 0x06), 0x00);
+        assert_eq!(command(&mut mount, b"DATA.BIN", 0x07), 0x00);
+        assert_eq!(result(&mut mount, 5), vec![0x2C, 0x01, 0x00, 0x00, 0x00]);
+
+        // Mounted files can't be deleted, and report unflushed growth
+        assert_eq!(command(&mut mount, b"DATA.BIN", 0x04), 0x00);
+        storage.borrow_mut().write(0x0A, 0x01);     // Address 010000
+        storage.borrow_mut().write(0x0B, 0xAA);
+        assert_eq!(command(&mut mount, b"DATA.BIN", 0x07), 0x00);
+        assert_eq!(result(&mut mount, 4), vec![0x01, 0x00, 0x01, 0x00]);
+        assert_eq!(command(&mut mount, b"DATA.BIN", 0x08), 0x04);
+
+        mount.write(0x0E, 0x02);
+        assert_eq!(command(&mut mount, b"DATA.BIN", 0x08), 0x00);
+        assert!(!dir.path().join("DATA.BIN").exists());
+        assert_eq!(command(&mut mount, b"DATA.BIN", 0x08), 0x01);
+        assert_eq!(command(&mut mount, b"../x", 0x08), 0x02);
+    }
+
+    #[test]
+    fn test_rename() {
+        let (dir, _storage, mut mount) = setup();
+        std::fs::write(dir.path().join("OLD.TXT"), b"hi").unwrap();
+        std::fs::write(dir.path().join("TAKEN.TXT"), b"").unwrap();
+
+        assert_eq!(command(&mut mount, b"NEW.TXT", 0x0A), 0x01);    // No source yet
+        assert_eq!(command(&mut mount, b"MISSING.TXT", 0x09), 0x01);
+        assert_eq!(command(&mut mount, b"OLD.TXT", 0x09), 0x00);
+        assert_eq!(command(&mut mount, b"TAKEN.TXT", 0x0A), 0x03);
+        assert!(dir.path().join("OLD.TXT").exists());
+
+        assert_eq!(command(&mut mount, b"OLD.TXT", 0x09), 0x00);
+        assert_eq!(command(&mut mount, b"NEW.TXT", 0x0A), 0x00);
+        assert_eq!(std::fs::read(dir.path().join("NEW.TXT")).unwrap(), b"hi");
+        assert!(!dir.path().join("OLD.TXT").exists());
+    }
+
+    #[test]
+    fn test_directory_listing() {
+        let (dir, _storage, mut mount) = setup();
+        std::fs::write(dir.path().join("B.BIN"), [0u8; 2]).unwrap();
+        std::fs::write(dir.path().join("A.COM"), [0u8; 1]).unwrap();
+        std::fs::write(dir.path().join("long name.txt"), b"").unwrap();   // Not a valid name
+        std::fs::create_dir(dir.path().join("SUB")).unwrap();
+
+        mount.write(0x0E, 0x0B);
+        assert_eq!(mount.read(0x0F), 0x00);
+        assert_eq!(result(&mut mount, 10), b"A.COM\0\x01\0\0\0");
+        mount.write(0x0E, 0x0C);
+        assert_eq!(mount.read(0x0F), 0x00);
+        assert_eq!(result(&mut mount, 11), b"B.BIN\0\x02\0\0\0\0");   // Then 0x00
+        mount.write(0x0E, 0x0C);
+        assert_eq!(mount.read(0x0F), 0x05);
+
+        mount.write(0x0E, 0x0B);                    // Rewinds
+        assert_eq!(result(&mut mount, 1), b"A");
+    }
+
+    #[test]
+    fn test_overlay_mount() {
+        let (dir, storage, mut mount) = setup();
+        std::fs::write(dir.path().join("GOLDEN.IMG"), b"AB").unwrap();
+
+        assert_eq!(command(&mut mount, b"NONE.IMG", 0x0D), 0x01);
+        assert_eq!(command(&mut mount, b"GOLDEN.IMG", 0x0D), 0x00);
+        storage.borrow_mut().write(0x0B, b'Z');
+        mount.write(0x0E, 0x02);
+        assert_eq!(std::fs::read(dir.path().join("GOLDEN.IMG")).unwrap(), b"AB");
+    }
 }
@@ -0,0 +1,357 @@
+// time.rs - System time and uptime device
+//
+// Port 0x60: Second (0-59)        - reading latches all seven time fields
+// Port 0x61: Minute (0-59)
+// Port 0x62: Hour (0-23)
+// Port 0x63: Day (1-31)
+// Port 0x64: Month (1-12)
+// Port 0x65: Year (since 1900)
+// Port 0x66: Day of week (0=Sun)
+//
+// Port 0x68: Uptime seconds (0-59) - reading latches all four uptime fields
+// Port 0x69: Uptime minutes (0-59)
+// Port 0x6A: Uptime hours (0-23)
+// Port 0x6B: Uptime days (0-255, saturates)
+//
+// All ports are read-only. Time is UTC.
+//
+// The clock source is injectable: HostClock for normal runs, FixedClock for
+// tests. Uptime comes either from the clock source or from emulated cycles.
+
+use crate::io::{Clocked, IoDevice};
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Default 8080 clock rate (2.0 MHz)
+pub const DEFAULT_CPU_HZ: u64 = 2_000_000;
+
+/// Where the device gets wall-clock time and uptime from
+pub trait ClockSource {
+    /// Seconds since 1970-01-01 00:00:00 UTC
+    fn unix_seconds(&self) -> u64;
+    /// Seconds since the clock was started
+    fn uptime_seconds(&self) -> u64;
+}
+
+impl<C: ClockSource + ?Sized> ClockSource for Rc<C> {
+    fn unix_seconds(&self) -> u64 {
+        (**self).unix_seconds()
+    }
+
+    fn uptime_seconds(&self) -> u64 {
+        (**self).uptime_seconds()
+    }
+}
+
+/// Host wall clock
+pub struct HostClock {
+    started: Instant,
+}
+
+impl HostClock {
+    pub fn new() -> Self {
+        HostClock {
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Default for HostClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClockSource for HostClock {
+    fn unix_seconds(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+
+    fn uptime_seconds(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+}
+
+/// Fixed or simulated clock for tests. Wrap in an Rc to advance it
+/// while the device holds it.
+pub struct FixedClock {
+    unix: Cell<u64>,
+    uptime: Cell<u64>,
+}
+
+impl FixedClock {
+    pub fn new(unix_seconds: u64) -> Self {
+        FixedClock {
+            unix: Cell::new(unix_seconds),
+            uptime: Cell::new(0),
+        }
+    }
+
+    pub fn set(&self, unix_seconds: u64) {
+        self.unix.set(unix_seconds);
+    }
+
+    /// Move both wall time and uptime forward
+    pub fn advance(&self, seconds: u64) {
+        self.unix.set(self.unix.get() + seconds);
+        self.uptime.set(self.uptime.get() + seconds);
+    }
+}
+
+impl ClockSource for FixedClock {
+    fn unix_seconds(&self) -> u64 {
+        self.unix.get()
+    }
+
+    fn uptime_seconds(&self) -> u64 {
+        self.uptime.get()
+    }
+}
+
+/// Where uptime comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UptimeMode {
+    /// Uptime from the clock source (wall time)
+    Clock,
+    /// Uptime from emulated cycles at the given CPU clock rate.
+    /// The device must be attached to the CPU with `attach_clocked`.
+    Cycles { hz: u64 },
+}
+
+/// Broken-down calendar time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub second: u8,
+    pub minute: u8,
+    pub hour: u8,
+    pub day: u8,
+    pub month: u8,
+    pub year: u16,      // Full year, e.g. 2025
+    pub weekday: u8,    // 0 = Sunday
+}
+
+impl DateTime {
+    /// Convert seconds since the Unix epoch to UTC calendar time
+    pub fn from_unix(seconds: u64) -> Self {
+        let days = (seconds / 86_400) as i64;
+        let secs_of_day = seconds % 86_400;
+
+        // Civil-from-days (Howard Hinnant's algorithm)
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        DateTime {
+            second: (secs_of_day % 60) as u8,
+            minute: ((secs_of_day / 60) % 60) as u8,
+            hour: (secs_of_day / 3600) as u8,
+            day: day as u8,
+            month: month as u8,
+            year: year as u16,
+            weekday: ((days + 4) % 7) as u8,  // 1970-01-01 was a Thursday
+        }
+    }
+}
+
+pub struct TimeDevice {
+    clock: Box<dyn ClockSource>,
+    uptime_mode: UptimeMode,
+    cycles: u64,
+    latched_time: Option<DateTime>,
+    latched_uptime: Option<[u8; 4]>,
+}
+
+impl TimeDevice {
+    pub fn new(clock: Box<dyn ClockSource>) -> Self {
+        TimeDevice {
+            clock,
+            uptime_mode: UptimeMode::Clock,
+            cycles: 0,
+            latched_time: None,
+            latched_uptime: None,
+        }
+    }
+
+    /// Time device on the host wall clock
+    pub fn host() -> Self {
+        Self::new(Box::new(HostClock::new()))
+    }
+
+    pub fn set_uptime_mode(&mut self, mode: UptimeMode) {
+        self.uptime_mode = mode;
+    }
+
+    /// Current uptime in seconds, per the uptime mode
+    pub fn uptime_seconds(&self) -> u64 {
+        match self.uptime_mode {
+            UptimeMode::Clock => self.clock.uptime_seconds(),
+            UptimeMode::Cycles { hz } => self.cycles / hz.max(1),
+        }
+    }
+
+    fn latch_time(&mut self) -> DateTime {
+        let now = DateTime::from_unix(self.clock.unix_seconds());
+        self.latched_time = Some(now);
+        now
+    }
+
+    fn latch_uptime(&mut self) -> [u8; 4] {
+        let up = self.uptime_seconds();
+        let fields = [
+            (up % 60) as u8,
+            ((up / 60) % 60) as u8,
+            ((up / 3600) % 24) as u8,
+            (up / 86_400).min(255) as u8,
+        ];
+        self.latched_uptime = Some(fields);
+        fields
+    }
+
+    fn time_field(&mut self, port: u8) -> u8 {
+        let t = match self.latched_time {
+            Some(t) => t,
+            None => self.latch_time(),
+        };
+        match port {
+            0x60 => t.second,
+            0x61 => t.minute,
+            0x62 => t.hour,
+            0x63 => t.day,
+            0x64 => t.month,
+            0x65 => t.year.saturating_sub(1900).min(255) as u8,
+            0x66 => t.weekday,
+            _ => 0xFF,
+        }
+    }
+
+    fn uptime_field(&mut self, port: u8) -> u8 {
+        let fields = match self.latched_uptime {
+            Some(f) => f,
+            None => self.latch_uptime(),
+        };
+        fields[(port - 0x68) as usize]
+    }
+}
+
+impl IoDevice for TimeDevice {
+    fn read(&mut self, port: u8) -> u8 {
+        match port {
+            0x60 => {
+                self.latch_time();
+                self.time_field(port)
+            }
+            0x61..=0x66 => self.time_field(port),
+            0x68 => {
+                self.latch_uptime();
+                self.uptime_field(port)
+            }
+            0x69..=0x6B => self.uptime_field(port),
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, _port: u8, _value: u8) {
+        // Read-only
+    }
+}
+
+impl Clocked for TimeDevice {
+    fn tick(&mut self, cycles: u64) {
+        self.cycles = self.cycles.wrapping_add(cycles);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2025-12-19 14:32:07 UTC, a Friday
+    const FRI_2025_12_19: u64 = 1_766_154_727;
+
+    fn read_time(dev: &mut TimeDevice) -> [u8; 7] {
+        let mut fields = [0u8; 7];
+        for (i, f) in fields.iter_mut().enumerate() {
+            *f = dev.read(0x60 + i as u8);
+        }
+        fields
+    }
+
+    #[test]
+    fn test_from_unix_epoch() {
+        let t = DateTime::from_unix(0);
+        assert_eq!((t.year, t.month, t.day), (1970, 1, 1));
+        assert_eq!((t.hour, t.minute, t.second), (0, 0, 0));
+        assert_eq!(t.weekday, 4);  // Thursday
+    }
+
+    #[test]
+    fn test_from_unix_leap_day() {
+        // 2024-02-29 12:00:00 UTC, a Thursday
+        let t = DateTime::from_unix(1_709_208_000);
+        assert_eq!((t.year, t.month, t.day), (2024, 2, 29));
+        assert_eq!(t.hour, 12);
+        assert_eq!(t.weekday, 4);
+    }
+
+    #[test]
+    fn test_read_fixed_time() {
+        let mut dev = TimeDevice::new(Box::new(FixedClock::new(FRI_2025_12_19)));
+
+        assert_eq!(read_time(&mut dev), [7, 32, 14, 19, 12, 125, 5]);
+    }
+
+    #[test]
+    fn test_latch_on_second_read() {
+        let clock = Rc::new(FixedClock::new(FRI_2025_12_19 + 52));  // 14:32:59
+        let mut dev = TimeDevice::new(Box::new(Rc::clone(&clock)));
+
+        assert_eq!(dev.read(0x60), 59);
+        clock.advance(1);                       // Rolls over to 14:33:00
+        assert_eq!(dev.read(0x61), 32);         // Still latched minute
+
+        assert_eq!(dev.read(0x60), 0);          // Re-latch
+        assert_eq!(dev.read(0x61), 33);
+    }
+
+    #[test]
+    fn test_uptime_from_clock() {
+        let clock = Rc::new(FixedClock::new(0));
+        let mut dev = TimeDevice::new(Box::new(Rc::clone(&clock)));
+
+        clock.advance(86_400 + 3 * 3600 + 4 * 60 + 5);
+        assert_eq!(dev.read(0x68), 5);
+        assert_eq!(dev.read(0x69), 4);
+        assert_eq!(dev.read(0x6A), 3);
+        assert_eq!(dev.read(0x6B), 1);
+    }
+
+    #[test]
+    fn test_uptime_from_cycles() {
+        let mut dev = TimeDevice::new(Box::new(FixedClock::new(0)));
+        dev.set_uptime_mode(UptimeMode::Cycles { hz: DEFAULT_CPU_HZ });
+
+        dev.tick(DEFAULT_CPU_HZ * 61);
+        assert_eq!(dev.read(0x68), 1);
+        assert_eq!(dev.read(0x69), 1);
+    }
+
+    #[test]
+    fn test_writes_ignored_and_unused_ports() {
+        let mut dev = TimeDevice::new(Box::new(FixedClock::new(FRI_2025_12_19)));
+
+        dev.write(0x60, 0x00);
+        assert_eq!(dev.read(0x60), 7);
+        assert_eq!(dev.read(0x67), 0xFF);
+        assert_eq!(dev.read(0x6F), 0xFF);
+    }
+}
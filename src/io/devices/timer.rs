@@ -5,8 +5,12 @@
 // Port 0x32: Status/Control
 //            Read:  bit 0 = enabled, bit 1 = interrupt pending
 //            Write: bit 0 = enable, bit 1 = acknowledge interrupt
+//
+// The interrupt line is an InterruptSource: the CPU has it attached first
+// on RST 7, and a board can detach it and wire it to a PIC instead.
+// Taking the interrupt acknowledges it.
 
-use crate::io::IoDevice;
+use crate::io::{InterruptSource, IoDevice};
 
 pub struct Timer {
     counter: u16,
@@ -52,6 +56,12 @@ impl Timer {
     }
 }
 
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl IoDevice for Timer {
     fn read(&mut self, port: u8) -> u8 {
         match port {
@@ -89,3 +99,14 @@ impl IoDevice for Timer {
         }
     }
 }
+
+impl InterruptSource for Timer {
+    fn interrupt_requested(&mut self) -> bool {
+        self.interrupt_pending
+    }
+
+    fn acknowledge(&mut self) -> u16 {
+        self.interrupt_pending = false;
+        0x0038  // RST 7
+    }
+}
@@ -0,0 +1,373 @@
+// usart.rs - Intel 8251 USART
+//
+// Port base+0: RX data (read) / TX data (write)         (C/D = 0)
+// Port base+1: Status (read) / Mode or command (write)  (C/D = 1)
+//
+// Control writes follow the 8251 sequence:
+//   After reset:    mode instruction
+//   Sync mode only: one or two sync characters
+//   Then:           command instructions, until internal reset
+//
+// Mode instruction (async, bits 0-1 != 00):
+//   Bits 0-1: Baud factor - 01 x1, 10 x16, 11 x64
+//   Bits 2-3: Character length - 00 5, 01 6, 10 7, 11 8 bits
+//   Bit 4:    Parity enable
+//   Bit 5:    Even parity
+//   Bits 6-7: Stop bits
+// Mode instruction (sync, bits 0-1 = 00):
+//   Bit 6: External sync detect
+//   Bit 7: Single sync character (else two)
+//
+// Command instruction:
+//   Bit 0: TxEN - transmit enable
+//   Bit 1: DTR
+//   Bit 2: RxE - receive enable
+//   Bit 3: SBRK - send break
+//   Bit 4: ER - error reset (clears PE/OE/FE)
+//   Bit 5: RTS
+//   Bit 6: IR - internal reset (back to expecting a mode instruction)
+//   Bit 7: EH - enter hunt mode (sync only)
+//
+// Status:
+//   Bit 0: TxRDY - transmit buffer empty
+//   Bit 1: RxRDY - character waiting
+//   Bit 2: TxEMPTY - transmitter idle
+//   Bit 3: PE - parity error
+//   Bit 4: OE - overrun error
+//   Bit 5: FE - framing error
+//   Bit 6: SYNDET/BRKDET (always 0)
+//   Bit 7: DSR
+//
+// Characters move instantly through a SerialBackend, the same kind of
+// host byte stream the 6850 uses. Data is masked to the character length.
+// The wire carries no parity or framing, so PE/OE/FE only come from
+// inject_errors(); ER clears them. Sync hunt is not modeled.
+//
+// Interrupt outputs: the RxRDY pin is RxRDY while RxE is on; the TxRDY pin
+// is TxRDY while TxEN is on (CTS is taken as asserted). Either can drive
+// a CPU interrupt through InterruptSource.
+
+use crate::io::serial::SerialBackend;
+use crate::io::{InterruptSource, IoDevice};
+
+pub const STATUS_TXRDY: u8 = 0x01;
+pub const STATUS_RXRDY: u8 = 0x02;
+pub const STATUS_TXEMPTY: u8 = 0x04;
+pub const STATUS_PE: u8 = 0x08;
+pub const STATUS_OE: u8 = 0x10;
+pub const STATUS_FE: u8 = 0x20;
+pub const STATUS_DSR: u8 = 0x80;
+const ERROR_BITS: u8 = STATUS_PE | STATUS_OE | STATUS_FE;
+
+const COMMAND_TXEN: u8 = 0x01;
+const COMMAND_DTR: u8 = 0x02;
+const COMMAND_RXE: u8 = 0x04;
+const COMMAND_ER: u8 = 0x10;
+const COMMAND_RTS: u8 = 0x20;
+const COMMAND_IR: u8 = 0x40;
+
+/// Which control byte the chip expects next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ControlState {
+    Mode,
+    Sync1,
+    Sync2,
+    Command,
+}
+
+pub struct Usart8251 {
+    base: u8,
+    backend: Box<dyn SerialBackend>,
+    state: ControlState,
+    mode: u8,
+    sync_chars: [u8; 2],
+    command: u8,
+    rx_data: Option<u8>,
+    errors: u8,
+    dsr: bool,
+    rx_interrupt: bool,
+    tx_interrupt: bool,
+    vector: u16,
+}
+
+impl Usart8251 {
+    /// USART on `base`/`base + 1`, talking to `backend`
+    pub fn new(base: u8, backend: Box<dyn SerialBackend>) -> Self {
+        Usart8251 {
+            base,
+            backend,
+            state: ControlState::Mode,
+            mode: 0,
+            sync_chars: [0; 2],
+            command: 0,
+            rx_data: None,
+            errors: 0,
+            dsr: true,
+            rx_interrupt: true,
+            tx_interrupt: false,
+            vector: 0x0038,
+        }
+    }
+
+    /// Which pins interrupt the CPU, and the RST vector (0-7) they call.
+    /// Default: RxRDY only, RST 7.
+    pub fn set_interrupts(&mut self, rst: u8, rx_ready: bool, tx_ready: bool) {
+        self.vector = (rst as u16 & 0x07) * 8;
+        self.rx_interrupt = rx_ready;
+        self.tx_interrupt = tx_ready;
+    }
+
+    /// Set the error flags a real line would raise (PE/OE/FE bits)
+    pub fn inject_errors(&mut self, flags: u8) {
+        self.errors |= flags & ERROR_BITS;
+    }
+
+    /// Drive the DSR input (status bit 7)
+    pub fn set_dsr(&mut self, asserted: bool) {
+        self.dsr = asserted;
+    }
+
+    pub fn mode(&self) -> u8 {
+        self.mode
+    }
+
+    pub fn sync_chars(&self) -> [u8; 2] {
+        self.sync_chars
+    }
+
+    /// DTR output pin (asserted = true)
+    pub fn dtr(&self) -> bool {
+        self.command & COMMAND_DTR != 0
+    }
+
+    /// RTS output pin (asserted = true)
+    pub fn rts(&self) -> bool {
+        self.command & COMMAND_RTS != 0
+    }
+
+    /// RxRDY output pin
+    pub fn rx_ready_pin(&mut self) -> bool {
+        self.poll_rx();
+        self.rx_data.is_some()
+    }
+
+    /// TxRDY output pin
+    pub fn tx_ready_pin(&self) -> bool {
+        self.state == ControlState::Command && self.command & COMMAND_TXEN != 0
+    }
+
+    fn char_mask(&self) -> u8 {
+        let bits = 5 + ((self.mode >> 2) & 0x03);
+        (0xFFu16 >> (8 - bits)) as u8
+    }
+
+    fn rx_enabled(&self) -> bool {
+        self.state == ControlState::Command && self.command & COMMAND_RXE != 0
+    }
+
+    fn poll_rx(&mut self) {
+        if self.rx_enabled() && self.rx_data.is_none() {
+            let mask = self.char_mask();
+            self.rx_data = self.backend.read_byte().map(|b| b & mask);
+        }
+    }
+
+    fn write_control(&mut self, value: u8) {
+        match self.state {
+            ControlState::Mode => {
+                self.mode = value;
+                self.state = if value & 0x03 == 0 {
+                    ControlState::Sync1
+                } else {
+                    ControlState::Command
+                };
+            }
+            ControlState::Sync1 => {
+                self.sync_chars[0] = value;
+                // Bit 7 of a sync mode byte = single sync character
+                self.state = if self.mode & 0x80 != 0 {
+                    ControlState::Command
+                } else {
+                    ControlState::Sync2
+                };
+            }
+            ControlState::Sync2 => {
+                self.sync_chars[1] = value;
+                self.state = ControlState::Command;
+            }
+            ControlState::Command if value & COMMAND_IR != 0 => self.reset(),
+            ControlState::Command => {
+                if value & COMMAND_ER != 0 {
+                    self.errors = 0;
+                }
+                if value & COMMAND_RXE == 0 {
+                    self.rx_data = None;
+                }
+                self.command = value;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.state = ControlState::Mode;
+        self.command = 0;
+        self.rx_data = None;
+        self.errors = 0;
+    }
+
+    fn status(&mut self) -> u8 {
+        self.poll_rx();
+        let mut status = STATUS_TXRDY | STATUS_TXEMPTY | self.errors;
+        if self.rx_data.is_some() {
+            status |= STATUS_RXRDY;
+        }
+        if self.dsr {
+            status |= STATUS_DSR;
+        }
+        status
+    }
+}
+
+impl IoDevice for Usart8251 {
+    fn read(&mut self, port: u8) -> u8 {
+        match port.wrapping_sub(self.base) {
+            0 => {
+                self.poll_rx();
+                self.rx_data.take().unwrap_or(0x00)
+            }
+            1 => self.status(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) {
+        match port.wrapping_sub(self.base) {
+            0 if self.tx_ready_pin() => {
+                let value = value & self.char_mask();
+                self.backend.write_byte(value);
+            }
+            1 => self.write_control(value),
+            _ => {}
+        }
+    }
+}
+
+impl InterruptSource for Usart8251 {
+    fn interrupt_requested(&mut self) -> bool {
+        (self.rx_interrupt && self.rx_ready_pin()) || (self.tx_interrupt && self.tx_ready_pin())
+    }
+
+    fn acknowledge(&mut self) -> u16 {
+        self.vector
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::serial::BufferBackend;
+
+    const BASE: u8 = 0x04;
+
+    /// Async 8N1 x16, then TxEN + RxE + DTR + RTS
+    fn usart() -> (Usart8251, BufferBackend) {
+        let wire = BufferBackend::new();
+        let mut dev = Usart8251::new(BASE, Box::new(wire.clone()));
+        dev.write(BASE + 1, 0x4E);
+        dev.write(BASE + 1, 0x27);
+        (dev, wire)
+    }
+
+    #[test]
+    fn test_async_setup_and_transfer() {
+        let (mut dev, wire) = usart();
+        assert!(dev.dtr() && dev.rts());
+
+        assert_eq!(dev.read(BASE + 1), STATUS_TXRDY | STATUS_TXEMPTY | STATUS_DSR);
+        dev.write(BASE, b'H');
+        assert_eq!(wire.take_output(), b"H");
+
+        wire.push_input(b"i");
+        assert_eq!(dev.read(BASE + 1) & STATUS_RXRDY, STATUS_RXRDY);
+        assert_eq!(dev.read(BASE), b'i');
+        assert_eq!(dev.read(BASE + 1) & STATUS_RXRDY, 0);
+    }
+
+    #[test]
+    fn test_transmit_and_receive_need_enables() {
+        let wire = BufferBackend::new();
+        let mut dev = Usart8251::new(BASE, Box::new(wire.clone()));
+        dev.write(BASE + 1, 0x4E);          // Mode
+        dev.write(BASE + 1, 0x00);          // Command: nothing enabled
+
+        dev.write(BASE, b'X');
+        assert!(wire.take_output().is_empty());
+        wire.push_input(b"Y");
+        assert_eq!(dev.read(BASE + 1) & STATUS_RXRDY, 0);
+
+        dev.write(BASE + 1, 0x05);          // TxEN + RxE
+        assert_eq!(dev.read(BASE), b'Y');
+    }
+
+    #[test]
+    fn test_internal_reset_expects_mode_again() {
+        let (mut dev, wire) = usart();
+
+        dev.write(BASE + 1, 0x40);          // Internal reset
+        assert!(!dev.tx_ready_pin());
+        dev.write(BASE + 1, 0x4A);          // Mode: 7 bits x16
+        dev.write(BASE + 1, 0x01);          // Command: TxEN
+        assert_eq!(dev.mode(), 0x4A);
+        dev.write(BASE, 0xC1);
+        assert_eq!(wire.take_output(), vec![0x41]);  // Masked to 7 bits
+    }
+
+    #[test]
+    fn test_sync_mode_takes_sync_characters() {
+        let wire = BufferBackend::new();
+        let mut dev = Usart8251::new(BASE, Box::new(wire.clone()));
+
+        dev.write(BASE + 1, 0x0C);          // Sync, 8 bits, two sync chars
+        dev.write(BASE + 1, 0x16);
+        dev.write(BASE + 1, 0x17);
+        assert!(!dev.tx_ready_pin());       // Still no command
+        dev.write(BASE + 1, 0x01);
+        assert_eq!(dev.sync_chars(), [0x16, 0x17]);
+        assert!(dev.tx_ready_pin());
+
+        let mut single = Usart8251::new(BASE, Box::new(wire));
+        single.write(BASE + 1, 0x8C);       // Single sync char
+        single.write(BASE + 1, 0x16);
+        single.write(BASE + 1, 0x01);
+        assert!(single.tx_ready_pin());
+    }
+
+    #[test]
+    fn test_error_bits_and_error_reset() {
+        let (mut dev, _wire) = usart();
+
+        dev.inject_errors(STATUS_PE | STATUS_OE | STATUS_FE);
+        assert_eq!(dev.read(BASE + 1) & ERROR_BITS, ERROR_BITS);
+        dev.write(BASE + 1, 0x37);          // Command with ER
+        assert_eq!(dev.read(BASE + 1) & ERROR_BITS, 0);
+
+        dev.set_dsr(false);
+        assert_eq!(dev.read(BASE + 1) & STATUS_DSR, 0);
+    }
+
+    #[test]
+    fn test_interrupt_pins() {
+        let (mut dev, wire) = usart();
+
+        assert!(!dev.interrupt_requested());
+        wire.push_input(b"!");
+        assert!(dev.interrupt_requested());
+        assert_eq!(dev.acknowledge(), 0x0038);
+        dev.read(BASE);
+        assert!(!dev.interrupt_requested());
+
+        dev.set_interrupts(6, false, true);
+        assert!(dev.interrupt_requested());  // TxRDY with TxEN on
+        assert_eq!(dev.acknowledge(), 0x0030);
+    }
+}
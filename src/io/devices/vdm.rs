@@ -0,0 +1,269 @@
+// vdm.rs - Processor Technology VDM-1 style memory-mapped text display
+//
+// Screen RAM: 1KB at a configurable base (default 0xCC00), 16 rows of
+// 64 characters. Bits 0-6 are the character, bit 7 shows it inverted
+// (the usual cursor).
+//
+// Control port (default 0xC8, write only, reads 0xFF):
+//   Bits 0-3: Memory row shown on the top screen row (hardware scroll)
+//   Bits 4-7: Screen rows blanked from the top
+//
+// The screen is memory, so the device watches it as a bus master: about
+// 60 times a second of CPU time it copies the RAM and, if anything changed,
+// redraws. Rendering goes to a region of the host terminal (crossterm) or
+// nowhere (headless); either way screen_text() dumps what is shown.
+
+use crate::io::{BusMaster, Clocked, IoDevice};
+use crate::memory::Memory;
+use crossterm::cursor::MoveTo;
+use crossterm::queue;
+use crossterm::style::{Attribute, Print, SetAttribute};
+use std::io::{self, Write};
+
+pub const COLUMNS: usize = 64;
+pub const ROWS: usize = 16;
+pub const DEFAULT_BASE: u16 = 0xCC00;
+pub const DEFAULT_PORT: u8 = 0xC8;
+
+/// Cycles between refreshes: 60 Hz at 2 MHz
+const REFRESH_CYCLES: u64 = 2_000_000 / 60;
+
+pub struct VdmDisplay {
+    base: u16,
+    port: u8,
+    control: u8,
+    ram: Vec<u8>,
+    cycles: u64,
+    due: bool,
+    dirty: bool,
+    terminal: Option<(u16, u16)>,
+}
+
+impl VdmDisplay {
+    /// Headless display with screen RAM at `base`, control on `port`
+    pub fn new(base: u16, port: u8) -> Self {
+        VdmDisplay {
+            base,
+            port,
+            control: 0,
+            ram: vec![b' '; COLUMNS * ROWS],
+            cycles: 0,
+            due: true,
+            dirty: true,
+            terminal: None,
+        }
+    }
+
+    /// Draw into the host terminal with the top-left corner at (column, row)
+    pub fn set_terminal(&mut self, column: u16, row: u16) {
+        self.terminal = Some((column, row));
+        self.dirty = true;
+    }
+
+    pub fn control(&self) -> u8 {
+        self.control
+    }
+
+    /// Memory row shown on a screen row, None when blanked
+    fn memory_row(&self, row: usize) -> Option<usize> {
+        if row < (self.control >> 4) as usize {
+            return None;
+        }
+        Some((row + (self.control & 0x0F) as usize) % ROWS)
+    }
+
+    /// Screen cell as (character, inverted)
+    fn cell(&self, row: usize, column: usize) -> (char, bool) {
+        let Some(mem_row) = self.memory_row(row) else { return (' ', false) };
+        let byte = self.ram[mem_row * COLUMNS + column];
+        let c = byte & 0x7F;
+        let c = if (0x20..0x7F).contains(&c) { c as char } else { ' ' };
+        (c, byte & 0x80 != 0)
+    }
+
+    /// One screen row as text (inversion dropped)
+    pub fn row_text(&self, row: usize) -> String {
+        (0..COLUMNS).map(|column| self.cell(row, column).0).collect()
+    }
+
+    /// The whole screen, 16 lines of 64 characters separated by '\n'
+    pub fn screen_text(&self) -> String {
+        (0..ROWS).map(|row| self.row_text(row)).collect::<Vec<_>>().join("\n")
+    }
+
+    pub fn is_inverse(&self, row: usize, column: usize) -> bool {
+        self.cell(row, column).1
+    }
+
+    /// Copy the screen RAM now, redrawing if it changed
+    pub fn refresh(&mut self, memory: &mut dyn Memory) {
+        for (i, cell) in self.ram.iter_mut().enumerate() {
+            let value = memory.read(self.base.wrapping_add(i as u16));
+            if *cell != value {
+                *cell = value;
+                self.dirty = true;
+            }
+        }
+        if self.dirty {
+            self.dirty = false;
+            if let Some((column, row)) = self.terminal {
+                let _ = self.draw(&mut io::stdout(), column, row);
+            }
+        }
+    }
+
+    fn draw(&self, out: &mut impl Write, column: u16, row: u16) -> io::Result<()> {
+        for r in 0..ROWS {
+            queue!(out, MoveTo(column, row + r as u16))?;
+            let mut inverted = false;
+            for c in 0..COLUMNS {
+                let (ch, inverse) = self.cell(r, c);
+                if inverse != inverted {
+                    let attr = if inverse { Attribute::Reverse } else { Attribute::NoReverse };
+                    queue!(out, SetAttribute(attr))?;
+                    inverted = inverse;
+                }
+                queue!(out, Print(ch))?;
+            }
+            if inverted {
+                queue!(out, SetAttribute(Attribute::NoReverse))?;
+            }
+        }
+        out.flush()
+    }
+}
+
+impl Default for VdmDisplay {
+    fn default() -> Self {
+        Self::new(DEFAULT_BASE, DEFAULT_PORT)
+    }
+}
+
+impl IoDevice for VdmDisplay {
+    fn read(&mut self, _port: u8) -> u8 {
+        0xFF
+    }
+
+    fn write(&mut self, port: u8, value: u8) {
+        if port == self.port && value != self.control {
+            self.control = value;
+            self.dirty = true;
+        }
+    }
+}
+
+impl Clocked for VdmDisplay {
+    fn tick(&mut self, cycles: u64) {
+        self.cycles += cycles;
+        if self.cycles >= REFRESH_CYCLES {
+            self.cycles %= REFRESH_CYCLES;
+            self.due = true;
+        }
+    }
+}
+
+impl BusMaster for VdmDisplay {
+    fn service(&mut self, memory: &mut dyn Memory) -> u32 {
+        // The VDM-1 reads its own RAM; the CPU never waits
+        if self.due {
+            self.due = false;
+            self.refresh(memory);
+        }
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Intel8080;
+    use crate::memory::FlatMemory;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn poke_str(mem: &mut FlatMemory, addr: u16, text: &[u8]) {
+        for (i, &b) in text.iter().enumerate() {
+            mem.write(addr + i as u16, b);
+        }
+    }
+
+    #[test]
+    fn test_screen_text() {
+        let mut mem = FlatMemory::new();
+        for i in 0..(COLUMNS * ROWS) as u16 {
+            mem.write(DEFAULT_BASE + i, b' ');
+        }
+        poke_str(&mut mem, DEFAULT_BASE, b"HELLO");
+        poke_str(&mut mem, DEFAULT_BASE + 15 * 64 + 60, b"END\x01");
+
+        let mut vdm = VdmDisplay::default();
+        vdm.refresh(&mut mem);
+        let text = vdm.screen_text();
+        assert_eq!(text.lines().count(), ROWS);
+        assert!(text.lines().all(|l| l.len() == COLUMNS));
+        assert_eq!(vdm.row_text(0).trim_end(), "HELLO");
+        assert!(vdm.row_text(15).ends_with("END "));     // Control char shown blank
+    }
+
+    #[test]
+    fn test_inverse_bit() {
+        let mut mem = FlatMemory::new();
+        mem.write(DEFAULT_BASE + 3, b'_' | 0x80);
+        let mut vdm = VdmDisplay::default();
+        vdm.refresh(&mut mem);
+        assert!(vdm.is_inverse(0, 3));
+        assert!(!vdm.is_inverse(0, 2));
+        assert_eq!(vdm.row_text(0).chars().nth(3), Some('_'));
+    }
+
+    #[test]
+    fn test_scroll_and_blank() {
+        let mut mem = FlatMemory::new();
+        for row in 0..ROWS as u16 {
+            mem.write(DEFAULT_BASE + row * 64, b'A' + row as u8);
+        }
+        let mut vdm = VdmDisplay::default();
+        vdm.refresh(&mut mem);
+
+        vdm.write(DEFAULT_PORT, 0x03);      // Row 3 on top
+        assert!(vdm.row_text(0).starts_with('D'));
+        assert!(vdm.row_text(13).starts_with('A'));
+
+        vdm.write(DEFAULT_PORT, 0x23);      // And the top two rows blank
+        assert_eq!(vdm.row_text(1).trim(), "");
+        assert!(vdm.row_text(2).starts_with('F'));
+        assert_eq!(vdm.read(DEFAULT_PORT), 0xFF);
+    }
+
+    #[test]
+    fn test_terminal_draw() {
+        let mut mem = FlatMemory::new();
+        mem.write(DEFAULT_BASE, b'X' | 0x80);
+        let mut vdm = VdmDisplay::default();
+        vdm.refresh(&mut mem);
+
+        let mut out = Vec::new();
+        vdm.draw(&mut out, 0, 2).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("\x1b[3;1H\x1b[7mX\x1b[27m"));
+        assert!(out.contains("\x1b[18;1H"));
+    }
+
+    #[test]
+    fn test_cpu_writes_appear_after_refresh() {
+        // LXI H,CC00 / MVI M,'8' / INX H / MVI M,'0' / JMP $
+        let mut cpu = Intel8080::new();
+        cpu.load_program(&[0x21, 0x00, 0xCC, 0x36, b'8', 0x23, 0x36, b'0', 0xC3, 0x08, 0x00], 0x0000);
+        let vdm = Rc::new(RefCell::new(VdmDisplay::default()));
+        cpu.io_bus_mut().map_port(DEFAULT_PORT, vdm.clone());
+        cpu.attach_clocked(vdm.clone());
+        cpu.attach_bus_master(vdm.clone());
+
+        cpu.execute_one();                  // First refresh: before the writes
+        assert!(!vdm.borrow().row_text(0).starts_with("80"));
+        while cpu.cycles < REFRESH_CYCLES + 20 {
+            cpu.execute_one();
+        }
+        assert!(vdm.borrow().row_text(0).starts_with("80"));
+    }
+}
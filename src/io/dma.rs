@@ -0,0 +1,13 @@
+/// DMA peripheral trait - the device side of a DMA channel. The
+/// controller watches DREQ and, while it is asserted, runs DACK cycles
+/// that move one byte between the device and memory.
+pub trait DmaDevice {
+    /// DREQ line asserted?
+    fn dma_request(&mut self) -> bool;
+    /// DACK on a write transfer: supply the byte for memory
+    fn dma_read(&mut self) -> u8;
+    /// DACK on a read transfer: take the byte read from memory
+    fn dma_write(&mut self, value: u8);
+    /// Terminal count - the last byte of the block just moved
+    fn terminal_count(&mut self) {}
+}
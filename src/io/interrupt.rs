@@ -0,0 +1,11 @@
+/// Interrupt source trait - for devices that can interrupt the CPU.
+/// The CPU checks sources before each instruction while interrupts are
+/// enabled, and acknowledges the first one requesting.
+pub trait InterruptSource {
+    /// Interrupt line asserted?
+    fn interrupt_requested(&mut self) -> bool;
+    /// CPU is taking the interrupt - return the vector address to call
+    /// (RST n = n * 8, or the CALL target an 8259 supplies). Level-triggered
+    /// sources keep requesting until the condition is cleared.
+    fn acknowledge(&mut self) -> u16;
+}
@@ -1,6 +1,15 @@
 mod bus;
+mod bus_master;
+mod clocked;
 mod device;
 pub mod devices;
+mod dma;
+mod interrupt;
+pub mod serial;
 
 pub use bus::IoBus;
+pub use bus_master::BusMaster;
+pub use clocked::Clocked;
 pub use device::IoDevice;
+pub use dma::DmaDevice;
+pub use interrupt::InterruptSource;
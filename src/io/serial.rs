@@ -0,0 +1,285 @@
+// serial.rs - Byte-stream backends for serial devices
+//
+// A serial chip (6850, 8251) models registers and status bits; what is on
+// the other end of the wire is a SerialBackend:
+//
+//   TerminalBackend - host keyboard and screen (crossterm, like Console)
+//   BufferBackend   - in-memory queues, shared with tests
+//   TcpBackend      - a TCP connection, outgoing or one accepted client
+//   FileBackend     - input from one file, output appended to another
+//
+// Reads never block: no byte available is None.
+
+use crate::io::devices::console::{key_to_byte, QuitFlag};
+use crossterm::event::{poll, read, Event, KeyEventKind};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+pub trait SerialBackend {
+    /// Next received byte, if one is waiting
+    fn read_byte(&mut self) -> Option<u8>;
+    /// Send a byte
+    fn write_byte(&mut self, value: u8);
+}
+
+/// Host terminal. Needs raw mode, which main sets up. Ctrl-C raises
+/// the quit flag, normally the console's.
+pub struct TerminalBackend {
+    quit: QuitFlag,
+}
+
+impl TerminalBackend {
+    pub fn new(quit: QuitFlag) -> Self {
+        TerminalBackend { quit }
+    }
+}
+
+impl SerialBackend for TerminalBackend {
+    fn read_byte(&mut self) -> Option<u8> {
+        while poll(Duration::ZERO).unwrap_or(false) {
+            if let Ok(Event::Key(key_event)) = read() {
+                if key_event.kind == KeyEventKind::Press {
+                    if let Some(c) = key_to_byte(key_event, &self.quit) {
+                        return Some(c);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn write_byte(&mut self, value: u8) {
+        let mut out = io::stdout();
+        let _ = out.write_all(&[value]);
+        let _ = out.flush();
+    }
+}
+
+#[derive(Default)]
+struct Buffers {
+    input: VecDeque<u8>,
+    output: Vec<u8>,
+}
+
+/// In-memory queues. Clones share the same buffers, so a test keeps one
+/// clone and hands the other to the device.
+#[derive(Clone, Default)]
+pub struct BufferBackend {
+    buffers: Rc<RefCell<Buffers>>,
+}
+
+impl BufferBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue bytes for the device to receive
+    pub fn push_input(&self, bytes: &[u8]) {
+        self.buffers.borrow_mut().input.extend(bytes);
+    }
+
+    pub fn has_input(&self) -> bool {
+        !self.buffers.borrow().input.is_empty()
+    }
+
+    /// Everything the device has sent so far, clearing it
+    pub fn take_output(&self) -> Vec<u8> {
+        std::mem::take(&mut self.buffers.borrow_mut().output)
+    }
+}
+
+impl SerialBackend for BufferBackend {
+    fn read_byte(&mut self) -> Option<u8> {
+        self.buffers.borrow_mut().input.pop_front()
+    }
+
+    fn write_byte(&mut self, value: u8) {
+        self.buffers.borrow_mut().output.push(value);
+    }
+}
+
+/// TCP connection. A listening backend accepts one client when it first
+/// connects; until then output is dropped, like an unplugged line.
+pub struct TcpBackend {
+    listener: Option<TcpListener>,
+    stream: Option<TcpStream>,
+}
+
+impl TcpBackend {
+    pub fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(TcpBackend { listener: None, stream: Some(stream) })
+    }
+
+    pub fn listen(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(TcpBackend { listener: Some(listener), stream: None })
+    }
+
+    /// Listening address (useful after binding port 0)
+    pub fn local_addr(&self) -> Option<std::net::SocketAddr> {
+        self.listener.as_ref()?.local_addr().ok()
+    }
+
+    pub fn is_connected(&mut self) -> bool {
+        self.accept();
+        self.stream.is_some()
+    }
+
+    fn accept(&mut self) {
+        if self.stream.is_some() {
+            return;
+        }
+        if let Some(listener) = &self.listener {
+            if let Ok((stream, _)) = listener.accept() {
+                let _ = stream.set_nonblocking(true);
+                let _ = stream.set_nodelay(true);
+                self.stream = Some(stream);
+            }
+        }
+    }
+}
+
+impl SerialBackend for TcpBackend {
+    fn read_byte(&mut self) -> Option<u8> {
+        self.accept();
+        let stream = self.stream.as_mut()?;
+        let mut buf = [0u8; 1];
+        match stream.read(&mut buf) {
+            Ok(1) => Some(buf[0]),
+            Ok(_) => {
+                // Peer hung up; a listener waits for the next client
+                self.stream = None;
+                None
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => None,
+            Err(_) => {
+                self.stream = None;
+                None
+            }
+        }
+    }
+
+    fn write_byte(&mut self, value: u8) {
+        self.accept();
+        if let Some(stream) = self.stream.as_mut() {
+            // Nonblocking socket: retry a full send buffer rather than lose the byte
+            loop {
+                match stream.write(&[value]) {
+                    Ok(_) => break,
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => std::thread::yield_now(),
+                    Err(_) => {
+                        self.stream = None;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Input from a file (paper-tape style, read once), output to a file
+pub struct FileBackend {
+    input: Option<BufReader<File>>,
+    output: Option<File>,
+}
+
+impl FileBackend {
+    /// Either side may be None: no input, or output discarded
+    pub fn new(input: Option<&Path>, output: Option<&Path>) -> io::Result<Self> {
+        let input = input.map(File::open).transpose()?.map(BufReader::new);
+        let output = output
+            .map(|p| std::fs::OpenOptions::new().create(true).append(true).open(p))
+            .transpose()?;
+        Ok(FileBackend { input, output })
+    }
+}
+
+impl SerialBackend for FileBackend {
+    fn read_byte(&mut self) -> Option<u8> {
+        let mut buf = [0u8; 1];
+        match self.input.as_mut()?.read(&mut buf) {
+            Ok(1) => Some(buf[0]),
+            _ => None,
+        }
+    }
+
+    fn write_byte(&mut self, value: u8) {
+        if let Some(file) = self.output.as_mut() {
+            let _ = file.write_all(&[value]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_buffer_clones_share_queues() {
+        let host = BufferBackend::new();
+        let mut device_side = host.clone();
+
+        host.push_input(b"hi");
+        assert_eq!(device_side.read_byte(), Some(b'h'));
+        assert_eq!(device_side.read_byte(), Some(b'i'));
+        assert_eq!(device_side.read_byte(), None);
+
+        device_side.write_byte(b'!');
+        assert_eq!(host.take_output(), b"!");
+        assert!(host.take_output().is_empty());
+    }
+
+    #[test]
+    fn test_file_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("in.txt");
+        let output = dir.path().join("out.txt");
+        std::fs::write(&input, b"AB").unwrap();
+
+        let mut backend = FileBackend::new(Some(&input), Some(&output)).unwrap();
+        assert_eq!(backend.read_byte(), Some(b'A'));
+        assert_eq!(backend.read_byte(), Some(b'B'));
+        assert_eq!(backend.read_byte(), None);
+        backend.write_byte(b'Z');
+        drop(backend);
+        assert_eq!(std::fs::read(&output).unwrap(), b"Z");
+    }
+
+    #[test]
+    fn test_tcp_listen_backend() {
+        let mut backend = TcpBackend::listen("127.0.0.1:0").unwrap();
+        let addr = backend.local_addr().unwrap();
+        backend.write_byte(b'x');   // Nobody connected - dropped
+        let mut client = TcpStream::connect(addr).unwrap();
+
+        let start = Instant::now();
+        while !backend.is_connected() {
+            assert!(start.elapsed() < Duration::from_secs(5));
+        }
+        backend.write_byte(b'K');
+        let mut buf = [0u8; 1];
+        client.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"K");
+
+        client.write_all(b"Q").unwrap();
+        let start = Instant::now();
+        let received = loop {
+            if let Some(b) = backend.read_byte() {
+                break b;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5));
+        };
+        assert_eq!(received, b'Q');
+    }
+}
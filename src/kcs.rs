@@ -0,0 +1,201 @@
+// kcs.rs - Kansas City Standard / CUTS cassette audio
+//
+// Bits are bursts of tone:
+//   300 baud (KCS):   0 = 4 cycles of 1200 Hz, 1 = 8 cycles of 2400 Hz
+//   1200 baud (CUTS): 0 = 1 cycle of 1200 Hz,  1 = 2 cycles of 2400 Hz
+//
+// Each byte is framed like a serial line: one start bit (0), eight data
+// bits LSB first, two stop bits (1). Between bytes the tape carries mark
+// tone (1s), and recordings start and end with a few seconds of it so a
+// reader can lock on.
+//
+// Decoding measures half-cycles between zero crossings: long ones are
+// 1200 Hz (space), short ones 2400 Hz (mark). The resulting mark/space
+// line is then sampled in the middle of each bit like a UART would.
+
+use crate::wav::Wav;
+use std::f64::consts::PI;
+
+/// Mark tone written before and after the data
+pub const LEADER_SECONDS: f64 = 2.0;
+
+/// Sample rate of encoded tapes
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+
+const SPACE_HZ: f64 = 1200.0;
+const MARK_HZ: f64 = 2400.0;
+const AMPLITUDE: f64 = 24_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Baud {
+    B300,
+    B1200,
+}
+
+impl Baud {
+    pub fn from_u32(baud: u32) -> Option<Baud> {
+        match baud {
+            300 => Some(Baud::B300),
+            1200 => Some(Baud::B1200),
+            _ => None,
+        }
+    }
+
+    pub fn bits_per_second(self) -> u32 {
+        match self {
+            Baud::B300 => 300,
+            Baud::B1200 => 1200,
+        }
+    }
+}
+
+/// A byte read off tape and when its stop bits ended, in seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TapeByte {
+    pub time: f64,
+    pub value: u8,
+}
+
+/// Record bytes as cassette audio, with leader and trailer
+pub fn encode(data: &[u8], baud: Baud, sample_rate: u32) -> Wav {
+    let mut bits = Vec::new();
+    let leader = (LEADER_SECONDS * baud.bits_per_second() as f64) as usize;
+    bits.resize(leader, true);
+    for &byte in data {
+        bits.push(false);
+        bits.extend((0..8).map(|i| byte >> i & 1 != 0));
+        bits.extend([true, true]);
+    }
+    bits.resize(bits.len() + leader, true);
+
+    // Every bit holds whole cycles, so the phase is continuous
+    let rate = sample_rate as f64;
+    let bit_time = 1.0 / baud.bits_per_second() as f64;
+    let count = (bits.len() as f64 * bit_time * rate) as usize;
+    let mut wav = Wav::new(sample_rate);
+    let mut phase = 0.0;
+    for n in 0..count {
+        let bit = bits[((n as f64 / rate) / bit_time) as usize];
+        wav.samples.push((AMPLITUDE * (2.0 * PI * phase).sin()) as i16);
+        phase = (phase + if bit { MARK_HZ } else { SPACE_HZ } / rate).fract();
+    }
+    wav
+}
+
+/// Bytes found on a tape, with their timing
+pub fn decode_timed(wav: &Wav, baud: Baud) -> Vec<TapeByte> {
+    let rate = wav.sample_rate as f64;
+    let line = mark_space(wav);
+    let bit = rate / baud.bits_per_second() as f64;
+    let at = |start: usize, bits: f64| start + (bit * bits) as usize;
+
+    let mut bytes = Vec::new();
+    let mut i = 0;
+    while i < line.len() {
+        if line[i] {
+            i += 1;
+            continue;
+        }
+        // Falling edge: check the start bit in its middle, then sample
+        let start = i;
+        if at(start, 10.5) >= line.len() {
+            break;
+        }
+        if line[at(start, 0.5)] {
+            i += 1;                         // Glitch, not a start bit
+            continue;
+        }
+        let value = (0..8).fold(0u8, |v, b| v | (line[at(start, 1.5 + b as f64)] as u8) << b);
+        if line[at(start, 9.5)] {
+            bytes.push(TapeByte { time: at(start, 11.0) as f64 / rate, value });
+        }
+        i = at(start, 9.5);                 // Hunt for the next start from the stop bit
+    }
+    bytes
+}
+
+/// Bytes found on a tape
+pub fn decode(wav: &Wav, baud: Baud) -> Vec<u8> {
+    decode_timed(wav, baud).into_iter().map(|b| b.value).collect()
+}
+
+/// Per-sample line level from half-cycle lengths: true = mark
+fn mark_space(wav: &Wav) -> Vec<bool> {
+    // Halfway (in period) between 1200 and 2400 Hz half-cycles
+    let threshold = wav.sample_rate as f64 / (2.0 * 1800.0);
+    let mut line = vec![true; wav.samples.len()];
+    let mut last = None;
+    let mut positive = wav.samples.first().is_some_and(|&s| s >= 0);
+    for (n, &sample) in wav.samples.iter().enumerate() {
+        if (sample >= 0) == positive {
+            continue;
+        }
+        positive = !positive;
+        if let Some(prev) = last {
+            let mark = ((n - prev) as f64) < threshold;
+            line[prev..n].fill(mark);
+        }
+        last = Some(n);
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_bytes() -> Vec<u8> {
+        (0..=255).collect()
+    }
+
+    #[test]
+    fn test_round_trip_300() {
+        let wav = encode(&all_bytes(), Baud::B300, 44_100);
+        assert_eq!(decode(&wav, Baud::B300), all_bytes());
+        // 256 bytes x 11 bits at 300 baud, plus leader and trailer
+        let expected = 256.0 * 11.0 / 300.0 + 2.0 * LEADER_SECONDS;
+        assert!((wav.duration() - expected).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_round_trip_1200() {
+        for rate in [22_050, 44_100, 48_000] {
+            let wav = encode(&all_bytes(), Baud::B1200, rate);
+            assert_eq!(decode(&wav, Baud::B1200), all_bytes(), "{} Hz", rate);
+        }
+    }
+
+    #[test]
+    fn test_tone_frequencies() {
+        // A 0x00 byte is 1200 Hz for 9 bits; count crossings in the first 8
+        let wav = encode(&[0x00], Baud::B300, 48_000);
+        let start = (LEADER_SECONDS * 48_000.0) as usize;
+        let cell = &wav.samples[start + 1..start + 160 * 8];
+        let crossings = cell.windows(2).filter(|w| (w[0] >= 0) != (w[1] >= 0)).count();
+        assert_eq!(crossings, 8 * 8 - 1);   // 4 cycles per bit
+    }
+
+    #[test]
+    fn test_noisy_8bit_tape() {
+        let clean = encode(b"HELLO", Baud::B300, 11_025);
+        let mut noise = 0x1234u32;
+        let samples = clean.samples.iter().map(|&s| {
+            noise = noise.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let hiss = (noise >> 16) as i32 % 4000 - 2000;
+            (((s as i32 / 2 + hiss) >> 8) << 8) as i16
+        }).collect();
+        let tape = Wav { sample_rate: 11_025, samples };
+        assert_eq!(decode(&tape, Baud::B300), b"HELLO");
+    }
+
+    #[test]
+    fn test_timing() {
+        let wav = encode(b"AB", Baud::B1200, 44_100);
+        let bytes = decode_timed(&wav, Baud::B1200);
+        assert_eq!(bytes.len(), 2);
+        let gap = bytes[1].time - bytes[0].time;
+        assert!((gap - 11.0 / 1200.0).abs() < 0.0002);
+        assert!((bytes[0].time - (LEADER_SECONDS + 11.0 / 1200.0)).abs() < 0.0002);
+        assert_eq!(Baud::from_u32(600), None);
+    }
+}
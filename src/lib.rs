@@ -1,9 +1,19 @@
 // Intel 8080 Emulator Library
 
+pub mod asm;
+pub mod cpm;
 pub mod cpu;
+pub mod disasm;
+pub mod formats;
+pub mod host;
+pub mod invaders;
 pub mod io;
+pub mod json;
+pub mod kcs;
 pub mod memory;
 pub mod registers;
+pub mod video;
+pub mod wav;
 
 pub use cpu::Intel8080;
 pub use memory::{Memory, FlatMemory};
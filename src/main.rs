@@ -3,16 +3,298 @@ use std::cell::RefCell;
 use std::path::PathBuf;
 
 use intel8080_emu::Intel8080;
+use intel8080_emu::cpm::{CpmExit, CpmHarness};
+use intel8080_emu::formats;
+use intel8080_emu::host::{HostCommands, MemoryJobs};
+use intel8080_emu::kcs::{self, Baud};
+use intel8080_emu::invaders::{Button, Invaders};
+use intel8080_emu::io::devices::acia::Acia6850;
+use intel8080_emu::io::devices::altair_disk::{self, AltairDisk};
+use intel8080_emu::io::devices::assembler::AssemblerDevice;
+use intel8080_emu::io::devices::assistant::{AssistantConfig, AssistantDevice};
+use intel8080_emu::io::devices::ay::{self, Ay38910};
+use intel8080_emu::io::devices::cassette::Cassette;
 use intel8080_emu::io::devices::console::Console;
+use intel8080_emu::io::devices::disassembler::DisassemblerDevice;
+use intel8080_emu::io::devices::dma::Dma8257;
+use intel8080_emu::io::devices::hex_loader::HexLoaderDevice;
+use intel8080_emu::io::devices::http::HttpDevice;
+use intel8080_emu::io::devices::paper_tape::{self, TapePunch, TapeReader};
+use intel8080_emu::io::devices::paper_tape_mount::{self, PaperTapeMount};
+use intel8080_emu::io::devices::printer::{self, Printer};
+use intel8080_emu::io::devices::page_cache::SyncPolicy;
+use intel8080_emu::io::devices::pic::Pic8259;
 use intel8080_emu::io::devices::storage::Storage;
 use intel8080_emu::io::devices::storage_mount::StorageMount;
+use intel8080_emu::io::devices::time::TimeDevice;
+use intel8080_emu::io::devices::vdm::{self, VdmDisplay};
+use intel8080_emu::io::serial::TerminalBackend;
+use intel8080_emu::io::InterruptSource;
+use intel8080_emu::wav::Wav;
 
+use crossterm::event::{poll, read, Event, KeyCode, KeyEventKind};
 use crossterm::terminal::{enable_raw_mode, disable_raw_mode};
 
 const BUILD_TIMESTAMP: &str = env!("BUILD_TIMESTAMP");
 
 
+const USAGE: &str = "usage: intel8080 [FILE[@ADDR]]... [--save FILE@START-END]...
+                 [--dsk IMAGE]... [--dsk-ro IMAGE]... [--vdm]
+                 [--wav FILE] [--tape WAV] [--tape-record WAV] [--tape-baud 300|1200]
+                 [--printer FILE] [--storage-sync back|through|durable]
+       intel8080 --cpm PROGRAM.COM [ARGS]...
+       intel8080 --invaders ROM
+       intel8080 --kcs INPUT OUTPUT [300|1200]
+  FILE          Program to load before the monitor starts. Format comes from
+                the extension: .hex/.ihx = Intel HEX, .s19/.srec/.mot =
+                S-record, anything else = raw binary at ADDR (default 0100)
+  --save        Write memory START-END (hex) to FILE when the emulator exits
+  --dsk         Mount an Altair .dsk image on the 88-DCDD controller at
+                ports 88-8A (drives 0, 1, ... in order); --dsk-ro mounts it
+                write-protected
+  --vdm         Show the VDM-1 text display (RAM CC00-CFFF, control port C8)
+                at the top of the terminal
+  --wav         Map the AY-3-8910 sound chip on ports A0-A1 and write what
+                it plays to FILE when the emulator exits
+  --tape        Put a Kansas City Standard WAV in the cassette deck at
+                ports 06-07; --tape-record writes what the 8080 records
+                to WAV on exit. --tape-baud picks KCS 300 (default) or
+                CUTS 1200
+  --printer     Spool the line printer at ports B8-B9 to FILE (:printer
+                changes it while running)
+  --storage-sync
+                When storage writes reach the host files: back (default) on
+                flush (control 02), unmount or exit; through on every write;
+                durable is back plus an fsync on each flush
+  --cpm         Run a CP/M program without the monitor. BDOS calls are
+                trapped; files come from the program's directory
+  --invaders    Run the Space Invaders board. ROM is an 8KB file or a
+                directory with invaders.h/.g/.f/.e. Keys: C coin, 1/2 start,
+                arrows or A/D move, space fire, S snapshot, Q quit
+  --kcs         Convert between binary and cassette audio: a .wav INPUT is
+                decoded to OUTPUT, anything else is encoded to a .wav";
+
+/// Program to load: path and address for raw binaries
+struct LoadArg {
+    path: PathBuf,
+    address: u16,
+}
+
+/// Memory range to write out on exit
+struct SaveArg {
+    path: PathBuf,
+    start: u16,
+    end: u16,
+}
+
+fn parse_hex16(text: &str) -> Result<u16, String> {
+    let digits = text.trim_end_matches(['h', 'H']);
+    u16::from_str_radix(digits, 16).map_err(|_| format!("bad hex address: {}", text))
+}
+
+/// Command-line options for the monitor machine
+#[derive(Default)]
+struct Options {
+    loads: Vec<LoadArg>,
+    saves: Vec<SaveArg>,
+    disks: Vec<(PathBuf, bool)>,    // Image, write-protected
+    vdm: bool,
+    wav: Option<PathBuf>,
+    tape: Option<PathBuf>,
+    tape_record: Option<PathBuf>,
+    tape_baud: Option<Baud>,
+    printer: Option<PathBuf>,
+    storage_sync: SyncPolicy,
+}
+
+fn parse_baud(text: &str) -> Result<Baud, String> {
+    text.parse().ok().and_then(Baud::from_u32).ok_or_else(|| format!("bad tape baud rate: {}", text))
+}
+
+fn parse_sync(text: &str) -> Result<SyncPolicy, String> {
+    match text {
+        "back" => Ok(SyncPolicy::WriteBack),
+        "through" => Ok(SyncPolicy::WriteThrough),
+        "durable" => Ok(SyncPolicy::Durable),
+        _ => Err(format!("bad storage sync policy: {}", text)),
+    }
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--vdm" {
+            options.vdm = true;
+        } else if arg == "--wav" {
+            options.wav = Some(PathBuf::from(args.next().ok_or("--wav needs a file")?));
+        } else if arg == "--printer" {
+            options.printer = Some(PathBuf::from(args.next().ok_or("--printer needs a file")?));
+        } else if arg == "--storage-sync" {
+            options.storage_sync = parse_sync(&args.next().ok_or("--storage-sync needs back, through or durable")?)?;
+        } else if arg == "--tape" {
+            options.tape = Some(PathBuf::from(args.next().ok_or("--tape needs a WAV file")?));
+        } else if arg == "--tape-record" {
+            options.tape_record = Some(PathBuf::from(args.next().ok_or("--tape-record needs a WAV file")?));
+        } else if arg == "--tape-baud" {
+            options.tape_baud = Some(parse_baud(&args.next().ok_or("--tape-baud needs 300 or 1200")?)?);
+        } else if arg == "--dsk" || arg == "--dsk-ro" {
+            let image = args.next().ok_or_else(|| format!("{} needs an image file", arg))?;
+            options.disks.push((PathBuf::from(image), arg == "--dsk-ro"));
+        } else if arg == "--save" {
+            let spec = args.next().ok_or("--save needs FILE@START-END")?;
+            let (path, range) = spec.rsplit_once('@').ok_or("--save needs FILE@START-END")?;
+            let (start, end) = range.split_once('-').ok_or("--save needs FILE@START-END")?;
+            options.saves.push(SaveArg {
+                path: PathBuf::from(path),
+                start: parse_hex16(start)?,
+                end: parse_hex16(end)?,
+            });
+        } else if arg.starts_with('-') {
+            return Err(format!("unknown option: {}", arg));
+        } else {
+            let (path, address) = match arg.rsplit_once('@') {
+                Some((path, addr)) => (path, parse_hex16(addr)?),
+                None => (arg.as_str(), 0x0100),
+            };
+            options.loads.push(LoadArg { path: PathBuf::from(path), address });
+        }
+    }
+    Ok(options)
+}
+
+/// CP/M harness mode: run a .COM with the console on stdin/stdout
+fn run_cpm(args: &[String]) -> ! {
+    let program = match args.first() {
+        Some(p) => PathBuf::from(p),
+        None => {
+            eprintln!("--cpm needs a .COM file\n{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let dir = program.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(std::path::Path::new("."));
+    let mut cpm = CpmHarness::new(dir);
+    if let Err(e) = cpm.load_com_file(&program, &args[1..].join(" ")) {
+        eprintln!("{}: {}", program.display(), e);
+        std::process::exit(1);
+    }
+    cpm.set_echo(true);
+    cpm.set_input(Box::new(std::io::stdin()));
+    let exit = cpm.run(None);
+    println!();
+    std::process::exit(if exit == CpmExit::WarmBoot { 0 } else { 1 });
+}
+
+/// Cassette conversion: .wav to binary, anything else to .wav
+fn run_kcs(args: &[String]) -> ! {
+    let (Some(input), Some(output)) = (args.first(), args.get(1)) else {
+        eprintln!("--kcs needs INPUT and OUTPUT files\n{}", USAGE);
+        std::process::exit(2);
+    };
+    let baud = parse_baud(args.get(2).map(String::as_str).unwrap_or("300")).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    let (input, output) = (PathBuf::from(input), PathBuf::from(output));
+    let decoding = input.extension().is_some_and(|e| e.eq_ignore_ascii_case("wav"));
+    let result = if decoding {
+        Wav::load(&input).and_then(|wav| {
+            let data = kcs::decode(&wav, baud);
+            println!("{} bytes", data.len());
+            std::fs::write(&output, data)
+        })
+    } else {
+        std::fs::read(&input).and_then(|data| {
+            let wav = kcs::encode(&data, baud, kcs::DEFAULT_SAMPLE_RATE);
+            println!("{} bytes, {:.1}s of tape", data.len(), wav.duration());
+            wav.save(&output)
+        })
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    std::process::exit(0);
+}
+
+/// Space Invaders mode: the arcade board drawn in the terminal
+fn run_invaders(args: &[String]) -> ! {
+    let Some(rom) = args.first() else {
+        eprintln!("--invaders needs a ROM file or directory\n{}", USAGE);
+        std::process::exit(2);
+    };
+    let mut machine = Invaders::from_path(std::path::Path::new(rom)).unwrap_or_else(|e| {
+        eprintln!("{}: {}", rom, e);
+        std::process::exit(1);
+    });
+
+    // Terminals report presses, not releases: a key holds its button
+    // for a few frames
+    const HOLD_FRAMES: u32 = 6;
+    let mut held: Vec<(Button, u32)> = Vec::new();
+
+    enable_raw_mode().expect("Failed to enable raw mode");
+    print!("\x1b[2J\x1b[?25l");
+    let frame_time = std::time::Duration::from_micros(1_000_000 / 60);
+    'frames: loop {
+        let start = std::time::Instant::now();
+        while poll(std::time::Duration::ZERO).unwrap_or(false) {
+            let Ok(Event::Key(key)) = read() else { continue };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let button = match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => break 'frames,
+                KeyCode::Char('s') => {
+                    let _ = machine.screen().save(std::path::Path::new("invaders.png"));
+                    continue;
+                }
+                KeyCode::Char('c') => Button::Coin,
+                KeyCode::Char('1') => Button::Start1,
+                KeyCode::Char('2') => Button::Start2,
+                KeyCode::Char(' ') => Button::Fire1,
+                KeyCode::Left | KeyCode::Char('a') => Button::Left1,
+                KeyCode::Right | KeyCode::Char('d') => Button::Right1,
+                _ => continue,
+            };
+            held.retain(|(b, _)| *b != button);
+            held.push((button, HOLD_FRAMES));
+        }
+        for (button, frames) in held.iter_mut() {
+            machine.set_button(*button, true);
+            *frames -= 1;
+        }
+        machine.run_frame();
+        for (button, _) in held.iter().filter(|(_, frames)| *frames == 0) {
+            machine.set_button(*button, false);
+        }
+        held.retain(|(_, frames)| *frames > 0);
+
+        print!("\x1b[H{}", machine.screen().to_blocks().replace('\n', "\r\n"));
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+        if let Some(rest) = frame_time.checked_sub(start.elapsed()) {
+            std::thread::sleep(rest);
+        }
+    }
+    print!("\x1b[?25h\r\n");
+    disable_raw_mode().ok();
+    std::process::exit(0);
+}
+
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("--cpm") => run_cpm(&args[1..]),
+        Some("--invaders") => run_invaders(&args[1..]),
+        Some("--kcs") => run_kcs(&args[1..]),
+        _ => {}
+    }
+
+    let options = parse_args().unwrap_or_else(|e| {
+        eprintln!("{}\n{}", e, USAGE);
+        std::process::exit(2);
+    });
+
     println!("8080 Emulator");
     println!("Built: {}", BUILD_TIMESTAMP);
     enable_raw_mode().expect("Failed to enable raw mode");
@@ -25,17 +307,100 @@ fn main() {
 
     let mut cpu = Intel8080::new();
         
+    // Emulator commands typed as ':' lines at the console
+    let host = Rc::new(RefCell::new(HostCommands::new()));
+    
+    // :load and :save run between instructions, when memory is free
+    let jobs = Rc::new(RefCell::new(MemoryJobs::new()));
+    cpu.attach_bus_master(jobs.clone());
+    let queue = jobs.clone();
+    host.borrow_mut().register("load", ":load FILE [ADDR]", Box::new(move |args| {
+        let (path, address) = match args {
+            [path] => (PathBuf::from(path), 0x0100),
+            [path, addr] => (PathBuf::from(path), parse_hex16(addr)?),
+            _ => return Err("usage: :load FILE [ADDR]".to_string()),
+        };
+        queue.borrow_mut().queue(Box::new(move |memory| {
+            let image = formats::load_into(memory, &path, address)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            let mut lines: Vec<String> = image.segments.iter()
+                .map(|(address, data)| format!("Loaded {} bytes at {:04X} ({:?})", data.len(), address, formats::Format::from_path(&path)))
+                .collect();
+            if let Some(start) = image.start {
+                lines.push(format!("Start address {:04X}", start));
+            }
+            let hidden = formats::hidden_bytes(memory, &image);
+            if hidden > 0 {
+                lines.push(format!("Warning: {} bytes are in RAM under ROM and not visible", hidden));
+            }
+            Ok(lines.join("\r\n"))
+        }));
+        Ok(String::new())
+    }));
+    let queue = jobs;
+    host.borrow_mut().register("save", ":save FILE START END [ENTRY]", Box::new(move |args| {
+        let (path, start, end, entry) = match args {
+            [path, start, end] => (PathBuf::from(path), parse_hex16(start)?, parse_hex16(end)?, None),
+            [path, start, end, entry] => (PathBuf::from(path), parse_hex16(start)?, parse_hex16(end)?, Some(parse_hex16(entry)?)),
+            _ => return Err("usage: :save FILE START END [ENTRY]".to_string()),
+        };
+        queue.borrow_mut().queue(Box::new(move |memory| {
+            let n = formats::save_file(memory, &path, start, end, entry)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            Ok(format!("Saved {} bytes to {} ({:?})", n, path.display(), formats::Format::from_path(&path)))
+        }));
+        Ok(String::new())
+    }));
+    
     // Set up console device on ports 0x00-0x02
-    let console = Rc::new(RefCell::new(Console::new()));
+    let mut console = Console::new();
+    console.set_host_commands(host.clone());
+    let quit = console.quit_flag();
+    let console = Rc::new(RefCell::new(console));
     cpu.io_bus_mut().map_port(0x00, console.clone());
     cpu.io_bus_mut().map_port(0x01, console.clone());
     cpu.io_bus_mut().map_port(0x02, console);
     
+    // Set up 8259 PIC on ports 0x34-0x35 with the timer on IR7. It starts
+    // out programmed so IR7 still calls 0038 (RST 7) with no EOI needed.
+    let mut pic = Pic8259::new(0x34);
+    pic.init_rst();
+    let timer: Rc<RefCell<dyn InterruptSource>> = cpu.timer.clone();
+    cpu.detach_interrupt_source(&timer);
+    pic.connect(7, timer);
+    let pic = Rc::new(RefCell::new(pic));
+    cpu.io_bus_mut().map_port(0x34, pic.clone());
+    cpu.io_bus_mut().map_port(0x35, pic.clone());
+    cpu.attach_interrupt_source(pic);
+    
+    // Set up 88-2SIO (6850 ACIA) on ports 0x10-0x11 for Altair software,
+    // sharing the terminal with the console
+    let acia = Rc::new(RefCell::new(Acia6850::new(0x10, Box::new(TerminalBackend::new(quit.clone())))));
+    cpu.io_bus_mut().map_port(0x10, acia.clone());
+    cpu.io_bus_mut().map_port(0x11, acia.clone());
+    cpu.attach_interrupt_source(acia);
+    
+    // Set up cassette deck (Kansas City Standard) on ports 0x06-0x07
+    let mut deck = Cassette::new(0x06, options.tape_baud.unwrap_or(Baud::B300));
+    if let Some(path) = &options.tape {
+        match deck.load(path) {
+            Ok(()) => println!("Tape {}: {} bytes\r", path.display(), deck.tape_len()),
+            Err(e) => println!("{}: {}\r", path.display(), e),
+        }
+    }
+    let deck = Rc::new(RefCell::new(deck));
+    cpu.io_bus_mut().map_port(0x06, deck.clone());
+    cpu.io_bus_mut().map_port(0x07, deck.clone());
+    cpu.attach_clocked(deck.clone());
+    
     // Create storage directory
     std::fs::create_dir_all("./storage/").ok();
     
-    // Set up storage device on ports 0x08-0x0C
+    // Set up storage device on ports 0x08-0x0C, unit select and error code on 0x04-0x05
     let storage = Rc::new(RefCell::new(Storage::new()));
+    storage.borrow_mut().set_sync_policy(options.storage_sync);
+    cpu.io_bus_mut().map_port(0x04, storage.clone());
+    cpu.io_bus_mut().map_port(0x05, storage.clone());
     cpu.io_bus_mut().map_port(0x08, storage.clone());
     cpu.io_bus_mut().map_port(0x09, storage.clone());
     cpu.io_bus_mut().map_port(0x0A, storage.clone());
@@ -51,14 +416,200 @@ fn main() {
     cpu.io_bus_mut().map_port(0x0E, mount.clone());
     cpu.io_bus_mut().map_port(0x0F, mount);
     
+    // Set up paper tape reader (0xB0-0xB1), punch (0xB2-0xB3) and their
+    // mount service (0xB4-0xB6); :reader and :punch drive them from the host
+    let reader = Rc::new(RefCell::new(TapeReader::new(paper_tape::READER_BASE)));
+    let punch = Rc::new(RefCell::new(TapePunch::new(paper_tape::PUNCH_BASE)));
+    cpu.io_bus_mut().map_port(0xB0, reader.clone());
+    cpu.io_bus_mut().map_port(0xB1, reader.clone());
+    cpu.io_bus_mut().map_port(0xB2, punch.clone());
+    cpu.io_bus_mut().map_port(0xB3, punch.clone());
+    let tape_mount = Rc::new(RefCell::new(PaperTapeMount::new(
+        paper_tape_mount::DEFAULT_BASE,
+        reader.clone(),
+        punch.clone(),
+        PathBuf::from("./storage/"),
+    )));
+    for port in 0xB4..=0xB6 {
+        cpu.io_bus_mut().map_port(port, tape_mount.clone());
+    }
+    let tape = reader.clone();
+    host.borrow_mut().register("reader", ":reader [mount FILE | unmount | rewind | leader on|off]",
+        Box::new(move |args| tape.borrow_mut().host_command(args)));
+    let tape = punch.clone();
+    host.borrow_mut().register("punch", ":punch [mount FILE | unmount | leader]",
+        Box::new(move |args| tape.borrow_mut().host_command(args)));
+    
+    // Set up line printer on ports 0xB8-0xB9, spooling to --printer or :printer mount
+    let mut lp = Printer::new(printer::DEFAULT_BASE);
+    if let Some(path) = &options.printer {
+        if let Err(e) = lp.mount(path) {
+            println!("{}: {}\r", path.display(), e);
+        }
+    }
+    let lp = Rc::new(RefCell::new(lp));
+    cpu.io_bus_mut().map_port(0xB8, lp.clone());
+    cpu.io_bus_mut().map_port(0xB9, lp.clone());
+    cpu.attach_clocked(lp.clone());
+    let spool = lp.clone();
+    host.borrow_mut().register("printer",
+        ":printer [mount FILE | unmount | flush | split|crlf|paperout|busy on|off | delay CYCLES]",
+        Box::new(move |args| spool.borrow_mut().host_command(args)));
+    
+    // Set up 8257 DMA controller on ports 0x90-0x98, Storage on channel 0
+    let mut dma = Dma8257::new(0x90);
+    dma.connect(0, storage.clone());
+    let dma = Rc::new(RefCell::new(dma));
+    for port in 0x90..=0x98 {
+        cpu.io_bus_mut().map_port(port, dma.clone());
+    }
+    cpu.attach_bus_master(dma);
+    
+    // Set up assembler on ports 0x28-0x2E
+    let assembler = Rc::new(RefCell::new(AssemblerDevice::new()));
+    for port in 0x28..=0x2E {
+        cpu.io_bus_mut().map_port(port, assembler.clone());
+    }
+    
+    // Set up disassembler on ports 0x74-0x78 (0x20-0x22 is the legacy disk)
+    let disasm = Rc::new(RefCell::new(DisassemblerDevice::new()));
+    for port in 0x74..=0x78 {
+        cpu.io_bus_mut().map_port(port, disasm.clone());
+    }
+    
+    // Set up Intel HEX loader on ports 0x7C-0x85 (writes memory as a bus master)
+    let hex_loader = Rc::new(RefCell::new(HexLoaderDevice::new()));
+    for port in 0x7C..=0x85 {
+        cpu.io_bus_mut().map_port(port, hex_loader.clone());
+    }
+    cpu.attach_bus_master(hex_loader);
+    
+    // Set up HTTP client on ports 0x40-0x47
+    let http = Rc::new(RefCell::new(HttpDevice::new()));
+    for port in 0x40..=0x47 {
+        cpu.io_bus_mut().map_port(port, http.clone());
+    }
+    
+    // Set up LLM assistant on ports 0x38-0x3B (settings from config.json if present)
+    let config_path = std::path::Path::new("config.json");
+    let assistant_config = if config_path.exists() {
+        AssistantConfig::load(config_path).unwrap_or_else(|e| {
+            eprintln!("config.json: {}", e);
+            AssistantConfig::default()
+        })
+    } else {
+        AssistantConfig::default()
+    };
+    let assistant = Rc::new(RefCell::new(AssistantDevice::from_config(assistant_config)));
+    for port in 0x38..=0x3B {
+        cpu.io_bus_mut().map_port(port, assistant.clone());
+    }
+    
+    // Set up Altair 88-DCDD disk controller on ports 0x88-0x8A when images
+    // are given (the MITS default 0x08-0x0A is Storage)
+    if !options.disks.is_empty() {
+        let mut dcdd = AltairDisk::new(0x88);
+        for (drive, (image, read_only)) in options.disks.iter().enumerate().take(altair_disk::DRIVES) {
+            if let Err(e) = dcdd.mount(drive, image, *read_only) {
+                disable_raw_mode().ok();
+                eprintln!("{}: {}", image.display(), e);
+                std::process::exit(1);
+            }
+        }
+        let dcdd = Rc::new(RefCell::new(dcdd));
+        for port in 0x88..=0x8A {
+            cpu.io_bus_mut().map_port(port, dcdd.clone());
+        }
+    }
+    
+    // Set up VDM-1 display (screen RAM at 0xCC00, control port 0xC8) when asked
+    if options.vdm {
+        print!("\x1b[2J");
+        let mut display = VdmDisplay::new(vdm::DEFAULT_BASE, vdm::DEFAULT_PORT);
+        display.set_terminal(0, 0);
+        let display = Rc::new(RefCell::new(display));
+        cpu.io_bus_mut().map_port(vdm::DEFAULT_PORT, display.clone());
+        cpu.attach_clocked(display.clone());
+        cpu.attach_bus_master(display);
+    }
+    
+    // Set up AY-3-8910 sound on ports 0xA0-0xA1 when audio is being recorded
+    let sound = options.wav.as_ref().map(|_| {
+        let chip = Rc::new(RefCell::new(Ay38910::new(0xA0, ay::DEFAULT_CLOCK_HZ, ay::DEFAULT_SAMPLE_RATE)));
+        cpu.io_bus_mut().map_port(0xA0, chip.clone());
+        cpu.io_bus_mut().map_port(0xA1, chip.clone());
+        cpu.attach_clocked(chip.clone());
+        chip
+    });
+    
+    // Set up system time on ports 0x60-0x6F (host clock)
+    let time = Rc::new(RefCell::new(TimeDevice::host()));
+    for port in 0x60..=0x6F {
+        cpu.io_bus_mut().map_port(port, time.clone());
+    }
+    
     // Load ROM (mapped at 0xF000, visible at 0x0000 via overlay)
     cpu.load_rom_from_file(std::path::Path::new("rom/monitor.bin"))
         .expect("Failed to load ROM");
     
-    // Reset enables overlay and sets PC=0x0000
-    // ROM code will disable overlay after jumping to 0xF000+ address space
-    cpu.reset();
-    cpu.run();
+    // Read programs to load into RAM; the monitor's G command runs them
+    let mut images = Vec::new();
+    for load in &options.loads {
+        match formats::read_image(&load.path, load.address) {
+            Ok(image) => {
+                for (address, data) in &image.segments {
+                    println!("Loaded {} bytes at {:04X} from {}\r", data.len(), address, load.path.display());
+                }
+                images.push(image);
+            }
+            Err(e) => {
+                disable_raw_mode().ok();
+                eprintln!("{}: {}", load.path.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+    
+    // Reset enables overlay and sets PC=0x0000, then the programs go in.
+    // ROM code will disable overlay after jumping to 0xF000+ address space;
+    // a program with a start address (HEX type 03, S9) runs there instead
+    formats::boot(&mut cpu, &images);
+    if let Some(start) = images.iter().rev().find_map(|image| image.start) {
+        println!("Starting at {:04X}\r", start);
+    }
+    // Run until HLT or Ctrl-C, then save and unmount everything
+    while !cpu.halted && !quit.get() {
+        cpu.execute_one();
+    }
+    
+    for save in &options.saves {
+        match formats::save_file(&mut cpu, &save.path, save.start, save.end, None) {
+            Ok(n) => println!("Saved {} bytes to {}\r", n, save.path.display()),
+            Err(e) => println!("{}: {}\r", save.path.display(), e),
+        }
+    }
+    
+    if let Some(path) = &options.tape_record {
+        let deck = deck.borrow();
+        match deck.save_recording(path) {
+            Ok(()) => println!("Recorded {} bytes to {}\r", deck.recorded().len(), path.display()),
+            Err(e) => println!("{}: {}\r", path.display(), e),
+        }
+    }
+    
+    if let (Some(chip), Some(path)) = (&sound, &options.wav) {
+        let chip = chip.borrow();
+        match chip.save_wav(path) {
+            Ok(()) => println!("Wrote {:.1}s of audio to {}\r", chip.wav().duration(), path.display()),
+            Err(e) => println!("{}: {}\r", path.display(), e),
+        }
+    }
+    
+    punch.borrow_mut().unmount();
+    storage.borrow_mut().unmount_all();
+    if let Err(e) = lp.borrow_mut().unmount() {
+        println!("Printer: {}\r", e);
+    }
     
     println!("\r\nProgram finished!\r");
     println!("A={:02X} B={:02X} C={:02X}\r", cpu.a, cpu.b, cpu.c);
@@ -1,6 +1,11 @@
 pub trait Memory {
     fn read(&mut self, addr: u16) -> u8;
     fn write(&mut self, addr: u16, value: u8);
+
+    /// Store a byte as a program loader does, past any write protection
+    fn load(&mut self, addr: u16, value: u8) {
+        self.write(addr, value)
+    }
 }
 
 pub struct FlatMemory {
@@ -13,6 +18,12 @@ impl FlatMemory {
     }
 }
 
+impl Default for FlatMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Memory for FlatMemory {
     fn read(&mut self, addr: u16) -> u8 {
         self.ram[addr as usize]
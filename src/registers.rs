@@ -213,6 +213,15 @@ impl RegisterPair {
             RegisterPair::SP => "SP",
         }
     }
+    /// Intel assembler operand name (B, D, H, SP)
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            RegisterPair::BC => "B",
+            RegisterPair::DE => "D",
+            RegisterPair::HL => "H",
+            RegisterPair::SP => "SP",
+        }
+    }
     pub fn description(self) -> &'static str {
         match self {
             RegisterPair::BC => "BC register pair",
@@ -242,12 +251,12 @@ impl RegisterPair {
 
     // Capability queries
     pub fn matches_push_pop(&self, pp: PushPopPair) -> bool {
-        match (*self, pp) {
-            (RegisterPair::BC, PushPopPair::BC) => true,
-            (RegisterPair::DE, PushPopPair::DE) => true,
-            (RegisterPair::HL, PushPopPair::HL) => true,
-            _ => false,
-        }
+        matches!(
+            (*self, pp),
+            (RegisterPair::BC, PushPopPair::BC)
+                | (RegisterPair::DE, PushPopPair::DE)
+                | (RegisterPair::HL, PushPopPair::HL)
+        )
     }
     pub fn supports_indirect(self) -> bool {
         matches!(self, RegisterPair::BC | RegisterPair::DE)
@@ -273,6 +282,15 @@ impl PushPopPair {
             PushPopPair::PSW => "PSW",
         }
     }
+    /// Intel assembler operand name (B, D, H, PSW)
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            PushPopPair::BC => "B",
+            PushPopPair::DE => "D",
+            PushPopPair::HL => "H",
+            PushPopPair::PSW => "PSW",
+        }
+    }
     pub fn from_code(code: u8) -> Self {
         match code & 0x03 {
             0 => PushPopPair::BC,
@@ -0,0 +1,230 @@
+// video.rs - 1-bit frames for emulated displays
+//
+// A Frame is what a video board shows: width x height pixels, on or off.
+// It renders to:
+//   PPM (P6) and PNG (8-bit grayscale, stored deflate) snapshots
+//   Terminal text, one quadrant block character per 2x2 pixels
+//
+// No GPU or window is involved, so tests compare frames directly.
+
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+/// Quadrant characters indexed by bits: 1 top-left, 2 top-right,
+/// 4 bottom-left, 8 bottom-right
+const QUADRANTS: [char; 16] = [
+    ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛',
+    '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█',
+];
+
+impl Frame {
+    /// Blank frame
+    pub fn new(width: usize, height: usize) -> Self {
+        Frame { width, height, pixels: vec![false; width * height] }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Pixel at (x, y); outside the frame is off
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, on: bool) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = on;
+        }
+    }
+
+    /// Number of pixels lit
+    pub fn lit(&self) -> usize {
+        self.pixels.iter().filter(|&&p| p).count()
+    }
+
+    /// Turned a quarter turn counter-clockwise (for monitors mounted on
+    /// their side)
+    pub fn rotated_ccw(&self) -> Frame {
+        let mut out = Frame::new(self.height, self.width);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                out.set(y, self.width - 1 - x, self.get(x, y));
+            }
+        }
+        out
+    }
+
+    /// Binary PPM, white on black
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut out = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        for &p in &self.pixels {
+            let level = if p { 0xFF } else { 0x00 };
+            out.extend_from_slice(&[level; 3]);
+        }
+        out
+    }
+
+    /// PNG, 8-bit grayscale
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut raw = Vec::with_capacity((self.width + 1) * self.height);
+        for row in self.pixels.chunks(self.width.max(1)) {
+            raw.push(0);                    // Filter: none
+            raw.extend(row.iter().map(|&p| if p { 0xFF } else { 0x00 }));
+        }
+
+        let mut ihdr = Vec::with_capacity(13);
+        ihdr.extend_from_slice(&(self.width as u32).to_be_bytes());
+        ihdr.extend_from_slice(&(self.height as u32).to_be_bytes());
+        ihdr.extend_from_slice(&[8, 0, 0, 0, 0]);   // Depth 8, grayscale
+
+        let mut out = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        png_chunk(&mut out, b"IHDR", &ihdr);
+        png_chunk(&mut out, b"IDAT", &zlib_stored(&raw));
+        png_chunk(&mut out, b"IEND", &[]);
+        out
+    }
+
+    /// Terminal rendering, lines separated by '\n'
+    pub fn to_blocks(&self) -> String {
+        let mut out = String::new();
+        for y in (0..self.height).step_by(2) {
+            if y > 0 {
+                out.push('\n');
+            }
+            for x in (0..self.width).step_by(2) {
+                let index = self.get(x, y) as usize
+                    | (self.get(x + 1, y) as usize) << 1
+                    | (self.get(x, y + 1) as usize) << 2
+                    | (self.get(x + 1, y + 1) as usize) << 3;
+                out.push(QUADRANTS[index]);
+            }
+        }
+        out
+    }
+
+    /// Write a snapshot; .png is PNG, anything else PPM
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let is_png = path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("png"));
+        std::fs::write(path, if is_png { self.to_png() } else { self.to_ppm() })
+    }
+}
+
+fn png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// zlib stream of uncompressed deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xFFFF).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(blocks.peek().is_none() as u8);    // BFINAL, type 00
+        let len = block.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagonal(size: usize) -> Frame {
+        let mut frame = Frame::new(size, size);
+        for i in 0..size {
+            frame.set(i, i, true);
+        }
+        frame
+    }
+
+    #[test]
+    fn test_ppm() {
+        let mut frame = Frame::new(2, 1);
+        frame.set(1, 0, true);
+        let ppm = frame.to_ppm();
+        assert!(ppm.starts_with(b"P6\n2 1\n255\n"));
+        assert_eq!(&ppm[ppm.len() - 6..], &[0, 0, 0, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_png_structure() {
+        let png = diagonal(4).to_png();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], &[0, 0, 0, 4, 0, 0, 0, 4]);
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+        // Well-known CRC of an empty IEND chunk
+        assert_eq!(&png[png.len() - 4..], &[0xAE, 0x42, 0x60, 0x82]);
+    }
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn test_blocks() {
+        assert_eq!(diagonal(4).to_blocks(), "▚ \n ▚");
+        let mut frame = Frame::new(3, 3);
+        frame.set(2, 2, true);
+        assert_eq!(frame.to_blocks(), "  \n ▘");
+    }
+
+    #[test]
+    fn test_rotated_ccw() {
+        let mut frame = Frame::new(3, 2);
+        frame.set(0, 0, true);              // Top-left goes to bottom-left
+        frame.set(2, 1, true);              // Bottom-right goes to top-right
+        let rotated = frame.rotated_ccw();
+        assert_eq!((rotated.width(), rotated.height()), (2, 3));
+        assert!(rotated.get(0, 2));
+        assert!(rotated.get(1, 0));
+        assert_eq!(rotated.lit(), 2);
+    }
+}
@@ -0,0 +1,140 @@
+// wav.rs - Mono 16-bit PCM WAV files
+//
+// Emulated audio hardware renders into a Wav; CI has no sound card, so
+// the file is the sink tests check.
+//
+// Reading accepts 8-bit unsigned or 16-bit signed PCM with any number of
+// channels; only the first channel is kept (tape recordings are mono in
+// spirit even when the capture wasn't).
+
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wav {
+    pub sample_rate: u32,
+    pub samples: Vec<i16>,
+}
+
+impl Wav {
+    pub fn new(sample_rate: u32) -> Self {
+        Wav { sample_rate, samples: Vec::new() }
+    }
+
+    /// Length in seconds
+    pub fn duration(&self) -> f64 {
+        self.samples.len() as f64 / self.sample_rate as f64
+    }
+
+    /// RIFF/WAVE bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let data_len = (self.samples.len() * 2) as u32;
+        let mut out = Vec::with_capacity(44 + data_len as usize);
+        out.extend_from_slice(b"RIFF");
+        out.extend_from_slice(&(36 + data_len).to_le_bytes());
+        out.extend_from_slice(b"WAVEfmt ");
+        out.extend_from_slice(&16u32.to_le_bytes());
+        out.extend_from_slice(&1u16.to_le_bytes());         // PCM
+        out.extend_from_slice(&1u16.to_le_bytes());         // Mono
+        out.extend_from_slice(&self.sample_rate.to_le_bytes());
+        out.extend_from_slice(&(self.sample_rate * 2).to_le_bytes());
+        out.extend_from_slice(&2u16.to_le_bytes());         // Block align
+        out.extend_from_slice(&16u16.to_le_bytes());        // Bits per sample
+        out.extend_from_slice(b"data");
+        out.extend_from_slice(&data_len.to_le_bytes());
+        for sample in &self.samples {
+            out.extend_from_slice(&sample.to_le_bytes());
+        }
+        out
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+
+    /// Parse RIFF/WAVE bytes
+    pub fn parse(bytes: &[u8]) -> io::Result<Wav> {
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+            return Err(invalid("not a RIFF/WAVE file"));
+        }
+        let mut format = None;              // (channels, sample rate, bits)
+        let mut pos = 12;
+        while pos + 8 <= bytes.len() {
+            let id = &bytes[pos..pos + 4];
+            let len = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().unwrap()) as usize;
+            let body = &bytes[pos + 8..(pos + 8).saturating_add(len).min(bytes.len())];
+            match id {
+                b"fmt " if body.len() >= 16 => {
+                    let word = |i: usize| u16::from_le_bytes([body[i], body[i + 1]]);
+                    if word(0) != 1 {
+                        return Err(invalid("WAV is not PCM"));
+                    }
+                    let rate = u32::from_le_bytes(body[4..8].try_into().unwrap());
+                    format = Some((word(2).max(1) as usize, rate, word(14)));
+                }
+                b"data" => {
+                    let (channels, sample_rate, bits) =
+                        format.ok_or_else(|| invalid("WAV data before fmt chunk"))?;
+                    let samples = match bits {
+                        8 => body.chunks_exact(channels)
+                            .map(|frame| ((frame[0] as i16) - 0x80) << 8)
+                            .collect(),
+                        16 => body.chunks_exact(2 * channels)
+                            .map(|frame| i16::from_le_bytes([frame[0], frame[1]]))
+                            .collect(),
+                        _ => return Err(invalid("WAV must be 8 or 16 bits per sample")),
+                    };
+                    return Ok(Wav { sample_rate, samples });
+                }
+                _ => {}
+            }
+            pos += 8 + len + (len & 1);     // Chunks are word aligned
+        }
+        Err(invalid("WAV has no data chunk"))
+    }
+
+    pub fn load(path: &Path) -> io::Result<Wav> {
+        Wav::parse(&std::fs::read(path)?)
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_and_data() {
+        let wav = Wav { sample_rate: 8000, samples: vec![0x1234, -1] };
+        let bytes = wav.to_bytes();
+        assert_eq!(bytes.len(), 48);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(&bytes[4..8], &40u32.to_le_bytes());
+        assert_eq!(&bytes[24..28], &8000u32.to_le_bytes());
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(&bytes[44..], &[0x34, 0x12, 0xFF, 0xFF]);
+        assert_eq!(wav.duration(), 2.0 / 8000.0);
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        let wav = Wav { sample_rate: 22050, samples: vec![0, 100, -100, i16::MAX] };
+        assert_eq!(Wav::parse(&wav.to_bytes()).unwrap(), wav);
+        assert!(Wav::parse(b"RIFF\0\0\0\0WAVE").is_err());
+        assert!(Wav::parse(b"not a wav").is_err());
+    }
+
+    #[test]
+    fn test_parse_8bit_stereo() {
+        // fmt: PCM, 2 channels, 8000 Hz, 8 bits; a LIST chunk to skip
+        let mut bytes = b"RIFF\0\0\0\0WAVEfmt \x10\0\0\0\x01\0\x02\0\x40\x1f\0\0".to_vec();
+        bytes.extend_from_slice(b"\x80\x3e\0\0\x02\0\x08\0LIST\x03\0\0\0abc\0");
+        bytes.extend_from_slice(b"data\x04\0\0\0\xff\x00\x00\xff");
+        let wav = Wav::parse(&bytes).unwrap();
+        assert_eq!(wav.sample_rate, 8000);
+        assert_eq!(wav.samples, vec![0x7F00, -0x8000]);
+    }
+}
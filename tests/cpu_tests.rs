@@ -1,3 +1,5 @@
+#![allow(clippy::bool_assert_comparison, clippy::unnecessary_cast)]
+
 use intel8080_emu::cpu::Intel8080;
 use intel8080_emu::registers::*;
 